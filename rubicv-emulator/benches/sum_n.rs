@@ -0,0 +1,49 @@
+// `cargo bench -p rubicv-emulator --bench sum_n`
+//
+// Times the sum_n program that test_sum_program runs (test_data/output.bin)
+// under `run` and `run_threaded`, and checks both leave the same sum in
+// scratch. The two alternate within each round so host noise hits both, and
+// the best round of each is reported.
+use std::time::{Duration, Instant};
+use rubicv_emulator::instructions::PredecodedProgram;
+use rubicv_emulator::memory::*;
+use rubicv_emulator::syscalls::NoSyscalls;
+use rubicv_emulator::vm::{ExecutionResult, ThreadedCode, VM};
+
+const N: u32 = 5_000_000;
+const ROUNDS: usize = 30;
+
+// Wall time of one run on fresh memory, with the sum and instruction count
+fn time(program: &PredecodedProgram, run: impl Fn(&mut VM) -> ExecutionResult) -> (Duration, u32, usize) {
+    let mut memory = vec![0u8; MEMORY_SIZE as usize].into_boxed_slice();
+    let at = ARGS_START as usize;
+    memory[at..at + 4].copy_from_slice(&N.to_le_bytes());
+    let mut vm = VM::new(memory.as_mut() as *mut [u8], program.entrypoint, &program.instructions);
+    let start = Instant::now();
+    let result = run(&mut vm);
+    let elapsed = start.elapsed();
+    assert_eq!(result, ExecutionResult::Success(0));
+    (elapsed, vm.read_u32(SCRATCH_START), vm.cycle_count)
+}
+
+fn main() {
+    let program = PredecodedProgram::new(include_bytes!("../src/vm/tests/test_data/output.bin")).unwrap();
+    let code = ThreadedCode::new(&program.instructions);
+
+    let (mut interpreted, mut threaded) = (Duration::MAX, Duration::MAX);
+    let mut cycles = 0;
+    for _ in 0..ROUNDS {
+        let (elapsed, sum, count) = time(&program, |vm| vm.run(None));
+        interpreted = interpreted.min(elapsed);
+        let (elapsed, threaded_sum, threaded_count) = time(&program, |vm| vm.run_threaded(&code, &mut NoSyscalls, None));
+        threaded = threaded.min(elapsed);
+        assert_eq!((threaded_sum, threaded_count), (sum, count));
+        cycles = count;
+    }
+
+    let rate = |elapsed: Duration| cycles as f64 / elapsed.as_secs_f64() / 1e6;
+    println!("sum_n({}): {} instructions, best of {}", N, cycles, ROUNDS);
+    println!("run           {:>8.2?}  {:>6.1} M instructions/s", interpreted, rate(interpreted));
+    println!("run_threaded  {:>8.2?}  {:>6.1} M instructions/s", threaded, rate(threaded));
+    println!("speedup       {:.2}x", interpreted.as_secs_f64() / threaded.as_secs_f64());
+}
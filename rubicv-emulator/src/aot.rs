@@ -0,0 +1,398 @@
+use alloc::string::String;
+use core::fmt::Write;
+use crate::disasm::Disasm;
+use crate::errors::RubicVError;
+use crate::instructions::{InsnKind, PreDecodedInstruction, PredecodedProgram};
+use crate::memory::*;
+use crate::syscalls::*;
+use crate::vm::ExecutionResult;
+
+// Ahead-of-time translation of a predecoded program to Rust source. The
+// generated module is compiled into the host and exposes
+//
+//   pub fn run(state: &mut VmState, max_fuel: Option<u64>) -> ExecutionResult
+//   pub fn run_with_syscalls(state, syscalls, max_fuel) -> ExecutionResult
+//
+// Each basic block becomes one match arm of straight-line code, and each
+// instruction an arm of its own for when fuel runs short or control lands
+// mid-block. Program::run meters, traps and masks memory like VM::run, so
+// results, registers, cycle counts and fuel match the interpreter.
+
+// The guest side of a translated program. memory_slab must span
+// MEMORY_SIZE bytes; reads wrap at MEMORY_SIZE and writes at RW_SIZE.
+pub struct VmState {
+    pub registers: [u32; 32],
+    pub cycle_count: usize, // instructions retired
+    pub fuel_used: u64,
+    pub ppc: usize,
+    pub memory_slab: *mut [u8],
+}
+
+impl VmState {
+    pub fn new(memory_slab: *mut [u8], entry_point: usize) -> Self {
+        let mut registers = [0; 32];
+        registers[2] = STACK_START;
+        Self { registers, cycle_count: 0, fuel_used: 0, ppc: entry_point, memory_slab }
+    }
+
+    // What a guest load of `size` bytes reads, misaligned or not
+    #[inline(always)]
+    pub fn load(&self, addr: u32, size: u8) -> u32 {
+        let slab = self.memory_slab as *const u8;
+        if addr & (size as u32 - 1) != 0 {
+            return (0..size as u32).fold(0, |value, i| {
+                value | (unsafe { *slab.add((addr.wrapping_add(i) & MEMORY_MASK) as usize) } as u32) << (8 * i)
+            });
+        }
+        unsafe {
+            let ptr = slab.add((addr & MEMORY_MASK) as usize);
+            match size {
+                1 => *ptr as u32,
+                2 => (ptr as *const u16).read_unaligned() as u32,
+                _ => (ptr as *const u32).read_unaligned(),
+            }
+        }
+    }
+
+    #[inline(always)]
+    pub fn store(&mut self, addr: u32, size: u8, value: u32) {
+        let slab = self.memory_slab as *mut u8;
+        if addr & (size as u32 - 1) != 0 {
+            for i in 0..size as u32 {
+                unsafe { *slab.add((addr.wrapping_add(i) & RW_MASK) as usize) = (value >> (8 * i)) as u8 };
+            }
+            return;
+        }
+        unsafe {
+            let ptr = slab.add((addr & RW_MASK) as usize);
+            match size {
+                1 => *ptr = value as u8,
+                2 => (ptr as *mut u16).write_unaligned(value as u16),
+                _ => (ptr as *mut u32).write_unaligned(value),
+            }
+        }
+    }
+
+    // Same as VM::handle_trap, without hooks
+    fn handle_trap<S: SyscallHandler + ?Sized>(&mut self, trap: RubicVError, syscalls: &mut S) -> Option<ExecutionResult> {
+        match trap {
+            RubicVError::SystemCall(syscall) => {
+                self.cycle_count += 1;
+                if syscall == SYS_EXIT {
+                    return Some(ExecutionResult::Success(self.registers[REG_A0]));
+                }
+                let mut ctx = SyscallContext::new(&mut self.registers, self.memory_slab, self.memory_slab);
+                match syscalls.handle(syscall, &mut ctx) {
+                    Ok(SyscallResult::Continue) => {
+                        self.ppc += 1;
+                        None
+                    },
+                    Ok(SyscallResult::Yield) => {
+                        self.ppc += 1;
+                        Some(ExecutionResult::Yield(syscall))
+                    },
+                    Ok(SyscallResult::Halt(code)) => Some(ExecutionResult::Success(code)),
+                    Err(e) => Some(ExecutionResult::Error(e)),
+                }
+            },
+            RubicVError::Breakpoint => {
+                self.cycle_count += 1;
+                self.ppc += 1;
+                Some(ExecutionResult::Breakpoint)
+            },
+            e => Some(ExecutionResult::Error(e)),
+        }
+    }
+}
+
+// Fuel for one instruction, as in its PreDecodedInstruction
+pub struct Meter {
+    pub cost: u32,
+    pub block_cost: u32,
+    pub block_len: u16,
+}
+
+// Runs generated code from the ppc: a whole block or one instruction. Only
+// the last instruction of a block can trap, and it leaves the ppc on itself.
+pub type Code = fn(&mut VmState, usize) -> Result<(), RubicVError>;
+
+pub struct Program {
+    pub meters: &'static [Meter],
+    pub block: Code,
+    pub step: Code,
+}
+
+impl Program {
+    pub fn run(&self, state: &mut VmState, max_fuel: Option<u64>) -> ExecutionResult {
+        self.run_with_syscalls(state, &mut NoSyscalls, max_fuel)
+    }
+
+    // Metered like VM::resume: a block whose rest fits in the budget is paid
+    // up front, otherwise instructions are charged one at a time. Blocks
+    // entered mid-way run their instructions one arm at a time.
+    pub fn run_with_syscalls<S: SyscallHandler + ?Sized>(&self, state: &mut VmState, syscalls: &mut S, max_fuel: Option<u64>) -> ExecutionResult {
+        let fuel_limit = match max_fuel {
+            Some(max) => state.fuel_used.saturating_add(max),
+            None => u64::MAX,
+        };
+        loop {
+            let ppc = state.ppc;
+            let Some(meter) = self.meters.get(ppc) else {
+                return ExecutionResult::Error(RubicVError::InvalidInstruction);
+            };
+            let remaining = fuel_limit - state.fuel_used;
+            let (charge, steps) = if meter.block_cost as u64 <= remaining {
+                (meter.block_cost as u64, meter.block_len as usize)
+            } else if meter.cost as u64 <= remaining {
+                (meter.cost as u64, 1)
+            } else {
+                return ExecutionResult::CycleLimitExceeded;
+            };
+            state.fuel_used += charge;
+            state.cycle_count += steps;
+
+            let leader = ppc == 0 || self.meters[ppc - 1].block_len == 1;
+            let result = if steps > 1 && leader {
+                (self.block)(state, ppc)
+            } else {
+                self.steps(state, steps)
+            };
+            if let Err(trap) = result {
+                // Hand back what was charged for the unexecuted rest of the block
+                let meter = &self.meters[state.ppc];
+                if steps > 1 {
+                    state.fuel_used -= (meter.block_cost - meter.cost) as u64;
+                    state.cycle_count -= meter.block_len as usize;
+                } else {
+                    state.cycle_count -= 1;
+                }
+                if let Some(result) = state.handle_trap(trap, syscalls) {
+                    return result;
+                }
+            }
+        }
+    }
+
+    fn steps(&self, state: &mut VmState, steps: usize) -> Result<(), RubicVError> {
+        for _ in 0..steps {
+            (self.step)(state, state.ppc)?;
+        }
+        Ok(())
+    }
+}
+
+// Division and remainder with the RISC-V results for a zero divisor and
+// for i32::MIN / -1, for generated code
+#[inline(always)]
+pub fn div(a: u32, b: u32) -> u32 {
+    if b == 0 { u32::MAX } else { (a as i32).wrapping_div(b as i32) as u32 }
+}
+
+#[inline(always)]
+pub fn divu(a: u32, b: u32) -> u32 {
+    a.checked_div(b).unwrap_or(u32::MAX)
+}
+
+#[inline(always)]
+pub fn rem(a: u32, b: u32) -> u32 {
+    if b == 0 { a } else { (a as i32).wrapping_rem(b as i32) as u32 }
+}
+
+#[inline(always)]
+pub fn remu(a: u32, b: u32) -> u32 {
+    a.checked_rem(b).unwrap_or(a)
+}
+
+// Source of a module for `program`, see the top of this file. The module
+// refers to this crate as `rubicv_emulator`.
+pub fn translate(program: &PredecodedProgram) -> String {
+    let len = program.instructions.len();
+    let original: alloc::vec::Vec<PreDecodedInstruction> = (0..len).map(|ppc| program.original(ppc).unwrap()).collect();
+    let mut out = String::new();
+    let _ = translate_into(&mut out, program, &original);
+    out
+}
+
+fn translate_into(out: &mut String, program: &PredecodedProgram, original: &[PreDecodedInstruction]) -> core::fmt::Result {
+    let len = original.len();
+    writeln!(out, "// Generated by `utils aot`, do not edit")?;
+    writeln!(out, "use rubicv_emulator::aot::{{Meter, Program, VmState}};")?;
+    writeln!(out, "use rubicv_emulator::errors::RubicVError;")?;
+    writeln!(out, "use rubicv_emulator::syscalls::SyscallHandler;")?;
+    writeln!(out, "use rubicv_emulator::vm::ExecutionResult;")?;
+    writeln!(out)?;
+    writeln!(out, "pub const ENTRYPOINT: usize = {};", program.entrypoint)?;
+    writeln!(out)?;
+    writeln!(out, "pub static PROGRAM: Program = Program {{ meters: &METERS, block, step }};")?;
+    writeln!(out)?;
+    writeln!(out, "pub fn run(state: &mut VmState, max_fuel: Option<u64>) -> ExecutionResult {{")?;
+    writeln!(out, "    PROGRAM.run(state, max_fuel)")?;
+    writeln!(out, "}}")?;
+    writeln!(out)?;
+    writeln!(out, "pub fn run_with_syscalls<S: SyscallHandler + ?Sized>(state: &mut VmState, syscalls: &mut S, max_fuel: Option<u64>) -> ExecutionResult {{")?;
+    writeln!(out, "    PROGRAM.run_with_syscalls(state, syscalls, max_fuel)")?;
+    writeln!(out, "}}")?;
+    writeln!(out)?;
+    writeln!(out, "static METERS: [Meter; {}] = [", len)?;
+    for insn in &program.instructions {
+        writeln!(out, "    Meter {{ cost: {}, block_cost: {}, block_len: {} }},", insn.cost, insn.block_cost, insn.block_len)?;
+    }
+    writeln!(out, "];")?;
+
+    // Whole blocks, from their leader
+    writeln!(out)?;
+    // Straight-line code compares registers with themselves and so on
+    writeln!(out, "#[allow(clippy::all)]")?;
+    writeln!(out, "fn block(s: &mut VmState, ppc: usize) -> Result<(), RubicVError> {{")?;
+    writeln!(out, "    match ppc {{")?;
+    let mut ppc = 0;
+    while ppc < len {
+        let block_len = program.instructions[ppc].block_len as usize;
+        if block_len > 1 {
+            writeln!(out, "        {} => {{", ppc)?;
+            for (i, insn) in original.iter().enumerate().skip(ppc).take(block_len) {
+                write_insn(out, i, insn, i + 1 == ppc + block_len, len)?;
+            }
+            writeln!(out, "        }}")?;
+        }
+        ppc += block_len;
+    }
+    writeln!(out, "        _ => unreachable!(),")?;
+    writeln!(out, "    }}")?;
+    writeln!(out, "}}")?;
+
+    // Single instructions
+    writeln!(out)?;
+    writeln!(out, "#[allow(clippy::all)]")?;
+    writeln!(out, "fn step(s: &mut VmState, ppc: usize) -> Result<(), RubicVError> {{")?;
+    writeln!(out, "    match ppc {{")?;
+    for (i, insn) in original.iter().enumerate() {
+        writeln!(out, "        {} => {{", i)?;
+        write_insn(out, i, insn, true, len)?;
+        writeln!(out, "        }}")?;
+    }
+    writeln!(out, "        _ => unreachable!(),")?;
+    writeln!(out, "    }}")?;
+    writeln!(out, "}}")
+}
+
+// One instruction at `ppc`. Only the last of a block moves the ppc and
+// gives the arm its value; control transfers and traps always end a block.
+fn write_insn(out: &mut String, ppc: usize, insn: &PreDecodedInstruction, last: bool, len: usize) -> core::fmt::Result {
+    const INDENT: &str = "            ";
+    writeln!(out, "{}// {:#06x}: {}", INDENT, ppc * 4, Disasm::new(insn))?;
+    let rs1 = alloc::format!("s.registers[{}]", insn.rs1);
+    let rs2 = alloc::format!("s.registers[{}]", insn.rs2);
+    let imm = insn.imm as u32;
+    let shamt = imm & 0x1f;
+    let addr = alloc::format!("{}.wrapping_add({:#x})", rs1, imm);
+    let link = (ppc as u32 + 1) * 4;
+    let value = match insn.kind {
+        InsnKind::ADD => Some(alloc::format!("{}.wrapping_add({})", rs1, rs2)),
+        InsnKind::SUB => Some(alloc::format!("{}.wrapping_sub({})", rs1, rs2)),
+        InsnKind::XOR => Some(alloc::format!("{} ^ {}", rs1, rs2)),
+        InsnKind::OR => Some(alloc::format!("{} | {}", rs1, rs2)),
+        InsnKind::AND => Some(alloc::format!("{} & {}", rs1, rs2)),
+        InsnKind::SLL => Some(alloc::format!("{} << ({} & 0x1f)", rs1, rs2)),
+        InsnKind::SRL => Some(alloc::format!("{} >> ({} & 0x1f)", rs1, rs2)),
+        InsnKind::SRA => Some(alloc::format!("(({} as i32) >> ({} & 0x1f)) as u32", rs1, rs2)),
+        InsnKind::SLT => Some(alloc::format!("(({} as i32) < ({} as i32)) as u32", rs1, rs2)),
+        InsnKind::SLTU => Some(alloc::format!("({} < {}) as u32", rs1, rs2)),
+        InsnKind::ADDI => Some(alloc::format!("{}.wrapping_add({:#x})", rs1, imm)),
+        InsnKind::XORI => Some(alloc::format!("{} ^ {:#x}", rs1, imm)),
+        InsnKind::ORI => Some(alloc::format!("{} | {:#x}", rs1, imm)),
+        InsnKind::ANDI => Some(alloc::format!("{} & {:#x}", rs1, imm)),
+        InsnKind::SLLI => Some(alloc::format!("{} << {}", rs1, shamt)),
+        InsnKind::SRLI => Some(alloc::format!("{} >> {}", rs1, shamt)),
+        InsnKind::SRAI => Some(alloc::format!("(({} as i32) >> {}) as u32", rs1, shamt)),
+        InsnKind::SLTI => Some(alloc::format!("(({} as i32) < {}) as u32", rs1, insn.imm)),
+        InsnKind::SLTIU => Some(alloc::format!("({} < {:#x}) as u32", rs1, imm)),
+        InsnKind::LUI => Some(alloc::format!("{:#x}", imm)),
+        InsnKind::AUIPC => Some(alloc::format!("{:#x}", (ppc as u32 * 4).wrapping_add(imm))),
+        InsnKind::MUL => Some(alloc::format!("{}.wrapping_mul({})", rs1, rs2)),
+        InsnKind::MULH => Some(alloc::format!("(({} as i32 as i64).wrapping_mul({} as i32 as i64) >> 32) as u32", rs1, rs2)),
+        InsnKind::MULHSU => Some(alloc::format!("(({} as i32 as i64).wrapping_mul({} as i64) >> 32) as u32", rs1, rs2)),
+        InsnKind::MULHU => Some(alloc::format!("(({} as u64).wrapping_mul({} as u64) >> 32) as u32", rs1, rs2)),
+        InsnKind::DIV => Some(alloc::format!("rubicv_emulator::aot::div({}, {})", rs1, rs2)),
+        InsnKind::DIVU => Some(alloc::format!("rubicv_emulator::aot::divu({}, {})", rs1, rs2)),
+        InsnKind::REM => Some(alloc::format!("rubicv_emulator::aot::rem({}, {})", rs1, rs2)),
+        InsnKind::REMU => Some(alloc::format!("rubicv_emulator::aot::remu({}, {})", rs1, rs2)),
+        InsnKind::LB => Some(alloc::format!("s.load({}, 1) as u8 as i8 as u32", addr)),
+        InsnKind::LH => Some(alloc::format!("s.load({}, 2) as u16 as i16 as u32", addr)),
+        InsnKind::LW => Some(alloc::format!("s.load({}, 4)", addr)),
+        InsnKind::LBU => Some(alloc::format!("s.load({}, 1)", addr)),
+        InsnKind::LHU => Some(alloc::format!("s.load({}, 2)", addr)),
+        _ => None,
+    };
+    if let Some(value) = value {
+        // Writes to x0 were made nops by the predecoder
+        if insn.rd != 0 {
+            writeln!(out, "{}s.registers[{}] = {};", INDENT, insn.rd, value)?;
+        }
+        if last {
+            writeln!(out, "{}s.ppc = {};", INDENT, ppc + 1)?;
+            writeln!(out, "{}Ok(())", INDENT)?;
+        }
+        return Ok(());
+    }
+    let condition = match insn.kind {
+        InsnKind::BEQ => Some(alloc::format!("{} == {}", rs1, rs2)),
+        InsnKind::BNE => Some(alloc::format!("{} != {}", rs1, rs2)),
+        InsnKind::BLT => Some(alloc::format!("({} as i32) < ({} as i32)", rs1, rs2)),
+        InsnKind::BGE => Some(alloc::format!("({} as i32) >= ({} as i32)", rs1, rs2)),
+        InsnKind::BLTU => Some(alloc::format!("{} < {}", rs1, rs2)),
+        InsnKind::BGEU => Some(alloc::format!("{} >= {}", rs1, rs2)),
+        _ => None,
+    };
+    if let Some(condition) = condition {
+        writeln!(out, "{}s.ppc = if {} {{ {} }} else {{ {} }};", INDENT, condition, insn.imm, ppc + 1)?;
+        return writeln!(out, "{}Ok(())", INDENT);
+    }
+    match insn.kind {
+        InsnKind::SB | InsnKind::SH | InsnKind::SW => {
+            let size = match insn.kind { InsnKind::SB => 1, InsnKind::SH => 2, _ => 4 };
+            writeln!(out, "{}s.store({}, {}, {});", INDENT, addr, size, rs2)?;
+            if last {
+                writeln!(out, "{}s.ppc = {};", INDENT, ppc + 1)?;
+                writeln!(out, "{}Ok(())", INDENT)?;
+            }
+            Ok(())
+        },
+        // Probes cannot fault with wrapping memory
+        InsnKind::PROBE => {
+            if last {
+                writeln!(out, "{}s.ppc = {};", INDENT, ppc + 1)?;
+                writeln!(out, "{}Ok(())", INDENT)?;
+            }
+            Ok(())
+        },
+        InsnKind::JAL => {
+            if insn.rd != 0 {
+                writeln!(out, "{}s.registers[{}] = {:#x};", INDENT, insn.rd, link)?;
+            }
+            writeln!(out, "{}s.ppc = {};", INDENT, insn.imm)?;
+            writeln!(out, "{}Ok(())", INDENT)
+        },
+        InsnKind::JALR => {
+            // Targets past the end land on the trailing sentinel
+            writeln!(out, "{}let target = {} & !1;", INDENT, addr)?;
+            if insn.rd != 0 {
+                writeln!(out, "{}s.registers[{}] = {:#x};", INDENT, insn.rd, link)?;
+            }
+            writeln!(out, "{}s.ppc = ((target / 4) as usize).min({});", INDENT, len - 1)?;
+            writeln!(out, "{}Ok(())", INDENT)
+        },
+        InsnKind::ECALL => {
+            writeln!(out, "{}s.ppc = {};", INDENT, ppc)?;
+            writeln!(out, "{}Err(RubicVError::SystemCall(s.registers[{}]))", INDENT, REG_A7)
+        },
+        InsnKind::EBREAK => {
+            writeln!(out, "{}s.ppc = {};", INDENT, ppc)?;
+            writeln!(out, "{}Err(RubicVError::Breakpoint)", INDENT)
+        },
+        _ => {
+            writeln!(out, "{}s.ppc = {};", INDENT, ppc)?;
+            writeln!(out, "{}Err(RubicVError::IllegalInstruction)", INDENT)
+        },
+    }
+}
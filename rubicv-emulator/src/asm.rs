@@ -0,0 +1,314 @@
+use alloc::collections::BTreeMap;
+use alloc::vec::Vec;
+use crate::disasm::ABI_NAMES;
+use crate::errors::RubicVError;
+use crate::instructions::{InsnKind, RV32IM_ISA};
+
+// `unimp`, which the decoder maps to InsnKind::INVALID
+pub const UNIMP: u32 = 0xC000_1073;
+
+// Encodes one instruction. Branch and JAL offsets are in bytes relative to
+// the instruction itself; LUI and AUIPC take the full upper value, as found
+// in PreDecodedInstruction::imm. Immediates are truncated to their field,
+// use `imm_fits` to check them first.
+pub fn encode(kind: InsnKind, rd: u8, rs1: u8, rs2: u8, imm: i32) -> u32 {
+    // Fused kinds and PROBE have no encoding of their own
+    if kind.is_fused() || kind == InsnKind::PROBE {
+        return UNIMP;
+    }
+    let spec = RV32IM_ISA[kind as usize];
+    let (opcode, func3, func7) = (spec.opcode, spec.func3 & 0x7, spec.func7 & 0x7F);
+    let (rd, rs1, rs2) = ((rd as u32 & 0x1F) << 7, (rs1 as u32 & 0x1F) << 15, (rs2 as u32 & 0x1F) << 20);
+    let imm = imm as u32;
+    match kind {
+        InsnKind::INVALID | InsnKind::LUI_ADDI | InsnKind::AUIPC_JALR | InsnKind::ADDI_BNE | InsnKind::SLLI_ADD
+        | InsnKind::PROBE => UNIMP,
+        InsnKind::ECALL => 0x0000_0073,
+        InsnKind::EBREAK => 0x0010_0073,
+        InsnKind::ADD | InsnKind::SUB | InsnKind::XOR | InsnKind::OR | InsnKind::AND
+        | InsnKind::SLL | InsnKind::SRL | InsnKind::SRA | InsnKind::SLT | InsnKind::SLTU
+        | InsnKind::MUL | InsnKind::MULH | InsnKind::MULHSU | InsnKind::MULHU
+        | InsnKind::DIV | InsnKind::DIVU | InsnKind::REM | InsnKind::REMU => {
+            (func7 << 25) | rs2 | rs1 | (func3 << 12) | rd | opcode
+        }
+        InsnKind::SLLI | InsnKind::SRLI | InsnKind::SRAI => {
+            (func7 << 25) | ((imm & 0x1F) << 20) | rs1 | (func3 << 12) | rd | opcode
+        }
+        InsnKind::ADDI | InsnKind::XORI | InsnKind::ORI | InsnKind::ANDI
+        | InsnKind::SLTI | InsnKind::SLTIU | InsnKind::JALR
+        | InsnKind::LB | InsnKind::LH | InsnKind::LW | InsnKind::LBU | InsnKind::LHU => {
+            ((imm & 0xFFF) << 20) | rs1 | (func3 << 12) | rd | opcode
+        }
+        InsnKind::SB | InsnKind::SH | InsnKind::SW => {
+            (((imm >> 5) & 0x7F) << 25) | rs2 | rs1 | (func3 << 12) | ((imm & 0x1F) << 7) | opcode
+        }
+        InsnKind::BEQ | InsnKind::BNE | InsnKind::BLT | InsnKind::BGE
+        | InsnKind::BLTU | InsnKind::BGEU => {
+            (((imm >> 12) & 1) << 31) | (((imm >> 5) & 0x3F) << 25) | rs2 | rs1 | (func3 << 12)
+                | (((imm >> 1) & 0xF) << 8) | (((imm >> 11) & 1) << 7) | opcode
+        }
+        InsnKind::JAL => {
+            (((imm >> 20) & 1) << 31) | (((imm >> 1) & 0x3FF) << 21) | (((imm >> 11) & 1) << 20)
+                | (imm & 0xFF000) | rd | opcode
+        }
+        InsnKind::LUI | InsnKind::AUIPC => (imm & 0xFFFF_F000) | rd | opcode,
+    }
+}
+
+// Whether `imm` survives encoding for `kind` unchanged
+pub fn imm_fits(kind: InsnKind, imm: i32) -> bool {
+    match kind {
+        InsnKind::SLLI | InsnKind::SRLI | InsnKind::SRAI => (0..32).contains(&imm),
+        InsnKind::ADDI | InsnKind::XORI | InsnKind::ORI | InsnKind::ANDI
+        | InsnKind::SLTI | InsnKind::SLTIU | InsnKind::JALR
+        | InsnKind::LB | InsnKind::LH | InsnKind::LW | InsnKind::LBU | InsnKind::LHU
+        | InsnKind::SB | InsnKind::SH | InsnKind::SW => (-2048..2048).contains(&imm),
+        InsnKind::BEQ | InsnKind::BNE | InsnKind::BLT | InsnKind::BGE
+        | InsnKind::BLTU | InsnKind::BGEU => imm % 2 == 0 && (-4096..4096).contains(&imm),
+        InsnKind::JAL => imm % 2 == 0 && (-(1 << 20)..(1 << 20)).contains(&imm),
+        InsnKind::LUI | InsnKind::AUIPC => imm & 0xFFF == 0,
+        _ => imm == 0,
+    }
+}
+
+// Assembles RV32IM source into a RubicV binary, `[entry][text]` with the
+// text loaded at address 0.
+//
+// One instruction per line, optionally preceded by `label:`. Comments start
+// with `#` or `//`. Registers are xN or ABI names. Branch and jump targets
+// are labels or absolute addresses. Supported pseudo-instructions are
+// li, mv, j, ret, call and nop; `.word` emits data and `.text`/`.globl` are
+// accepted and ignored. Execution starts at `_start`, or at 0 without one.
+pub fn assemble(source: &str) -> Result<Vec<u8>, RubicVError> {
+    // Pass 1: addresses of labels and of every statement
+    let mut labels = BTreeMap::new();
+    let mut statements = Vec::new();
+    let mut pc = 0u32;
+    for (i, line) in source.lines().enumerate() {
+        let line_no = i + 1;
+        let err = |reason| RubicVError::AssemblerError { line: line_no, reason };
+        let mut rest = strip_comment(line).trim();
+        while let Some((label, tail)) = split_label(rest) {
+            if labels.insert(label, pc).is_some() {
+                return Err(err("duplicate label"));
+            }
+            rest = tail.trim();
+        }
+        if rest.is_empty() {
+            continue;
+        }
+        let (mnemonic, operands) = match rest.split_once(char::is_whitespace) {
+            Some((m, ops)) => (m, split_operands(ops)),
+            None => (rest, Vec::new()),
+        };
+        let words = match mnemonic {
+            ".text" | ".globl" | ".global" => continue,
+            ".word" => operands.len() as u32,
+            "li" => li_expansion(&operands).map_err(err)?.len() as u32,
+            m if m.starts_with('.') => return Err(err("unknown directive")),
+            _ => 1,
+        };
+        statements.push(Statement { line: line_no, pc, mnemonic, operands });
+        pc += words * 4;
+    }
+
+    // Pass 2: encode
+    let mut words = Vec::with_capacity(pc as usize / 4);
+    for statement in &statements {
+        statement.encode(&labels, &mut words)
+            .map_err(|reason| RubicVError::AssemblerError { line: statement.line, reason })?;
+    }
+
+    let entry = labels.get("_start").copied().unwrap_or(0);
+    let mut bytes = Vec::with_capacity(4 + words.len() * 4);
+    bytes.extend_from_slice(&entry.to_le_bytes());
+    for word in words {
+        bytes.extend_from_slice(&word.to_le_bytes());
+    }
+    Ok(bytes)
+}
+
+struct Statement<'s> {
+    line: usize,
+    pc: u32,
+    mnemonic: &'s str,
+    operands: Vec<&'s str>,
+}
+
+impl Statement<'_> {
+    fn encode(&self, labels: &BTreeMap<&str, u32>, out: &mut Vec<u32>) -> Result<(), &'static str> {
+        let ops = &self.operands;
+        let target = |op: &str| -> Result<i32, &'static str> {
+            let addr = match labels.get(op) {
+                Some(addr) => *addr as i64,
+                None if is_label(op) => return Err("undefined label"),
+                None => parse_int(op)?,
+            };
+            Ok((addr - self.pc as i64) as i32)
+        };
+        let mut emit = |kind, rd, rs1, rs2, imm| {
+            if !imm_fits(kind, imm) {
+                return Err("immediate out of range");
+            }
+            out.push(encode(kind, rd, rs1, rs2, imm));
+            Ok(())
+        };
+
+        match (self.mnemonic, ops.as_slice()) {
+            (".word", values) => {
+                for value in values {
+                    let value = match labels.get(value) {
+                        Some(addr) => *addr,
+                        None => to_i32(parse_int(value)?)? as u32,
+                    };
+                    out.push(value);
+                }
+                Ok(())
+            }
+            ("li", _) => {
+                for (kind, rd, rs1, imm) in li_expansion(ops)? {
+                    emit(kind, rd, rs1, 0, imm)?;
+                }
+                Ok(())
+            }
+            ("nop", []) => emit(InsnKind::ADDI, 0, 0, 0, 0),
+            ("mv", [rd, rs]) => emit(InsnKind::ADDI, reg(rd)?, reg(rs)?, 0, 0),
+            ("j", [t]) => emit(InsnKind::JAL, 0, 0, 0, target(t)?),
+            ("call", [t]) => emit(InsnKind::JAL, 1, 0, 0, target(t)?),
+            ("ret", []) => emit(InsnKind::JALR, 0, 1, 0, 0),
+            ("jal", [t]) => emit(InsnKind::JAL, 1, 0, 0, target(t)?),
+            ("jal", [rd, t]) => emit(InsnKind::JAL, reg(rd)?, 0, 0, target(t)?),
+            ("jalr", [rs1]) => emit(InsnKind::JALR, 1, reg(rs1)?, 0, 0),
+            ("jalr", [rd, rs1, imm]) => emit(InsnKind::JALR, reg(rd)?, reg(rs1)?, 0, to_i32(parse_int(imm)?)?),
+            (mnemonic, ops) => {
+                let kind = kind_for(mnemonic).ok_or("unknown instruction")?;
+                match (kind, ops) {
+                    (InsnKind::ECALL | InsnKind::EBREAK, []) => emit(kind, 0, 0, 0, 0),
+                    (InsnKind::LUI | InsnKind::AUIPC, [rd, imm]) => {
+                        let imm = parse_int(imm)?;
+                        if !(-0x80000..0x100000).contains(&imm) {
+                            return Err("immediate out of range");
+                        }
+                        emit(kind, reg(rd)?, 0, 0, (imm << 12) as i32)
+                    }
+                    (InsnKind::BEQ | InsnKind::BNE | InsnKind::BLT | InsnKind::BGE
+                     | InsnKind::BLTU | InsnKind::BGEU, [rs1, rs2, t]) => {
+                        emit(kind, 0, reg(rs1)?, reg(rs2)?, target(t)?)
+                    }
+                    (InsnKind::LB | InsnKind::LH | InsnKind::LW | InsnKind::LBU | InsnKind::LHU
+                     | InsnKind::JALR, [rd, mem]) => {
+                        let (imm, rs1) = mem_operand(mem)?;
+                        emit(kind, reg(rd)?, rs1, 0, imm)
+                    }
+                    (InsnKind::SB | InsnKind::SH | InsnKind::SW, [rs2, mem]) => {
+                        let (imm, rs1) = mem_operand(mem)?;
+                        emit(kind, 0, rs1, reg(rs2)?, imm)
+                    }
+                    (InsnKind::ADDI | InsnKind::XORI | InsnKind::ORI | InsnKind::ANDI
+                     | InsnKind::SLTI | InsnKind::SLTIU | InsnKind::SLLI | InsnKind::SRLI
+                     | InsnKind::SRAI, [rd, rs1, imm]) => {
+                        emit(kind, reg(rd)?, reg(rs1)?, 0, to_i32(parse_int(imm)?)?)
+                    }
+                    (InsnKind::ADD | InsnKind::SUB | InsnKind::XOR | InsnKind::OR | InsnKind::AND
+                     | InsnKind::SLL | InsnKind::SRL | InsnKind::SRA | InsnKind::SLT | InsnKind::SLTU
+                     | InsnKind::MUL | InsnKind::MULH | InsnKind::MULHSU | InsnKind::MULHU
+                     | InsnKind::DIV | InsnKind::DIVU | InsnKind::REM | InsnKind::REMU, [rd, rs1, rs2]) => {
+                        emit(kind, reg(rd)?, reg(rs1)?, reg(rs2)?, 0)
+                    }
+                    _ => Err("wrong operands"),
+                }
+            }
+        }
+    }
+}
+
+// `li rd, value` as (kind, rd, rs1, imm): one ADDI when the value fits in
+// 12 bits, otherwise LUI plus an ADDI for the low part if it is non-zero
+fn li_expansion(operands: &[&str]) -> Result<Vec<(InsnKind, u8, u8, i32)>, &'static str> {
+    let [rd, value] = operands else {
+        return Err("wrong operands");
+    };
+    let rd = reg(rd)?;
+    let value = to_i32(parse_int(value)?)?;
+    if (-2048..2048).contains(&value) {
+        return Ok(alloc::vec![(InsnKind::ADDI, rd, 0, value)]);
+    }
+    let hi = value.wrapping_add(0x800) & !0xFFF;
+    let lo = value.wrapping_sub(hi);
+    let mut expansion = alloc::vec![(InsnKind::LUI, rd, 0, hi)];
+    if lo != 0 {
+        expansion.push((InsnKind::ADDI, rd, rd, lo));
+    }
+    Ok(expansion)
+}
+
+fn kind_for(mnemonic: &str) -> Option<InsnKind> {
+    RV32IM_ISA.iter()
+        .map(|insn| insn.kind)
+        .find(|kind| *kind != InsnKind::INVALID && kind.mnemonic() == mnemonic)
+}
+
+fn reg(name: &str) -> Result<u8, &'static str> {
+    if let Some(n) = name.strip_prefix('x').and_then(|n| n.parse::<u8>().ok()) {
+        if n < 32 {
+            return Ok(n);
+        }
+    }
+    if name == "fp" {
+        return Ok(8);
+    }
+    ABI_NAMES.iter().position(|abi| *abi == name).map(|r| r as u8).ok_or("unknown register")
+}
+
+// `imm(reg)`, `(reg)` or `imm(reg)` with whitespace
+fn mem_operand(op: &str) -> Result<(i32, u8), &'static str> {
+    let (imm, rest) = op.split_once('(').ok_or("expected imm(reg)")?;
+    let reg_name = rest.strip_suffix(')').ok_or("expected imm(reg)")?;
+    let imm = imm.trim();
+    let imm = if imm.is_empty() { 0 } else { to_i32(parse_int(imm)?)? };
+    Ok((imm, reg(reg_name.trim())?))
+}
+
+fn parse_int(text: &str) -> Result<i64, &'static str> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, text),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        i64::from_str_radix(hex, 16)
+    } else if let Some(bin) = digits.strip_prefix("0b") {
+        i64::from_str_radix(bin, 2)
+    } else {
+        digits.parse::<i64>()
+    }.map_err(|_| "bad number")?;
+    Ok(if negative { -value } else { value })
+}
+
+// Accepts both signed and unsigned 32-bit spellings, e.g. -1 and 0xFFFFFFFF
+fn to_i32(value: i64) -> Result<i32, &'static str> {
+    if (i32::MIN as i64..=u32::MAX as i64).contains(&value) {
+        Ok(value as u32 as i32)
+    } else {
+        Err("immediate out of range")
+    }
+}
+
+fn strip_comment(line: &str) -> &str {
+    let end = [line.find('#'), line.find("//")].into_iter().flatten().min().unwrap_or(line.len());
+    &line[..end]
+}
+
+fn split_label(text: &str) -> Option<(&str, &str)> {
+    let (label, rest) = text.split_once(':')?;
+    is_label(label).then_some((label, rest))
+}
+
+fn is_label(text: &str) -> bool {
+    let mut chars = text.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_' || c == '.')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '$')
+}
+
+fn split_operands(text: &str) -> Vec<&str> {
+    text.split(',').map(str::trim).filter(|op| !op.is_empty()).collect()
+}
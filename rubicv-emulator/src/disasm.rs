@@ -0,0 +1,212 @@
+use alloc::string::String;
+use core::fmt;
+use crate::instructions::{FastDecodeTable, InsnKind, PreDecodedInstruction, PredecodedProgram};
+use crate::metering::CostTable;
+
+pub const ABI_NAMES: [&str; 32] = [
+    "zero", "ra", "sp", "gp", "tp", "t0", "t1", "t2",
+    "s0", "s1", "a0", "a1", "a2", "a3", "a4", "a5",
+    "a6", "a7", "s2", "s3", "s4", "s5", "s6", "s7",
+    "s8", "s9", "s10", "s11", "t3", "t4", "t5", "t6",
+];
+
+impl InsnKind {
+    pub fn mnemonic(self) -> &'static str {
+        match self {
+            InsnKind::INVALID => "invalid",
+            InsnKind::ADD => "add",
+            InsnKind::SUB => "sub",
+            InsnKind::XOR => "xor",
+            InsnKind::OR => "or",
+            InsnKind::AND => "and",
+            InsnKind::SLL => "sll",
+            InsnKind::SRL => "srl",
+            InsnKind::SRA => "sra",
+            InsnKind::SLT => "slt",
+            InsnKind::SLTU => "sltu",
+            InsnKind::ADDI => "addi",
+            InsnKind::XORI => "xori",
+            InsnKind::ORI => "ori",
+            InsnKind::ANDI => "andi",
+            InsnKind::SLLI => "slli",
+            InsnKind::SRLI => "srli",
+            InsnKind::SRAI => "srai",
+            InsnKind::SLTI => "slti",
+            InsnKind::SLTIU => "sltiu",
+            InsnKind::BEQ => "beq",
+            InsnKind::BNE => "bne",
+            InsnKind::BLT => "blt",
+            InsnKind::BGE => "bge",
+            InsnKind::BLTU => "bltu",
+            InsnKind::BGEU => "bgeu",
+            InsnKind::JAL => "jal",
+            InsnKind::JALR => "jalr",
+            InsnKind::LUI => "lui",
+            InsnKind::AUIPC => "auipc",
+            InsnKind::MUL => "mul",
+            InsnKind::MULH => "mulh",
+            InsnKind::MULHSU => "mulhsu",
+            InsnKind::MULHU => "mulhu",
+            InsnKind::DIV => "div",
+            InsnKind::DIVU => "divu",
+            InsnKind::REM => "rem",
+            InsnKind::REMU => "remu",
+            InsnKind::LB => "lb",
+            InsnKind::LH => "lh",
+            InsnKind::LW => "lw",
+            InsnKind::LBU => "lbu",
+            InsnKind::LHU => "lhu",
+            InsnKind::SB => "sb",
+            InsnKind::SH => "sh",
+            InsnKind::SW => "sw",
+            InsnKind::ECALL => "ecall",
+            InsnKind::EBREAK => "ebreak",
+            InsnKind::LUI_ADDI => "lui+addi",
+            InsnKind::AUIPC_JALR => "auipc+jalr",
+            InsnKind::ADDI_BNE => "addi+bne",
+            InsnKind::SLLI_ADD => "slli+add",
+            InsnKind::PROBE => "probe",
+        }
+    }
+}
+
+// Formats one instruction as RISC-V assembly, without pseudo-instructions.
+// Branch and JAL targets are printed as a byte address followed by the ppc
+// they resolve to, e.g. `bne a0, a2, 0x20 <ppc 8>`.
+pub struct Disasm<'i> {
+    insn: &'i PreDecodedInstruction,
+    abi: bool,
+}
+
+impl<'i> Disasm<'i> {
+    pub fn new(insn: &'i PreDecodedInstruction) -> Self {
+        Self { insn, abi: true }
+    }
+
+    // x0..x31 instead of ABI names
+    pub fn numeric(mut self) -> Self {
+        self.abi = false;
+        self
+    }
+
+    fn reg(&self, r: u8) -> RegName {
+        RegName { r, abi: self.abi }
+    }
+}
+
+struct RegName {
+    r: u8,
+    abi: bool,
+}
+
+impl fmt::Display for RegName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.abi {
+            f.write_str(ABI_NAMES[self.r as usize])
+        } else {
+            write!(f, "x{}", self.r)
+        }
+    }
+}
+
+impl fmt::Display for Disasm<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let insn = self.insn;
+        let (rd, rs1, rs2) = (self.reg(insn.rd), self.reg(insn.rs1), self.reg(insn.rs2));
+        let name = insn.kind.mnemonic();
+        match insn.kind {
+            InsnKind::INVALID => f.write_str(name),
+            InsnKind::ECALL | InsnKind::EBREAK => f.write_str(name),
+            InsnKind::ADD | InsnKind::SUB | InsnKind::XOR | InsnKind::OR | InsnKind::AND
+            | InsnKind::SLL | InsnKind::SRL | InsnKind::SRA | InsnKind::SLT | InsnKind::SLTU
+            | InsnKind::MUL | InsnKind::MULH | InsnKind::MULHSU | InsnKind::MULHU
+            | InsnKind::DIV | InsnKind::DIVU | InsnKind::REM | InsnKind::REMU => {
+                write!(f, "{} {}, {}, {}", name, rd, rs1, rs2)
+            }
+            InsnKind::ADDI | InsnKind::XORI | InsnKind::ORI | InsnKind::ANDI
+            | InsnKind::SLTI | InsnKind::SLTIU => {
+                write!(f, "{} {}, {}, {}", name, rd, rs1, insn.imm)
+            }
+            // SRAI carries its func7 in the immediate
+            InsnKind::SLLI | InsnKind::SRLI | InsnKind::SRAI => {
+                write!(f, "{} {}, {}, {}", name, rd, rs1, insn.imm & 0x1F)
+            }
+            InsnKind::LB | InsnKind::LH | InsnKind::LW | InsnKind::LBU | InsnKind::LHU
+            | InsnKind::JALR => {
+                write!(f, "{} {}, {}({})", name, rd, insn.imm, rs1)
+            }
+            InsnKind::SB | InsnKind::SH | InsnKind::SW => {
+                write!(f, "{} {}, {}({})", name, rs2, insn.imm, rs1)
+            }
+            InsnKind::BEQ | InsnKind::BNE | InsnKind::BLT | InsnKind::BGE
+            | InsnKind::BLTU | InsnKind::BGEU => {
+                write!(f, "{} {}, {}, ", name, rs1, rs2)?;
+                write_target(f, insn.imm)
+            }
+            InsnKind::JAL => {
+                write!(f, "{} {}, ", name, rd)?;
+                write_target(f, insn.imm)
+            }
+            InsnKind::LUI | InsnKind::AUIPC => {
+                write!(f, "{} {}, 0x{:x}", name, rd, (insn.imm as u32) >> 12)
+            }
+            // Fused kinds print their own fields, see PreDecodedInstruction::unfused.
+            // Listings and PredecodedProgram::original show the pair instead.
+            InsnKind::LUI_ADDI => write!(f, "{} {}, {}", name, rd, insn.imm),
+            InsnKind::AUIPC_JALR => write!(f, "{} {}, 0x{:x}, {}", name, rd, (insn.imm as u32) >> 12, rs2),
+            InsnKind::ADDI_BNE => write!(f, "{} {}, {}, {}, {}", name, rd, rs1, insn.imm, rs2),
+            InsnKind::SLLI_ADD => {
+                write!(f, "{} {}, {}, {}, {}, {}", name, rd, rs1, insn.imm & 0x1F, rs2, self.reg((insn.imm >> 8) as u8))
+            }
+            // The access size is in rs2
+            InsnKind::PROBE => write!(f, "{} {}({}), {}", name, insn.imm, rs1, insn.rs2),
+        }
+    }
+}
+
+fn write_target(f: &mut fmt::Formatter<'_>, ppc: i32) -> fmt::Result {
+    write!(f, "0x{:x} <ppc {}>", ppc.wrapping_mul(4), ppc)
+}
+
+// Disassembles a raw instruction word found at byte address `pc`
+pub fn disassemble_word(word: u32, pc: u32) -> String {
+    let decoder = FastDecodeTable::default();
+    let insn = decoder.predecode(word, (pc / 4) as usize, &CostTable::default());
+    alloc::format!("{}", Disasm::new(&insn))
+}
+
+// Full program listing: one line per word with its address, the raw word and
+// the instruction. Words that did not decode are marked so they stand out.
+pub struct Listing<'p> {
+    program: &'p PredecodedProgram,
+    abi: bool,
+}
+
+impl<'p> Listing<'p> {
+    pub fn new(program: &'p PredecodedProgram) -> Self {
+        Self { program, abi: true }
+    }
+
+    pub fn numeric(mut self) -> Self {
+        self.abi = false;
+        self
+    }
+}
+
+impl fmt::Display for Listing<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (ppc, word) in self.program.words.iter().enumerate() {
+            if ppc == self.program.entrypoint {
+                writeln!(f, "{:08x} <entry>:", ppc * 4)?;
+            }
+            let insn = &self.program.original(ppc).ok_or(fmt::Error)?;
+            let disasm = Disasm { insn, abi: self.abi };
+            if insn.kind == InsnKind::INVALID {
+                writeln!(f, "{:08x}:  {:08x}  .word 0x{:08x}  # INVALID", ppc * 4, word, word)?;
+            } else {
+                writeln!(f, "{:08x}:  {:08x}  {}", ppc * 4, word, disasm)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -1,15 +1,26 @@
-#[derive(Debug, PartialEq)]
+use crate::watch::WatchHit;
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum RubicVError {
     GeneralVMError,
     IllegalInstruction,
     InvalidInstruction,
-    MemoryReadOutOfBounds,
-    MemoryWriteOutOfBounds,
-    MemoryMisaligned,
-    MisalignedAccess,
-    IllegalMemoryAccess,
-    WriteToReadOnlyMemory,
+    // Carry the faulting address
+    MemoryReadOutOfBounds(u32),
+    MemoryWriteOutOfBounds(u32),
+    // Misaligned guest load and store under TrapMisaligned
+    MemoryMisaligned(u32),
+    MisalignedAccess(u32),
+    IllegalMemoryAccess(u32),
+    WriteToReadOnlyMemory(u32),
     Breakpoint, // :P
+    Watchpoint(WatchHit),
     ELFDecodeError,
-    SystemCall(u32)
+    SystemCall(u32),
+    InvalidTrace,
+    InvalidSnapshot,
+    SnapshotProgramMismatch,
+    AssemblerError { line: usize, reason: &'static str },
+    // Rejected by PredecodedProgram::verify; `pc` is a byte address
+    InvalidProgram { pc: u32, reason: &'static str },
 }
\ No newline at end of file
@@ -0,0 +1,50 @@
+use crate::instructions::PreDecodedInstruction;
+use crate::watch::{WatchHit, Watchpoints};
+
+// Instrumentation callbacks, picked at compile time like MemoryPolicy.
+// Every method defaults to a no-op, so VMs built with NoHooks compile down
+// to the plain interpreter.
+pub trait Hooks {
+    // Whether resume() may run a fused pair of instructions (see
+    // InsnKind::LUI_ADDI) in one step, which the callbacks then see as one
+    // instruction. Off unless the hooks ignore individual instructions.
+    const FUSED: bool = false;
+
+    // Before the instruction at `ppc` executes
+    #[inline(always)]
+    fn on_step(&mut self, _ppc: usize, _insn: &PreDecodedInstruction, _registers: &[u32; 32]) {}
+
+    #[inline(always)]
+    fn on_mem_read(&mut self, _addr: u32, _size: u8, _value: u32) {}
+
+    // `old` is what the store overwrites
+    #[inline(always)]
+    fn on_mem_write(&mut self, _addr: u32, _size: u8, _old: u32, _value: u32) {}
+
+    // Conditional branches report whether they were taken, jumps always are
+    #[inline(always)]
+    fn on_branch(&mut self, _from: usize, _to: usize, _taken: bool) {}
+
+    // Before the syscall is dispatched, SYS_EXIT included
+    #[inline(always)]
+    fn on_syscall(&mut self, _syscall: u32, _registers: &[u32; 32]) {}
+
+    // Polled after every instruction: a hit stops the run once the
+    // instruction has retired, reported as ExecutionResult::Watchpoint
+    #[inline(always)]
+    fn take_watch_hit(&mut self) -> Option<WatchHit> {
+        None
+    }
+
+    // Lets debuggers that only hold a `dyn VMOperations` manage watchpoints
+    #[inline(always)]
+    fn watchpoints(&mut self) -> Option<&mut Watchpoints> {
+        None
+    }
+}
+
+pub struct NoHooks;
+
+impl Hooks for NoHooks {
+    const FUSED: bool = true;
+}
@@ -1,9 +1,12 @@
+use alloc::vec;
 use alloc::vec::Vec;
 use crate::errors::RubicVError;
 use crate::memory::CODE_SIZE;
+use crate::metering::CostTable;
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 #[repr(u8)]
+#[allow(non_camel_case_types)]
 pub enum InsnKind {
     INVALID = 0,
     ADD = 1,
@@ -53,9 +56,45 @@ pub enum InsnKind {
     SW = 45,
     ECALL = 46,
     EBREAK = 47,
+
+    // Superinstructions the predecoder puts in place of the first of two
+    // instructions; the second keeps its slot so it can still be jumped to.
+    // See PreDecodedInstruction::unfused for how their fields are laid out.
+    LUI_ADDI = 48,
+    AUIPC_JALR = 49,
+    ADDI_BNE = 50,
+    SLLI_ADD = 51,
+
+    // A load into x0: checks and reads rs1 + imm, rs2 bytes wide, for its
+    // fault and watchpoint behaviour, and writes nothing
+    PROBE = 52,
 }
 
-#[derive(Clone, Copy, Debug)]
+impl InsnKind {
+    #[inline(always)]
+    pub fn is_branch(self) -> bool {
+        matches!(self, InsnKind::BEQ | InsnKind::BNE | InsnKind::BLT | InsnKind::BGE | InsnKind::BLTU | InsnKind::BGEU)
+    }
+
+    #[inline(always)]
+    pub fn writes_rd(self) -> bool {
+        !(self.is_branch() || matches!(self,
+            InsnKind::SB | InsnKind::SH | InsnKind::SW | InsnKind::ECALL | InsnKind::EBREAK | InsnKind::INVALID | InsnKind::PROBE))
+    }
+
+    #[inline(always)]
+    pub fn is_fused(self) -> bool {
+        (InsnKind::LUI_ADDI as u8..=InsnKind::SLLI_ADD as u8).contains(&(self as u8))
+    }
+
+    // Control may not fall through to the next instruction
+    #[inline(always)]
+    pub fn ends_block(self) -> bool {
+        self.is_branch() || matches!(self, InsnKind::JAL | InsnKind::JALR | InsnKind::ECALL | InsnKind::EBREAK | InsnKind::INVALID)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum InsnCategory {
     Compute,
     Load,
@@ -67,16 +106,16 @@ pub enum InsnCategory {
 #[derive(Clone, Copy, Debug)]
 pub struct Instruction {
     pub(crate)  kind: InsnKind,
-    #[allow(dead_code)]
-    category: InsnCategory,
+    pub(crate)  category: InsnCategory,
     pub(crate)  opcode: u32,
     pub(crate)  func3: u32,
     pub(crate)  func7: u32,
-    #[allow(dead_code)]
-    cycles: usize,
+    pub(crate)  cycles: usize,
 }
 
-type InstructionTable = [Instruction; 48];
+// Kinds that decode from a word, i.e. all but the fused ones and PROBE
+pub const INSN_KINDS: usize = 48;
+type InstructionTable = [Instruction; INSN_KINDS];
 const fn insn(
     kind: InsnKind,
     category: InsnCategory,
@@ -95,7 +134,7 @@ const fn insn(
     }
 }
 
-const RV32IM_ISA: InstructionTable = [
+pub(crate) const RV32IM_ISA: InstructionTable = [
     insn(InsnKind::INVALID, InsnCategory::Invalid, 0x00, 0x0, 0x00, 0),
     insn(InsnKind::ADD, InsnCategory::Compute, 0x33, 0x0, 0x00, 1),
     insn(InsnKind::SUB, InsnCategory::Compute, 0x33, 0x0, 0x20, 1),
@@ -282,9 +321,84 @@ impl FastDecodeTable {
 
     #[inline(always)]
     pub fn lookup(&self, decoded: &DecodedInstruction) -> Instruction {
+        // ECALL and EBREAK share opcode, func3 and func7; only the immediate tells them apart
+        if decoded.opcode == 0x73 {
+            return match decoded.insn {
+                0x0000_0073 => RV32IM_ISA[InsnKind::ECALL as usize],
+                0x0010_0073 => RV32IM_ISA[InsnKind::EBREAK as usize],
+                _ => RV32IM_ISA[InsnKind::INVALID as usize],
+            };
+        }
         // Direct table lookup, no second array access needed
         self.table[Self::map10(decoded.opcode, decoded.func3, decoded.func7)]
     }
+
+    // Decodes one instruction word at `ppc`. Branch and JAL targets are
+    // resolved to absolute ppc indices.
+    pub fn predecode(&self, word: u32, ppc: usize, costs: &CostTable) -> PreDecodedInstruction {
+        let decoded = DecodedInstruction::new(word);
+        let insn = self.lookup(&decoded);
+
+        let rd = decoded.rd as u8;
+        let rs1 = decoded.rs1 as u8;
+        let rs2 = decoded.rs2 as u8;
+        let mut imm = 0i32;
+
+        match insn.kind {
+            // Immediate instructions
+            InsnKind::ADDI | InsnKind::XORI | InsnKind::ORI | InsnKind::ANDI
+            | InsnKind::SLTI | InsnKind::SLTIU | InsnKind::SLLI | InsnKind::SRLI
+            | InsnKind::SRAI | InsnKind::LB | InsnKind::LH | InsnKind::LW
+            | InsnKind::LBU | InsnKind::LHU | InsnKind::JALR => {
+                imm = decoded.imm_i();
+            }
+            // Store instructions
+            InsnKind::SB | InsnKind::SH | InsnKind::SW => {
+                imm = decoded.imm_s();
+            }
+            // Branch instructions
+            InsnKind::BEQ | InsnKind::BNE | InsnKind::BLT | InsnKind::BGE
+            | InsnKind::BLTU | InsnKind::BGEU => {
+                let imm_b = decoded.imm_b();
+                let target_index = ppc as i32 + (imm_b / 4);
+                imm = target_index;
+            }
+            // JAL instruction
+            InsnKind::JAL => {
+                let imm_j = decoded.imm_j();
+                let target_index = ppc as i32 + (imm_j / 4);
+                imm = target_index;
+            }
+            // LUI and AUIPC instructions
+            InsnKind::LUI => {
+                imm = decoded.imm_u() as i32;
+            }
+            InsnKind::AUIPC => {
+                imm = decoded.imm_u() as i32;
+            }
+            // Other instructions (compute, system, etc.)
+            InsnKind::ADD | InsnKind::SUB | InsnKind::XOR | InsnKind::OR | InsnKind::AND
+            | InsnKind::SLL | InsnKind::SRL | InsnKind::SRA | InsnKind::SLT
+            | InsnKind::SLTU | InsnKind::MUL | InsnKind::MULH | InsnKind::MULHSU
+            | InsnKind::MULHU | InsnKind::DIV | InsnKind::DIVU | InsnKind::REM
+            | InsnKind::REMU => {
+                // No immediate needed; set imm to 0
+                imm = 0;
+            }
+            _ => {}
+        }
+
+        PreDecodedInstruction {
+            kind: insn.kind,
+            rd,
+            rs1,
+            rs2,
+            imm,
+            cost: costs.cost(insn.kind),
+            block_cost: 0,
+            block_len: 0,
+        }
+    }
 }
 
 #[repr(C)]
@@ -295,16 +409,80 @@ pub struct PreDecodedInstruction {
     pub rs1: u8,
     pub rs2: u8,
     pub imm: i32,
+    pub cost: u32, // fuel charged for executing this instruction
+    // From this instruction to the end of its basic block, so a block can be
+    // charged once however it is entered (JALR may land mid-block)
+    pub block_cost: u32,
+    pub block_len: u16,
 }
+
+impl PreDecodedInstruction {
+    // The first of the two instructions a fused one stands for, given the
+    // second, which follows it. Fused fields:
+    //   LUI_ADDI    rd, imm = the constant both load into rd
+    //   AUIPC_JALR  rd = the AUIPC's rd, rs2 = the link register, imm = the
+    //               AUIPC offset; the JALR adds its own imm to rd
+    //   ADDI_BNE    rd, rs1, imm as the ADDI; the BNE compares rd with rs2
+    //   SLLI_ADD    rd = rs2 + (rs1 << shamt), with imm = shamt | t << 8
+    //               and the shifted value also left in t
+    // Other instructions are returned as they are.
+    pub fn unfused(&self, next: &PreDecodedInstruction) -> PreDecodedInstruction {
+        let (kind, rd, rs1, imm) = match self.kind {
+            InsnKind::LUI_ADDI => (InsnKind::LUI, self.rd, 0, self.imm.wrapping_sub(next.imm)),
+            InsnKind::AUIPC_JALR => (InsnKind::AUIPC, self.rd, 0, self.imm),
+            InsnKind::ADDI_BNE => (InsnKind::ADDI, self.rd, self.rs1, self.imm),
+            InsnKind::SLLI_ADD => (InsnKind::SLLI, (self.imm >> 8) as u8, self.rs1, self.imm & 0x1f),
+            _ => return self.clone(),
+        };
+        PreDecodedInstruction { kind, rd, rs1, rs2: 0, imm, ..self.clone() }
+    }
+
+    // The superinstruction for `self` followed by `next`, if there is one.
+    // Only the register-carried idioms compilers emit are matched.
+    fn fuse(&self, next: &PreDecodedInstruction) -> Option<PreDecodedInstruction> {
+        let (kind, rd, rs1, rs2, imm) = match (self.kind, next.kind) {
+            // rd is never x0 here, instructions writing x0 are nops by now
+            (InsnKind::LUI, InsnKind::ADDI) if next.rd == self.rd && next.rs1 == self.rd =>
+                (InsnKind::LUI_ADDI, self.rd, 0, 0, self.imm.wrapping_add(next.imm)),
+            (InsnKind::AUIPC, InsnKind::JALR) if next.rs1 == self.rd =>
+                (InsnKind::AUIPC_JALR, self.rd, 0, next.rd, self.imm),
+            (InsnKind::ADDI, InsnKind::BNE) if self.rd != 0 && (next.rs1 == self.rd || next.rs2 == self.rd) => {
+                let other = if next.rs1 == self.rd { next.rs2 } else { next.rs1 };
+                (InsnKind::ADDI_BNE, self.rd, self.rs1, other, self.imm)
+            },
+            (InsnKind::SLLI, InsnKind::ADD) if next.rd != 0 && (next.rs1 == self.rd || next.rs2 == self.rd) => {
+                let other = if next.rs1 == self.rd { next.rs2 } else { next.rs1 };
+                (InsnKind::SLLI_ADD, next.rd, self.rs1, other, (self.imm & 0x1f) | (self.rd as i32) << 8)
+            },
+            _ => return None,
+        };
+        Some(PreDecodedInstruction { kind, rd, rs1, rs2, imm, ..self.clone() })
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BasicBlock {
+    pub start: usize,
+    pub len: usize,
+    pub cost: u64,
+}
+
 #[derive(Clone, Debug)]
 pub struct PredecodedProgram {
+    // One per word, then an INVALID sentinel that traps when control runs
+    // past the end of the code or a JALR jumps outside it
     pub instructions: Vec<PreDecodedInstruction>,
+    pub words: Vec<u32>, // raw instruction words, same indexing as instructions
     pub entrypoint: usize,
-    pub writes_to_x0: bool,
+    pub blocks: Vec<BasicBlock>,
 }
 
 impl PredecodedProgram {
     pub fn new(elf_bytes: &[u8]) -> Result<Self, RubicVError> {
+        Self::with_cost_table(elf_bytes, &CostTable::default())
+    }
+
+    pub fn with_cost_table(elf_bytes: &[u8], costs: &CostTable) -> Result<Self, RubicVError> {
         // Ensure the header is at least 8 bytes
         if elf_bytes.len() < 4 || elf_bytes.len() > CODE_SIZE as usize + 4  {
             return Err(RubicVError::ELFDecodeError)
@@ -315,107 +493,156 @@ impl PredecodedProgram {
 
         // Pre-decode the instructions
         let mut predecoded_instructions = Vec::with_capacity(code.len() / 4);
+        let mut words = Vec::with_capacity(code.len() / 4);
         let decoder = FastDecodeTable::new();
-        let mut writes_to_x0 = false;
 
         for (i, chunk) in code.chunks(4).enumerate() {
             if chunk.len() < 4 {
                 break;
             }
 
+            let insn_word = u32::from_le_bytes(chunk.try_into().expect("Incorrect chunk length"));
             // Code address is 0
-            let predecoded_offset = i as i32;
+            let pre_decoded_insn = Self::discard_x0_write(decoder.predecode(insn_word, i, costs));
 
-            let insn_word = u32::from_le_bytes(chunk.try_into().expect("Incorrect chunk length"));
+            predecoded_instructions.push(pre_decoded_insn);
+            words.push(insn_word);
+        }
+
+        Self::clamp_targets(&mut predecoded_instructions);
+        let blocks = Self::split_blocks(&mut predecoded_instructions, entrypoint);
+        Self::fuse_pairs(&mut predecoded_instructions, &blocks);
+        let cost = costs.cost(InsnKind::INVALID);
+        predecoded_instructions.push(PreDecodedInstruction {
+            kind: InsnKind::INVALID,
+            rd: 0,
+            rs1: 0,
+            rs2: 0,
+            imm: 0,
+            cost,
+            block_cost: cost,
+            block_len: 1,
+        });
+
+        Ok(PredecodedProgram {
+            instructions: predecoded_instructions,
+            words,
+            entrypoint,
+            blocks,
+        })
+    }
+
+    // Points branch and JAL targets outside the code at the trailing
+    // sentinel, which traps, so the VM can index instructions by them
+    // unchecked even in code verify() never walked
+    fn clamp_targets(instructions: &mut [PreDecodedInstruction]) {
+        let sentinel = instructions.len();
+        for insn in instructions.iter_mut() {
+            if (insn.kind.is_branch() || insn.kind == InsnKind::JAL) && (insn.imm < 0 || insn.imm as usize >= sentinel) {
+                insn.imm = sentinel as i32;
+            }
+        }
+    }
 
-            let decoded = DecodedInstruction::new(insn_word);
-            let insn = decoder.lookup(&decoded);
-
-            let rd = decoded.rd as u8;
-            let rs1 = decoded.rs1 as u8;
-            let rs2 = decoded.rs2 as u8;
-            let mut imm = 0i32;
-
-            // Check for writes to x0
-            match insn.kind {
-                InsnKind::ADDI | InsnKind::XORI | InsnKind::ORI | InsnKind::ANDI |
-                InsnKind::SLTI | InsnKind::SLTIU | InsnKind::SLLI | InsnKind::SRLI |
-                InsnKind::SRAI | InsnKind::LB | InsnKind::LH | InsnKind::LW |
-                InsnKind::LBU | InsnKind::LHU | InsnKind::JALR |  // removed JAL
-                InsnKind::LUI | InsnKind::AUIPC | InsnKind::ADD | InsnKind::SUB |
-                InsnKind::SLL | InsnKind::SLT | InsnKind::SLTU | InsnKind::XOR |
-                InsnKind::SRL | InsnKind::SRA | InsnKind::OR | InsnKind::AND => {
-                    if rd == 0 {
-                        if !(insn.kind == InsnKind::ADDI && rs1 == 0 && imm == 0) {
-                            if insn_word != 0 {
-                                writes_to_x0 = true;
-                            }
-                        }
+    // Keeps x0 zero without the VM checking for it. Instructions that only
+    // compute a value for rd become `addi x0, x0, 0` and loads become PROBE,
+    // both keeping their cost. JAL and JALR still jump; the VM skips their
+    // write when rd is x0.
+    fn discard_x0_write(insn: PreDecodedInstruction) -> PreDecodedInstruction {
+        let category = RV32IM_ISA[insn.kind as usize].category;
+        if insn.rd != 0 || !insn.kind.writes_rd() || insn.kind.ends_block() {
+            return insn;
+        }
+        match category {
+            InsnCategory::Compute => PreDecodedInstruction {
+                kind: InsnKind::ADDI,
+                rs1: 0,
+                rs2: 0,
+                imm: 0,
+                ..insn
+            },
+            InsnCategory::Load => PreDecodedInstruction {
+                kind: InsnKind::PROBE,
+                rs2: match insn.kind {
+                    InsnKind::LB | InsnKind::LBU => 1,
+                    InsnKind::LH | InsnKind::LHU => 2,
+                    _ => 4,
+                },
+                ..insn
+            },
+            _ => insn,
+        }
+    }
+
+    // Replaces the first of each fusable pair inside a basic block with its
+    // superinstruction. Pairs do not overlap, and the fused instruction keeps
+    // the first one's cost, so the block costs do not change.
+    fn fuse_pairs(instructions: &mut [PreDecodedInstruction], blocks: &[BasicBlock]) {
+        for block in blocks {
+            let mut i = block.start;
+            while i + 1 < block.start + block.len {
+                match instructions[i].fuse(&instructions[i + 1]) {
+                    Some(fused) => {
+                        instructions[i] = fused;
+                        i += 2;
                     }
+                    None => i += 1,
                 }
-                _ => {}
             }
+        }
+    }
 
-            match insn.kind {
-                // Immediate instructions
-                InsnKind::ADDI | InsnKind::XORI | InsnKind::ORI | InsnKind::ANDI
-                | InsnKind::SLTI | InsnKind::SLTIU | InsnKind::SLLI | InsnKind::SRLI
-                | InsnKind::SRAI | InsnKind::LB | InsnKind::LH | InsnKind::LW
-                | InsnKind::LBU | InsnKind::LHU | InsnKind::JALR => {
-                    imm = decoded.imm_i();
-                }
-                // Store instructions
-                InsnKind::SB | InsnKind::SH | InsnKind::SW => {
-                    imm = decoded.imm_s();
-                }
-                // Branch instructions
-                InsnKind::BEQ | InsnKind::BNE | InsnKind::BLT | InsnKind::BGE
-                | InsnKind::BLTU | InsnKind::BGEU => {
-                    let imm_b = decoded.imm_b();
-                    let target_index = predecoded_offset + (imm_b / 4);
-                    imm = target_index;
-                }
-                // JAL instruction
-                InsnKind::JAL => {
-                    let imm_j = decoded.imm_j();
-                    let target_index = predecoded_offset + (imm_j / 4);
-                    imm = target_index;
-                }
-                // LUI and AUIPC instructions
-                InsnKind::LUI => {
-                    imm = decoded.imm_u() as i32;
-                }
-                InsnKind::AUIPC => {
-                    imm = decoded.imm_u() as i32;
-                }
-                // Other instructions (compute, system, etc.)
-                InsnKind::ADD | InsnKind::SUB | InsnKind::XOR | InsnKind::OR | InsnKind::AND
-                | InsnKind::SLL | InsnKind::SRL | InsnKind::SRA | InsnKind::SLT
-                | InsnKind::SLTU | InsnKind::MUL | InsnKind::MULH | InsnKind::MULHSU
-                | InsnKind::MULHU | InsnKind::DIV | InsnKind::DIVU | InsnKind::REM
-                | InsnKind::REMU => {
-                    // No immediate needed; set imm to 0
-                    imm = 0;
-                }
-                _ => {}
+    // The instruction at `ppc` as decoded from its word, before fusion
+    pub fn original(&self, ppc: usize) -> Option<PreDecodedInstruction> {
+        let insn = self.instructions.get(ppc)?;
+        Some(match self.instructions.get(ppc + 1) {
+            Some(next) => insn.unfused(next),
+            None => insn.clone(),
+        })
+    }
+
+    // Leaders are the entrypoint, static branch/JAL targets and every
+    // instruction after a block end. Fills in block_cost/block_len.
+    fn split_blocks(instructions: &mut [PreDecodedInstruction], entrypoint: usize) -> Vec<BasicBlock> {
+        let len = instructions.len();
+        let mut leaders = vec![false; len + 1];
+        leaders[0] = true;
+        if entrypoint < len {
+            leaders[entrypoint] = true;
+        }
+        for (i, insn) in instructions.iter().enumerate() {
+            if insn.kind.ends_block() {
+                leaders[i + 1] = true;
+            }
+            if (insn.kind.is_branch() || insn.kind == InsnKind::JAL) && insn.imm >= 0 && (insn.imm as usize) < len {
+                leaders[insn.imm as usize] = true;
             }
+        }
 
-            let pre_decoded_insn = PreDecodedInstruction {
-                kind: insn.kind,
-                rd,
-                rs1,
-                rs2,
-                imm,
-            };
-            // println!("{:?} : {:?} | {:?}",insn_word, insn,pre_decoded_insn);
-            predecoded_instructions.push(pre_decoded_insn);
+        // Walk backwards accumulating the cost to the end of each block
+        let mut block_cost = 0u32;
+        let mut block_len = 0u16;
+        for i in (0..len).rev() {
+            let cost = instructions[i].cost;
+            if leaders[i + 1] || block_cost.checked_add(cost).is_none() {
+                leaders[i + 1] = true;
+                block_cost = 0;
+                block_len = 0;
+            }
+            block_cost += cost;
+            block_len += 1;
+            instructions[i].block_cost = block_cost;
+            instructions[i].block_len = block_len;
         }
 
-        Ok(PredecodedProgram {
-            instructions: predecoded_instructions,
-            entrypoint,
-            writes_to_x0,
-        })
+        (0..len)
+            .filter(|&i| leaders[i])
+            .map(|i| BasicBlock {
+                start: i,
+                len: instructions[i].block_len as usize,
+                cost: instructions[i].block_cost as u64,
+            })
+            .collect()
     }
 
 }
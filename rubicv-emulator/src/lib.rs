@@ -17,7 +17,23 @@
 #![no_std]
 
 extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
 pub mod instructions;
 pub mod memory;
+pub mod metering;
 pub mod errors;
+pub mod hooks;
+pub mod watch;
+pub mod syscalls;
+pub mod trace;
+pub mod replay;
+pub mod verify;
+pub mod disasm;
+pub mod asm;
+pub mod aot;
 pub mod vm;
+
+// Lets tests include modules generated by aot::translate
+#[cfg(test)]
+extern crate self as rubicv_emulator;
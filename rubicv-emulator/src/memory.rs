@@ -42,4 +42,8 @@ pub const ARGS_START: u32 = RO_START;
 pub const RO_SLAB_START: u32 = ARGS_START + ARGS_SIZE;
 pub const RO_SLAB_SIZE: u32 = RO_SIZE - ARGS_SIZE;
 
+// Per-VM part of the address space (RW region and args). A forked VM owns
+// only this much and shares the rest of the slab with its template.
+pub const PRIVATE_SIZE: u32 = RO_SLAB_START;
+
 
@@ -0,0 +1,80 @@
+use crate::instructions::{InsnCategory, InsnKind, INSN_KINDS, RV32IM_ISA};
+
+// Fuel schedule, indexed by InsnKind. Loads and stores additionally pay
+// `memory_access`, ECALL pays `syscall`. Costs are folded into the
+// PreDecodedInstructions when a program is predecoded. Kinds the predecoder
+// makes up are priced as the instruction they replace: a fused kind as its
+// first instruction, PROBE as LW.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CostTable {
+    pub insn: [u32; INSN_KINDS],
+    pub memory_access: u32,
+    pub syscall: u32,
+}
+
+impl Default for CostTable {
+    // The cycle counts from RV32IM_ISA, no surcharges
+    fn default() -> Self {
+        let mut insn = [0; INSN_KINDS];
+        for (cost, isa) in insn.iter_mut().zip(RV32IM_ISA.iter()) {
+            *cost = isa.cycles as u32;
+        }
+        Self {
+            insn,
+            memory_access: 0,
+            syscall: 0,
+        }
+    }
+}
+
+impl CostTable {
+    // Every instruction costs the same, fuel then counts steps
+    pub fn uniform(cost: u32) -> Self {
+        let mut insn = [cost; INSN_KINDS];
+        insn[InsnKind::INVALID as usize] = 0;
+        Self {
+            insn,
+            memory_access: 0,
+            syscall: 0,
+        }
+    }
+
+    // Only kinds that decode from a word have a cost of their own
+    pub fn with_cost(mut self, kind: InsnKind, cost: u32) -> Self {
+        assert!((kind as usize) < INSN_KINDS, "{:?} is priced as the instruction it replaces", kind);
+        self.insn[kind as usize] = cost;
+        self
+    }
+
+    pub fn with_memory_access(mut self, cost: u32) -> Self {
+        self.memory_access = cost;
+        self
+    }
+
+    pub fn with_syscall(mut self, cost: u32) -> Self {
+        self.syscall = cost;
+        self
+    }
+
+    // Total charge for one execution of `kind`, surcharges included
+    pub fn cost(&self, kind: InsnKind) -> u32 {
+        let kind = priced_as(kind);
+        let base = self.insn[kind as usize];
+        match RV32IM_ISA[kind as usize].category {
+            InsnCategory::Load | InsnCategory::Store => base.saturating_add(self.memory_access),
+            InsnCategory::System if kind == InsnKind::ECALL => base.saturating_add(self.syscall),
+            _ => base,
+        }
+    }
+}
+
+fn priced_as(kind: InsnKind) -> InsnKind {
+    match kind {
+        InsnKind::LUI_ADDI => InsnKind::LUI,
+        InsnKind::AUIPC_JALR => InsnKind::AUIPC,
+        InsnKind::ADDI_BNE => InsnKind::ADDI,
+        InsnKind::SLLI_ADD => InsnKind::SLLI,
+        InsnKind::PROBE => InsnKind::LW,
+        kind => kind,
+    }
+}
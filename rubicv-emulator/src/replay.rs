@@ -0,0 +1,187 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use crate::errors::RubicVError;
+use crate::memory::RW_SIZE;
+use crate::syscalls::{NoSyscalls, SyscallContext, SyscallHandler, SyscallResult};
+use crate::vm::{ExecutionResult, VMOperations};
+
+// Each checkpoint holds a copy of the RW region
+const MAX_CHECKPOINTS: usize = 256;
+
+// What one host syscall did to the guest, so it can be redone without the host
+#[derive(Clone, Debug, PartialEq)]
+pub struct SyscallRecord {
+    pub syscall: u32,
+    pub result: Result<SyscallResult, RubicVError>,
+    pub registers: [u32; 32],
+    // (addr, value) for every RW byte the handler changed
+    pub writes: Vec<(u32, u8)>,
+}
+
+// Host syscalls in the order the guest made them
+#[derive(Default)]
+pub struct SyscallLog {
+    pub records: Vec<SyscallRecord>,
+}
+
+// Answers syscalls from the log while `cursor` is inside it, and asks the
+// host and logs the answer once past its end
+struct Replayer<'r> {
+    log: &'r mut SyscallLog,
+    cursor: &'r mut usize,
+    host: &'r mut dyn SyscallHandler,
+}
+
+impl SyscallHandler for Replayer<'_> {
+    fn handle(&mut self, syscall: u32, ctx: &mut SyscallContext) -> Result<SyscallResult, RubicVError> {
+        *self.cursor += 1;
+        if let Some(record) = self.log.records.get(*self.cursor - 1) {
+            for r in 1..32 {
+                ctx.set_register(r, record.registers[r as usize]);
+            }
+            for &(addr, value) in &record.writes {
+                ctx.write_u8(addr, value);
+            }
+            return record.result.clone();
+        }
+
+        let mut before = vec![0u8; RW_SIZE as usize];
+        ctx.read_bytes(0, &mut before);
+        let result = self.host.handle(syscall, ctx);
+        let writes = before.iter()
+            .enumerate()
+            .filter_map(|(addr, &old)| {
+                let value = ctx.read_u8(addr as u32);
+                (value != old).then_some((addr as u32, value))
+            })
+            .collect();
+        let registers = core::array::from_fn(|r| ctx.get_register(r as u8));
+        self.log.records.push(SyscallRecord { syscall, result: result.clone(), registers, writes });
+        result
+    }
+}
+
+struct Checkpoint {
+    cycle: usize,
+    // Syscalls made before the checkpoint
+    syscalls: usize,
+    snapshot: Vec<u8>,
+}
+
+// Reverse execution for debuggers. Stepping through `step` keeps a snapshot
+// every `interval` instructions and logs host syscalls; going back restores
+// the nearest earlier snapshot and replays forward from it, answering
+// syscalls from the log. Anything that changes the VM outside of `step`,
+// such as edits from the debugger or from a host after a Yield, is not
+// recorded and is lost when going back past it.
+pub struct TimeTravel {
+    interval: usize,
+    checkpoints: Vec<Checkpoint>,
+    log: SyscallLog,
+    cursor: usize,
+}
+
+impl TimeTravel {
+    // History starts at the VM's current state
+    pub fn new(vm: &dyn VMOperations, interval: usize) -> Self {
+        assert!(interval > 0, "checkpoint interval must be positive");
+        Self {
+            interval,
+            checkpoints: vec![Checkpoint { cycle: vm.get_cycle_count(), syscalls: 0, snapshot: vm.snapshot() }],
+            log: SyscallLog::default(),
+            cursor: 0,
+        }
+    }
+
+    pub fn log(&self) -> &SyscallLog {
+        &self.log
+    }
+
+    // The earliest cycle that can be returned to
+    pub fn start(&self) -> usize {
+        self.checkpoints[0].cycle
+    }
+
+    // VMOperations::single_step, recorded. Instructions that were already
+    // executed once are replayed from the log rather than asking `host` again.
+    pub fn step(&mut self, vm: &mut dyn VMOperations, host: &mut dyn SyscallHandler) -> Option<ExecutionResult> {
+        let mut replayer = Replayer { log: &mut self.log, cursor: &mut self.cursor, host };
+        let result = vm.single_step(&mut replayer);
+        let last = self.checkpoints.last().map_or(0, |checkpoint| checkpoint.cycle);
+        if vm.get_cycle_count() >= last + self.interval {
+            self.checkpoints.push(Checkpoint { cycle: vm.get_cycle_count(), syscalls: self.cursor, snapshot: vm.snapshot() });
+            // Long runs keep every other checkpoint: half the memory, twice the replay
+            if self.checkpoints.len() > MAX_CHECKPOINTS {
+                let mut index = 0;
+                self.checkpoints.retain(|_| {
+                    index += 1;
+                    index % 2 == 1
+                });
+                self.interval *= 2;
+            }
+        }
+        result
+    }
+
+    // Moves back to the state just before instruction `cycle` ran. Only
+    // cycles between start() and the current one can be reached.
+    pub fn rewind_to(&mut self, vm: &mut dyn VMOperations, cycle: usize) -> Result<(), RubicVError> {
+        assert!(cycle >= self.start() && cycle <= vm.get_cycle_count(), "cycle outside the recorded history");
+        let index = self.checkpoints.partition_point(|checkpoint| checkpoint.cycle <= cycle) - 1;
+        let checkpoint = &self.checkpoints[index];
+        vm.load_snapshot(&checkpoint.snapshot)?;
+        self.cursor = checkpoint.syscalls;
+        while vm.get_cycle_count() < cycle {
+            if let Some(result) = self.step(vm, &mut NoSyscalls) {
+                if !result.is_resumable() {
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Undoes the last instruction. Returns false at the start of history.
+    pub fn reverse_step(&mut self, vm: &mut dyn VMOperations) -> Result<bool, RubicVError> {
+        let cycle = vm.get_cycle_count();
+        if cycle <= self.start() {
+            return Ok(false);
+        }
+        self.rewind_to(vm, cycle - 1)?;
+        Ok(true)
+    }
+
+    // Goes back to the latest earlier state for which `stop` holds, such as
+    // the pc sitting on a breakpoint, or that a watchpoint hit stopped at.
+    // Returns false, at the start of history, when there is none.
+    pub fn reverse_until(&mut self, vm: &mut dyn VMOperations, stop: impl Fn(&dyn VMOperations) -> bool) -> Result<bool, RubicVError> {
+        let mut end = vm.get_cycle_count();
+        // Each pass replays one checkpoint interval, newest first
+        for index in (0..self.checkpoints.len()).rev() {
+            let checkpoint = &self.checkpoints[index];
+            if checkpoint.cycle >= end {
+                continue;
+            }
+            let start = checkpoint.cycle;
+            self.rewind_to(vm, start)?;
+            let mut hit = None;
+            while vm.get_cycle_count() < end {
+                if stop(vm) {
+                    hit = Some(vm.get_cycle_count());
+                }
+                match self.step(vm, &mut NoSyscalls) {
+                    Some(ExecutionResult::Watchpoint(_)) if vm.get_cycle_count() < end => hit = Some(vm.get_cycle_count()),
+                    Some(result) if !result.is_resumable() => break,
+                    _ => {}
+                }
+            }
+            if let Some(cycle) = hit {
+                self.rewind_to(vm, cycle)?;
+                return Ok(true);
+            }
+            end = start;
+        }
+        self.rewind_to(vm, self.start())?;
+        Ok(false)
+    }
+}
@@ -0,0 +1,113 @@
+use crate::errors::RubicVError;
+use crate::memory::*;
+
+// Syscall ABI: number in a7, arguments in a0-a6, results in a0/a1.
+pub const REG_A0: usize = 10;
+pub const REG_A7: usize = 17;
+pub const SYSCALL_ARGS: usize = 7;
+
+// Reserved: exit with the code in a0. a7 is zero at reset, so guests that
+// never load a syscall number keep the old "ecall exits" behaviour.
+pub const SYS_EXIT: u32 = 0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SyscallResult {
+    // Resume at the instruction after the ecall
+    Continue,
+    // Stop the run, reported as ExecutionResult::Success(code)
+    Halt(u32),
+    // Pause the run, reported as ExecutionResult::Yield(syscall). The host can
+    // inspect or patch the VM and call resume() to continue after the ecall.
+    Yield,
+}
+
+pub trait SyscallHandler {
+    fn handle(&mut self, syscall: u32, ctx: &mut SyscallContext) -> Result<SyscallResult, RubicVError>;
+}
+
+// Default handler: only SYS_EXIT is available, everything else is an error
+pub struct NoSyscalls;
+
+impl SyscallHandler for NoSyscalls {
+    fn handle(&mut self, syscall: u32, _ctx: &mut SyscallContext) -> Result<SyscallResult, RubicVError> {
+        Err(RubicVError::SystemCall(syscall))
+    }
+}
+
+// View of the guest handed to a SyscallHandler. Memory accesses follow the
+// VM rules: reads see the whole slab, writes wrap into the RW region.
+pub struct SyscallContext<'v> {
+    registers: &'v mut [u32; 32],
+    memory_slab: *mut [u8],
+    ro_slab: *const [u8],
+}
+
+impl<'v> SyscallContext<'v> {
+    pub fn new(registers: &'v mut [u32; 32], memory_slab: *mut [u8], ro_slab: *const [u8]) -> Self {
+        Self { registers, memory_slab, ro_slab }
+    }
+
+    pub fn number(&self) -> u32 {
+        self.registers[REG_A7]
+    }
+
+    // a0..a6
+    pub fn arg(&self, n: usize) -> u32 {
+        assert!(n < SYSCALL_ARGS, "syscall argument index out of range");
+        self.registers[REG_A0 + n]
+    }
+
+    pub fn args(&self) -> [u32; SYSCALL_ARGS] {
+        let mut args = [0; SYSCALL_ARGS];
+        args.copy_from_slice(&self.registers[REG_A0..REG_A0 + SYSCALL_ARGS]);
+        args
+    }
+
+    // Return values go to a0 (n = 0) and a1 (n = 1)
+    pub fn set_return(&mut self, n: usize, value: u32) {
+        assert!(n < 2, "syscall return index out of range");
+        self.registers[REG_A0 + n] = value;
+    }
+
+    pub fn get_register(&self, r: u8) -> u32 {
+        self.registers[r as usize]
+    }
+
+    pub fn set_register(&mut self, r: u8, value: u32) {
+        if r != 0 {
+            self.registers[r as usize] = value;
+        }
+    }
+
+    pub fn read_u8(&self, addr: u32) -> u8 {
+        let addr = addr & MEMORY_MASK;
+        let slab = if addr < PRIVATE_SIZE { self.memory_slab as *const u8 } else { self.ro_slab as *const u8 };
+        unsafe { *slab.add(addr as usize) }
+    }
+
+    pub fn write_u8(&mut self, addr: u32, value: u8) {
+        unsafe { *(self.memory_slab as *mut u8).add((addr & RW_MASK) as usize) = value }
+    }
+
+    pub fn read_u32(&self, addr: u32) -> u32 {
+        let mut bytes = [0u8; 4];
+        self.read_bytes(addr, &mut bytes);
+        u32::from_le_bytes(bytes)
+    }
+
+    pub fn write_u32(&mut self, addr: u32, value: u32) {
+        self.write_bytes(addr, &value.to_le_bytes());
+    }
+
+    pub fn read_bytes(&self, addr: u32, buf: &mut [u8]) {
+        for (i, b) in buf.iter_mut().enumerate() {
+            *b = self.read_u8(addr.wrapping_add(i as u32));
+        }
+    }
+
+    pub fn write_bytes(&mut self, addr: u32, data: &[u8]) {
+        for (i, b) in data.iter().enumerate() {
+            self.write_u8(addr.wrapping_add(i as u32), *b);
+        }
+    }
+}
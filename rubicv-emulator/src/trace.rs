@@ -0,0 +1,179 @@
+use alloc::vec::Vec;
+use core::fmt;
+use crate::errors::RubicVError;
+use crate::instructions::{InsnKind, PreDecodedInstruction, PredecodedProgram};
+use crate::hooks::Hooks;
+use crate::vm::{MemoryPolicy, VM};
+
+// Spike prints the privilege level of every commit; guests always run in M-mode
+const SPIKE_PRIV: u8 = 3;
+
+// Binary record flags
+const HAS_REG: u8 = 1 << 0;
+const HAS_LOAD: u8 = 1 << 1;
+const HAS_STORE: u8 = 1 << 2;
+const SIZE_SHIFT: u8 = 4; // log2 of the access size in bytes
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MemAccess {
+    pub addr: u32,
+    pub value: u32,
+    pub size: u8,
+    pub write: bool,
+}
+
+// One retired instruction
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TraceEntry {
+    pub pc: u32,
+    pub insn: u32,
+    pub rd: Option<(u8, u32)>,
+    pub mem: Option<MemAccess>,
+}
+
+impl fmt::Display for TraceEntry {
+    // Spike --log-commits line, e.g.
+    // core   0: 3 0x00000004 (0x00b50633) x12 0x0000002a
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "core{:4}: {} 0x{:08x} (0x{:08x})", 0, SPIKE_PRIV, self.pc, self.insn)?;
+        if let Some((rd, value)) = self.rd {
+            write!(f, " x{:<2} 0x{:08x}", rd, value)?;
+        }
+        if let Some(mem) = self.mem {
+            write!(f, " mem 0x{:08x}", mem.addr)?;
+            if mem.write {
+                write!(f, " 0x{:0width$x}", mem.value, width = mem.size as usize * 2)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct TraceRecorder {
+    pub entries: Vec<TraceEntry>,
+    words: Vec<u32>,
+}
+
+impl TraceRecorder {
+    pub fn new(program: &PredecodedProgram) -> Self {
+        Self {
+            entries: Vec::new(),
+            words: program.words.clone(),
+        }
+    }
+
+    // Called after `insn` at `ppc` retired. `rs1` and `rs2` are the operand
+    // values it read, since the instruction may have overwritten them.
+    pub fn record<H: Hooks, M: MemoryPolicy>(&mut self, vm: &VM<H, M>, ppc: usize, insn: &PreDecodedInstruction, rs1: u32, rs2: u32) {
+        let addr = rs1.wrapping_add(insn.imm as u32);
+        let access = |size: u8, write: bool, value: u32| Some(MemAccess { addr, value, size, write });
+        let mem = match insn.kind {
+            InsnKind::LB | InsnKind::LBU => access(1, false, vm.read_u8(addr) as u32),
+            InsnKind::LH | InsnKind::LHU => access(2, false, vm.read_sized(addr, 2)),
+            InsnKind::LW => access(4, false, vm.read_sized(addr, 4)),
+            InsnKind::PROBE => access(insn.rs2, false, vm.read_sized(addr, insn.rs2)),
+            InsnKind::SB => access(1, true, rs2 & 0xFF),
+            InsnKind::SH => access(2, true, rs2 & 0xFFFF),
+            InsnKind::SW => access(4, true, rs2),
+            _ => None,
+        };
+
+        // Like Spike, writes to x0 are not logged
+        let rd = if insn.kind.writes_rd() && insn.rd != 0 {
+            Some((insn.rd, vm.registers[insn.rd as usize]))
+        } else {
+            None
+        };
+
+        let word = self.words.get(ppc).copied().unwrap_or(0);
+        self.entries.push(TraceEntry { pc: (ppc * 4) as u32, insn: word, rd, mem });
+    }
+
+    pub fn write_commit_log(&self, out: &mut dyn fmt::Write) -> fmt::Result {
+        for entry in &self.entries {
+            writeln!(out, "{}", entry)?;
+        }
+        Ok(())
+    }
+
+    // Compact form: per entry pc, insn and a flags byte, followed by
+    // [rd, value] and [addr, value] when present. Little-endian.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.entries.len() * 13);
+        for entry in &self.entries {
+            let mut flags = 0;
+            if entry.rd.is_some() {
+                flags |= HAS_REG;
+            }
+            if let Some(mem) = entry.mem {
+                flags |= if mem.write { HAS_STORE } else { HAS_LOAD };
+                flags |= (mem.size.trailing_zeros() as u8) << SIZE_SHIFT;
+            }
+            out.extend_from_slice(&entry.pc.to_le_bytes());
+            out.extend_from_slice(&entry.insn.to_le_bytes());
+            out.push(flags);
+            if let Some((rd, value)) = entry.rd {
+                out.push(rd);
+                out.extend_from_slice(&value.to_le_bytes());
+            }
+            if let Some(mem) = entry.mem {
+                out.extend_from_slice(&mem.addr.to_le_bytes());
+                out.extend_from_slice(&mem.value.to_le_bytes());
+            }
+        }
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Vec<TraceEntry>, RubicVError> {
+        let mut reader = Reader { bytes, pos: 0 };
+        let mut entries = Vec::new();
+        while reader.pos < bytes.len() {
+            let pc = reader.u32()?;
+            let insn = reader.u32()?;
+            let flags = reader.u8()?;
+            let rd = if flags & HAS_REG != 0 {
+                Some((reader.u8()?, reader.u32()?))
+            } else {
+                None
+            };
+            let mem = if flags & (HAS_LOAD | HAS_STORE) != 0 {
+                // Accesses are 1, 2 or 4 bytes
+                if flags >> SIZE_SHIFT > 2 {
+                    return Err(RubicVError::InvalidTrace);
+                }
+                Some(MemAccess {
+                    addr: reader.u32()?,
+                    value: reader.u32()?,
+                    size: 1 << (flags >> SIZE_SHIFT),
+                    write: flags & HAS_STORE != 0,
+                })
+            } else {
+                None
+            };
+            entries.push(TraceEntry { pc, insn, rd, mem });
+        }
+        Ok(entries)
+    }
+}
+
+struct Reader<'b> {
+    bytes: &'b [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, n: usize) -> Result<&[u8], RubicVError> {
+        let bytes = self.bytes.get(self.pos..self.pos + n).ok_or(RubicVError::InvalidTrace)?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, RubicVError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, RubicVError> {
+        let b = self.take(4)?;
+        Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+    }
+}
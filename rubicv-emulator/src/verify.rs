@@ -0,0 +1,136 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use crate::errors::RubicVError;
+use crate::instructions::{InsnKind, PreDecodedInstruction, PredecodedProgram};
+use crate::syscalls::SYS_EXIT;
+
+const RA: u8 = 1;
+const A7: u8 = 17;
+
+// What a verified program can do, for hosts deciding whether to run it
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Capabilities {
+    // Kinds of the instructions reachable from the entrypoint, in InsnKind order
+    pub kinds: Vec<InsnKind>,
+    // Syscall numbers reachable ecalls pass in a7, SYS_EXIT included, sorted
+    pub syscalls: Vec<u32>,
+    // Some reachable ecall takes a7 from somewhere other than a constant
+    // set in its basic block, so `syscalls` may be incomplete
+    pub dynamic_syscalls: bool,
+    // Some reachable JALR other than a return jumps to a target only known
+    // at run time. Code reached that way is not checked; a wild target traps
+    // on the sentinel.
+    pub indirect_jumps: bool,
+}
+
+impl PredecodedProgram {
+    // Walks the code reachable from the entrypoint and rejects programs the
+    // VM cannot run safely: an entrypoint outside the code, static branch,
+    // JAL or AUIPC+JALR targets outside it, and reachable invalid words.
+    // An ecall that sets a7 to SYS_EXIT right before it does not fall
+    // through, so the `unimp` compilers put after it is fine.
+    pub fn verify(&self) -> Result<Capabilities, RubicVError> {
+        let len = self.words.len();
+        if self.entrypoint >= len {
+            return Err(invalid(self.entrypoint, "entrypoint outside the code"));
+        }
+
+        // Superinstructions stand for their first instruction here
+        let instructions: Vec<PreDecodedInstruction> = (0..len).filter_map(|ppc| self.original(ppc)).collect();
+        let mut capabilities = Capabilities::default();
+        let mut reachable = vec![false; len];
+        let mut pending = vec![self.entrypoint];
+        while let Some(ppc) = pending.pop() {
+            if reachable[ppc] {
+                continue;
+            }
+            reachable[ppc] = true;
+            let insn = &instructions[ppc];
+            if !capabilities.kinds.contains(&insn.kind) {
+                capabilities.kinds.push(insn.kind);
+            }
+
+            // Falling off the end of the code runs into the sentinel
+            let mut falls_through = ppc + 1 < len;
+            let mut target = None;
+            match insn.kind {
+                InsnKind::INVALID => return Err(invalid(ppc, "invalid instruction reachable from the entrypoint")),
+                kind if kind.is_branch() => target = Some(insn.imm as i64),
+                InsnKind::JAL => {
+                    target = Some(insn.imm as i64);
+                    falls_through &= insn.rd != 0;
+                }
+                InsnKind::JALR => {
+                    match call_target(&instructions, ppc) {
+                        Some(addr) if addr % 4 != 0 => return Err(invalid(ppc, "misaligned jump target")),
+                        Some(addr) => target = Some(addr / 4),
+                        // Returns go back to after a call, which is walked anyway
+                        None => capabilities.indirect_jumps |= !(insn.rd == 0 && insn.rs1 == RA && insn.imm == 0),
+                    }
+                    falls_through &= insn.rd != 0;
+                }
+                InsnKind::ECALL => match self.constant_before(&instructions, A7, ppc) {
+                    Some(syscall) => {
+                        if !capabilities.syscalls.contains(&syscall) {
+                            capabilities.syscalls.push(syscall);
+                        }
+                        falls_through &= syscall != SYS_EXIT;
+                    }
+                    None => capabilities.dynamic_syscalls = true,
+                },
+                _ => {}
+            }
+
+            if let Some(target) = target {
+                if target < 0 || target >= len as i64 {
+                    return Err(invalid(ppc, "jump target outside the code"));
+                }
+                pending.push(target as usize);
+            }
+            if falls_through {
+                pending.push(ppc + 1);
+            }
+        }
+
+        capabilities.kinds.sort_by_key(|kind| *kind as u8);
+        capabilities.syscalls.sort_unstable();
+        Ok(capabilities)
+    }
+
+    // The value register `r` holds before `ppc` runs, if instructions
+    // earlier in its basic block set it to a constant
+    fn constant_before(&self, instructions: &[PreDecodedInstruction], r: u8, ppc: usize) -> Option<u32> {
+        if r == 0 {
+            return Some(0);
+        }
+        let start = self.blocks[self.blocks.partition_point(|block| block.start <= ppc) - 1].start;
+        let (at, writer) = instructions[start..ppc].iter()
+            .enumerate()
+            .rev()
+            .find(|(_, insn)| insn.kind.writes_rd() && insn.rd == r)?;
+        match writer.kind {
+            InsnKind::LUI => Some(writer.imm as u32),
+            InsnKind::ADDI => Some(self.constant_before(instructions, writer.rs1, start + at)?.wrapping_add(writer.imm as u32)),
+            _ => None,
+        }
+    }
+}
+
+// Byte address of a JALR at `ppc` off x0, or whose base comes from an
+// AUIPC right before it, the way compilers emit calls
+fn call_target(instructions: &[PreDecodedInstruction], ppc: usize) -> Option<i64> {
+    let jalr = &instructions[ppc];
+    if jalr.rs1 == 0 {
+        return Some((jalr.imm as u32 & !1) as i64);
+    }
+    let auipc = instructions[..ppc].last()?;
+    if auipc.kind != InsnKind::AUIPC || auipc.rd != jalr.rs1 || auipc.rd == 0 {
+        return None;
+    }
+    let base = ((ppc - 1) as u32 * 4).wrapping_add(auipc.imm as u32);
+    Some((base.wrapping_add(jalr.imm as u32) & !1) as i64)
+}
+
+fn invalid(ppc: usize, reason: &'static str) -> RubicVError {
+    RubicVError::InvalidProgram { pc: (ppc * 4) as u32, reason }
+}
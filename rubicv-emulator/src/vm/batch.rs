@@ -0,0 +1,242 @@
+use alloc::vec::Vec;
+use crate::instructions::{InsnKind, PreDecodedInstruction};
+use crate::syscalls::SyscallHandler;
+use super::{sign_extend, ExecutionResult, VM};
+
+// Runs one program over many inputs in lockstep. Each lane is a VM of its
+// own, typically forked from a VMTemplate with its args written to the
+// private region, and all lanes run the same predecoded instructions.
+//
+// Lanes at the same ppc run a basic block together: every instruction is
+// dispatched once and applied to all of them. After a branch the lanes with
+// the lowest ppc go first and the others wait, so lanes that took different
+// ways rejoin where the paths meet again. Metering and traps are per lane and
+// match VM::run: a lane that cannot pay for the whole block is stepped on its
+// own, and ecalls, ebreaks and invalid words are handled one lane at a time.
+//
+// Lanes are whole VMs rather than registers laid out struct-of-arrays: what
+// lockstep saves is the dispatch, paid once per instruction for the group,
+// and keeping VMs lets traps and short-on-fuel lanes go through VM::step and
+// handle_trap unchanged, and hands callers back VMs they can resume alone.
+pub struct Batch<'a> {
+    lanes: Vec<VM<'a>>,
+    group: Vec<usize>,
+}
+
+impl<'a> Batch<'a> {
+    pub fn new(lanes: Vec<VM<'a>>) -> Self {
+        if let Some(first) = lanes.first() {
+            let code = first.pre_decoded_instructions.as_ptr_range();
+            assert!(lanes.iter().all(|lane| lane.pre_decoded_instructions.as_ptr_range() == code), "batch lanes must run the same program");
+        }
+        let group = Vec::with_capacity(lanes.len());
+        Self { lanes, group }
+    }
+
+    pub fn lanes(&self) -> &[VM<'a>] {
+        &self.lanes
+    }
+
+    pub fn lanes_mut(&mut self) -> &mut [VM<'a>] {
+        &mut self.lanes
+    }
+
+    pub fn into_lanes(self) -> Vec<VM<'a>> {
+        self.lanes
+    }
+
+    // Like VM::run_with_syscalls for every lane, each with its own budget.
+    // The handler is shared and sees the lanes' ecalls in the order the batch
+    // reaches them: lowest ppc first, then lane order among lanes at one ppc.
+    pub fn run<S: SyscallHandler + ?Sized>(&mut self, syscalls: &mut S, max_fuel: Option<u64>) -> Vec<ExecutionResult> {
+        for lane in &mut self.lanes {
+            lane.start(max_fuel);
+        }
+        self.resume(syscalls)
+    }
+
+    // Runs every lane until it stops, and returns what stopped each one
+    pub fn resume<S: SyscallHandler + ?Sized>(&mut self, syscalls: &mut S) -> Vec<ExecutionResult> {
+        let mut results: Vec<Option<ExecutionResult>> = self.lanes.iter().map(|_| None).collect();
+        loop {
+            let running = self.lanes.iter().zip(&results).filter(|(_, result)| result.is_none());
+            let Some(ppc) = running.map(|(lane, _)| lane.ppc).min() else {
+                break;
+            };
+            let mut group = core::mem::take(&mut self.group);
+            group.clear();
+            for (i, lane) in self.lanes.iter_mut().enumerate() {
+                if results[i].is_some() || lane.ppc != ppc {
+                    continue;
+                }
+                if lane.pre_decoded_instructions[ppc].block_cost as u64 <= lane.fuel_remaining() {
+                    group.push(i);
+                } else {
+                    // Short on fuel: per-instruction metering, as in resume()
+                    results[i] = lane.single_step(syscalls);
+                }
+            }
+            if !group.is_empty() {
+                self.run_block(ppc, &group, syscalls, &mut results);
+            }
+            self.group = group;
+        }
+        results.into_iter().map(Option::unwrap).collect()
+    }
+
+    // Runs the block from `ppc` on every lane in `group`, all of which can pay for it
+    fn run_block<S: SyscallHandler + ?Sized>(&mut self, ppc: usize, group: &[usize], syscalls: &mut S, results: &mut [Option<ExecutionResult>]) {
+        let instructions = self.lanes[group[0]].pre_decoded_instructions;
+        let block = &instructions[ppc];
+        for &i in group {
+            let lane = &mut self.lanes[i];
+            lane.fuel_used += block.block_cost as u64;
+            lane.cycle_count += block.block_len as usize;
+        }
+        let end = ppc + block.block_len as usize;
+        for at in ppc..end {
+            // Fused pairs run one instruction at a time
+            let insn = match instructions.get(at + 1) {
+                Some(next) => instructions[at].unfused(next),
+                None => instructions[at].clone(),
+            };
+            if matches!(insn.kind, InsnKind::ECALL | InsnKind::EBREAK | InsnKind::INVALID) {
+                // Only ever the last of a block; hand back the rest, as resume_with does
+                for &i in group {
+                    let lane = &mut self.lanes[i];
+                    lane.ppc = at;
+                    let Err(trap) = lane.step() else { continue };
+                    if block.block_len > 1 {
+                        let insn = &instructions[at];
+                        lane.fuel_used -= (insn.block_cost - insn.cost) as u64;
+                        lane.cycle_count -= insn.block_len as usize;
+                    } else {
+                        lane.cycle_count -= 1;
+                    }
+                    results[i] = lane.handle_trap(trap, syscalls);
+                }
+                return;
+            }
+            self.execute(at, &insn, group, instructions.len());
+        }
+        if !instructions[end - 1].kind.ends_block() {
+            each(&mut self.lanes, group, |vm| vm.ppc = end);
+        }
+    }
+
+    // One instruction on every lane in `group`, dispatched once. Control
+    // transfers set each lane's ppc; other instructions leave it to the block.
+    fn execute(&mut self, ppc: usize, insn: &PreDecodedInstruction, group: &[usize], len: usize) {
+        let (rd, rs1, rs2) = (insn.rd as usize, insn.rs1 as usize, insn.rs2 as usize);
+        let imm = insn.imm;
+        let link = ((ppc + 1) * 4) as u32;
+        let lanes = &mut self.lanes;
+        match insn.kind {
+            InsnKind::ADD => compute(lanes, group, rd, rs1, rs2, |a, b| a.wrapping_add(b)),
+            InsnKind::SUB => compute(lanes, group, rd, rs1, rs2, |a, b| a.wrapping_sub(b)),
+            InsnKind::XOR => compute(lanes, group, rd, rs1, rs2, |a, b| a ^ b),
+            InsnKind::OR => compute(lanes, group, rd, rs1, rs2, |a, b| a | b),
+            InsnKind::AND => compute(lanes, group, rd, rs1, rs2, |a, b| a & b),
+            InsnKind::SLL => compute(lanes, group, rd, rs1, rs2, |a, b| a << (b & 0x1f)),
+            InsnKind::SRL => compute(lanes, group, rd, rs1, rs2, |a, b| a >> (b & 0x1f)),
+            InsnKind::SRA => compute(lanes, group, rd, rs1, rs2, |a, b| ((a as i32) >> (b & 0x1f)) as u32),
+            InsnKind::SLT => compute(lanes, group, rd, rs1, rs2, |a, b| ((a as i32) < (b as i32)) as u32),
+            InsnKind::SLTU => compute(lanes, group, rd, rs1, rs2, |a, b| (a < b) as u32),
+            InsnKind::ADDI => compute(lanes, group, rd, rs1, 0, |a, _| a.wrapping_add(imm as u32)),
+            InsnKind::XORI => compute(lanes, group, rd, rs1, 0, |a, _| a ^ imm as u32),
+            InsnKind::ORI => compute(lanes, group, rd, rs1, 0, |a, _| a | imm as u32),
+            InsnKind::ANDI => compute(lanes, group, rd, rs1, 0, |a, _| a & imm as u32),
+            InsnKind::SLLI => compute(lanes, group, rd, rs1, 0, |a, _| a << (imm as u32 & 0x1f)),
+            InsnKind::SRLI => compute(lanes, group, rd, rs1, 0, |a, _| a >> (imm as u32 & 0x1f)),
+            InsnKind::SRAI => compute(lanes, group, rd, rs1, 0, |a, _| ((a as i32) >> (imm as u32 & 0x1f)) as u32),
+            InsnKind::SLTI => compute(lanes, group, rd, rs1, 0, |a, _| ((a as i32) < imm) as u32),
+            InsnKind::SLTIU => compute(lanes, group, rd, rs1, 0, |a, _| (a < imm as u32) as u32),
+            InsnKind::LUI => compute(lanes, group, rd, 0, 0, |_, _| imm as u32),
+            InsnKind::AUIPC => compute(lanes, group, rd, 0, 0, |_, _| ((ppc as u32) * 4).wrapping_add(imm as u32)),
+            InsnKind::MUL => compute(lanes, group, rd, rs1, rs2, |a, b| a.wrapping_mul(b)),
+            InsnKind::MULH => compute(lanes, group, rd, rs1, rs2, |a, b| ((a as i32 as i64).wrapping_mul(b as i32 as i64) >> 32) as u32),
+            InsnKind::MULHSU => compute(lanes, group, rd, rs1, rs2, |a, b| ((a as i32 as i64).wrapping_mul(b as u64 as i64) >> 32) as u32),
+            InsnKind::MULHU => compute(lanes, group, rd, rs1, rs2, |a, b| ((a as u64).wrapping_mul(b as u64) >> 32) as u32),
+            InsnKind::DIV => compute(lanes, group, rd, rs1, rs2, |a, b| {
+                if b == 0 { u32::MAX } else { (a as i32).wrapping_div(b as i32) as u32 }
+            }),
+            InsnKind::DIVU => compute(lanes, group, rd, rs1, rs2, |a, b| a.checked_div(b).unwrap_or(u32::MAX)),
+            InsnKind::REM => compute(lanes, group, rd, rs1, rs2, |a, b| {
+                if b == 0 { a } else { (a as i32).wrapping_rem(b as i32) as u32 }
+            }),
+            InsnKind::REMU => compute(lanes, group, rd, rs1, rs2, |a, b| a.checked_rem(b).unwrap_or(a)),
+
+            InsnKind::BEQ => branch(lanes, group, ppc, imm, |a, b| a == b, rs1, rs2),
+            InsnKind::BNE => branch(lanes, group, ppc, imm, |a, b| a != b, rs1, rs2),
+            InsnKind::BLT => branch(lanes, group, ppc, imm, |a, b| (a as i32) < (b as i32), rs1, rs2),
+            InsnKind::BGE => branch(lanes, group, ppc, imm, |a, b| (a as i32) >= (b as i32), rs1, rs2),
+            InsnKind::BLTU => branch(lanes, group, ppc, imm, |a, b| a < b, rs1, rs2),
+            InsnKind::BGEU => branch(lanes, group, ppc, imm, |a, b| a >= b, rs1, rs2),
+            InsnKind::JAL => each(lanes, group, |vm| {
+                if rd != 0 {
+                    vm.registers[rd] = link;
+                }
+                vm.ppc = imm as usize;
+            }),
+            InsnKind::JALR => each(lanes, group, |vm| {
+                let target_addr = vm.registers[rs1].wrapping_add(imm as u32) & !1;
+                if rd != 0 {
+                    vm.registers[rd] = link;
+                }
+                vm.ppc = ((target_addr / 4) as usize).min(len - 1);
+            }),
+
+            InsnKind::LB => load(lanes, group, rd, rs1, imm, 1, |value| sign_extend(value, 8)),
+            InsnKind::LH => load(lanes, group, rd, rs1, imm, 2, |value| sign_extend(value, 16)),
+            InsnKind::LW => load(lanes, group, rd, rs1, imm, 4, |value| value),
+            InsnKind::LBU => load(lanes, group, rd, rs1, imm, 1, |value| value),
+            InsnKind::LHU => load(lanes, group, rd, rs1, imm, 2, |value| value),
+            InsnKind::SB => store(lanes, group, rs1, rs2, imm, 1),
+            InsnKind::SH => store(lanes, group, rs1, rs2, imm, 2),
+            InsnKind::SW => store(lanes, group, rs1, rs2, imm, 4),
+            // Nothing to do without hooks
+            InsnKind::PROBE => {},
+
+            // Traps are handled per lane by run_block, and unfused() never
+            // returns a superinstruction
+            _ => unreachable!("{:?} in a lockstep block", insn.kind),
+        }
+    }
+}
+
+#[inline(always)]
+fn each<'a>(lanes: &mut [VM<'a>], group: &[usize], mut f: impl FnMut(&mut VM<'a>)) {
+    for &i in group {
+        f(&mut lanes[i]);
+    }
+}
+
+// Writes to x0 were turned into no-ops or probes by the predecoder, so rd is
+// written unchecked here and in load
+#[inline(always)]
+fn compute(lanes: &mut [VM], group: &[usize], rd: usize, rs1: usize, rs2: usize, f: impl Fn(u32, u32) -> u32) {
+    each(lanes, group, |vm| vm.registers[rd] = f(vm.registers[rs1], vm.registers[rs2]));
+}
+
+#[inline(always)]
+fn branch(lanes: &mut [VM], group: &[usize], ppc: usize, target: i32, taken: impl Fn(u32, u32) -> bool, rs1: usize, rs2: usize) {
+    each(lanes, group, |vm| {
+        vm.ppc = if taken(vm.registers[rs1], vm.registers[rs2]) { target as usize } else { ppc + 1 };
+    });
+}
+
+#[inline(always)]
+fn load(lanes: &mut [VM], group: &[usize], rd: usize, rs1: usize, imm: i32, size: u8, extend: impl Fn(u32) -> u32) {
+    each(lanes, group, |vm| {
+        let addr = vm.registers[rs1].wrapping_add(imm as u32);
+        vm.registers[rd] = extend(vm.read_sized(addr, size));
+    });
+}
+
+#[inline(always)]
+fn store(lanes: &mut [VM], group: &[usize], rs1: usize, rs2: usize, imm: i32, size: u8) {
+    each(lanes, group, |vm| {
+        let addr = vm.registers[rs1].wrapping_add(imm as u32);
+        vm.write_sized(addr, size, vm.registers[rs2]);
+    });
+}
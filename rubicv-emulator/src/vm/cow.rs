@@ -0,0 +1,118 @@
+use core::ffi::c_void;
+use core::ops::{Deref, DerefMut};
+use crate::hooks::{Hooks, NoHooks};
+use crate::memory::PRIVATE_SIZE;
+use super::{VMTemplate, VM};
+
+// A template's private region in a memory file, for forks that share its
+// pages copy-on-write. Each fork maps the file privately, so the kernel only
+// copies a page the first time that child writes to it, and a fork costs a
+// mapping rather than PRIVATE_SIZE bytes of copying. Reads and writes of the
+// mapping are ordinary memory accesses, so the VM runs exactly as it does on
+// a slab of its own.
+//
+//   let shared = template.share().expect("memfd");
+//   let mut child = shared.fork().expect("mmap");
+//   child.private_mut()[ARGS_START as usize..][..4].copy_from_slice(&arg.to_le_bytes());
+//   child.add_fuel(fuel);
+//   child.resume(&mut syscalls);
+pub struct SharedPrivate<'t, 'a> {
+    template: &'t VMTemplate<'a>,
+    fd: i32,
+}
+
+// A child VM together with the copy-on-write mapping it runs on. Derefs to
+// the VM; the mapping is unmapped when it is dropped.
+pub struct CowFork<'a, H: Hooks = NoHooks> {
+    vm: VM<'a, H>,
+    map: *mut u8,
+}
+
+impl<'a> VMTemplate<'a> {
+    // None if the host refuses the memory file
+    pub fn share(&self) -> Option<SharedPrivate<'_, 'a>> {
+        let private = self.private();
+        unsafe {
+            let fd = memfd_create(c"rubicv-private".as_ptr(), MFD_CLOEXEC);
+            if fd < 0 {
+                return None;
+            }
+            let shared = SharedPrivate { template: self, fd };
+            if ftruncate(fd, PRIVATE_SIZE as i64) != 0 {
+                return None;
+            }
+            let map = mmap(core::ptr::null_mut(), PRIVATE_SIZE as usize, PROT_READ | PROT_WRITE, MAP_SHARED, fd, 0);
+            if map == MAP_FAILED {
+                return None;
+            }
+            core::ptr::copy_nonoverlapping(private.as_ptr(), map as *mut u8, PRIVATE_SIZE as usize);
+            munmap(map, PRIVATE_SIZE as usize);
+            Some(shared)
+        }
+    }
+}
+
+impl<'a> SharedPrivate<'_, 'a> {
+    // Like VMTemplate::fork. None if the mapping fails.
+    pub fn fork(&self) -> Option<CowFork<'a>> {
+        self.fork_with_hooks(NoHooks)
+    }
+
+    pub fn fork_with_hooks<H: Hooks>(&self, hooks: H) -> Option<CowFork<'a, H>> {
+        let map = unsafe { mmap(core::ptr::null_mut(), PRIVATE_SIZE as usize, PROT_READ | PROT_WRITE, MAP_PRIVATE, self.fd, 0) };
+        if map == MAP_FAILED {
+            return None;
+        }
+        let private = core::ptr::slice_from_raw_parts_mut(map as *mut u8, PRIVATE_SIZE as usize);
+        Some(CowFork { vm: self.template.child(private, hooks), map: map as *mut u8 })
+    }
+}
+
+impl Drop for SharedPrivate<'_, '_> {
+    // Mappings keep the file alive, so forks outlive this
+    fn drop(&mut self) {
+        unsafe { close(self.fd) };
+    }
+}
+
+impl<H: Hooks> CowFork<'_, H> {
+    // The child's private region, e.g. to write its args from ARGS_START
+    pub fn private_mut(&mut self) -> &mut [u8] {
+        unsafe { core::slice::from_raw_parts_mut(self.map, PRIVATE_SIZE as usize) }
+    }
+}
+
+impl<'a, H: Hooks> Deref for CowFork<'a, H> {
+    type Target = VM<'a, H>;
+
+    fn deref(&self) -> &VM<'a, H> {
+        &self.vm
+    }
+}
+
+impl<H: Hooks> DerefMut for CowFork<'_, H> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.vm
+    }
+}
+
+impl<H: Hooks> Drop for CowFork<'_, H> {
+    fn drop(&mut self) {
+        unsafe { munmap(self.map as *mut c_void, PRIVATE_SIZE as usize) };
+    }
+}
+
+const PROT_READ: i32 = 1;
+const PROT_WRITE: i32 = 2;
+const MAP_SHARED: i32 = 1;
+const MAP_PRIVATE: i32 = 2;
+const MAP_FAILED: *mut c_void = !0 as *mut c_void;
+const MFD_CLOEXEC: u32 = 1;
+
+extern "C" {
+    fn memfd_create(name: *const core::ffi::c_char, flags: u32) -> i32;
+    fn ftruncate(fd: i32, length: i64) -> i32;
+    fn mmap(addr: *mut c_void, len: usize, prot: i32, flags: i32, fd: i32, offset: i64) -> *mut c_void;
+    fn munmap(addr: *mut c_void, len: usize) -> i32;
+    fn close(fd: i32) -> i32;
+}
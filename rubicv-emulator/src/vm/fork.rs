@@ -0,0 +1,79 @@
+use alloc::boxed::Box;
+use core::marker::PhantomData;
+use crate::hooks::{Hooks, NoHooks};
+use crate::instructions::PreDecodedInstruction;
+use crate::memory::PRIVATE_SIZE;
+use super::{MemoryPolicy, VM};
+
+// A paused VM frozen for forking, typically after the guest has done its
+// setup and stopped on a marker syscall (SyscallResult::Yield).
+//
+// The template keeps its own copy of the private region (RW and args) and
+// borrows the rest of the original slab, which guests cannot write. Children
+// read that shared part in place and get a fresh copy of the private region,
+// so a fork costs PRIVATE_SIZE bytes instead of the whole slab. With the
+// std feature on Linux, share() goes further: children map the private
+// region copy-on-write and only copy the pages they write.
+pub struct VMTemplate<'a> {
+    registers: [u32; 32],
+    cycle_count: usize,
+    fuel_used: u64,
+    ppc: usize,
+    private: Box<[u8]>,
+    ro_slab: *const [u8],
+    pre_decoded_instructions: &'a [PreDecodedInstruction],
+}
+
+impl<'a, H: Hooks, M: MemoryPolicy> VM<'a, H, M> {
+    // The slab this VM reads from must outlive the template and its children
+    pub fn freeze(&self) -> VMTemplate<'a> {
+        let private = unsafe { core::slice::from_raw_parts(self.memory_slab as *const u8, PRIVATE_SIZE as usize) };
+        VMTemplate {
+            registers: self.registers,
+            cycle_count: self.cycle_count,
+            fuel_used: self.fuel_used,
+            ppc: self.ppc,
+            private: private.into(),
+            ro_slab: self.ro_slab,
+            pre_decoded_instructions: self.pre_decoded_instructions,
+        }
+    }
+}
+
+impl<'a> VMTemplate<'a> {
+    // `private` must hold at least PRIVATE_SIZE bytes. The child starts where
+    // the template stopped with no fuel left: top it up with add_fuel() and
+    // continue with resume(). Per-request args can be written to ARGS_START
+    // in `private` after forking.
+    pub fn fork(&self, private: *mut [u8]) -> VM<'a> {
+        self.fork_with_hooks(private, NoHooks)
+    }
+
+    pub fn fork_with_hooks<H: Hooks>(&self, private: *mut [u8], hooks: H) -> VM<'a, H> {
+        assert!(private.len() >= PRIVATE_SIZE as usize, "private slab too small for a fork");
+        unsafe { core::ptr::copy_nonoverlapping(self.private.as_ptr(), private as *mut u8, PRIVATE_SIZE as usize) };
+        self.child(private, hooks)
+    }
+
+    // The template's private region, as children start with it
+    #[cfg(all(feature = "std", target_os = "linux"))]
+    pub(super) fn private(&self) -> &[u8] {
+        &self.private
+    }
+
+    // A child on `private`, which already holds the template's private region
+    pub(super) fn child<H: Hooks>(&self, private: *mut [u8], hooks: H) -> VM<'a, H> {
+        VM::<H> {
+            registers: self.registers,
+            cycle_count: self.cycle_count,
+            fuel_used: self.fuel_used,
+            fuel_limit: self.fuel_used,
+            memory_slab: private,
+            ro_slab: self.ro_slab,
+            ppc: self.ppc,
+            pre_decoded_instructions: self.pre_decoded_instructions,
+            hooks,
+            _phantom: PhantomData,
+        }
+    }
+}
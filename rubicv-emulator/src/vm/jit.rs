@@ -0,0 +1,563 @@
+use alloc::vec;
+use alloc::vec::Vec;
+use core::ffi::c_void;
+use crate::instructions::{InsnKind, PreDecodedInstruction};
+use crate::memory::{MEMORY_MASK, PRIVATE_SIZE, RW_MASK};
+use crate::syscalls::SyscallHandler;
+use super::{ExecutionResult, VM};
+
+// A program translated to x86-64, for VMs without hooks on the default
+// WrapMemory policy. Guest registers stay in VM::registers and every load
+// and store is masked the way the interpreter masks it.
+//
+// Fuel is charged per basic block like resume() does: each instruction
+// has an entry stub that pays for the rest of its block, and a block that
+// does not fit hands the instruction to the interpreter instead. Ecall,
+// ebreak, invalid words and misaligned accesses are not translated either;
+// the native code refunds the rest of the block and returns, and the
+// interpreter runs that one instruction as single_step() would.
+//
+//   let code = JitCode::new(&program.instructions).expect("no executable memory");
+//   let mut vm = VM::new(slab, program.entrypoint, &program.instructions);
+//   vm.run_jit(&code, &mut syscalls, Some(fuel));
+pub struct JitCode {
+    map: *mut u8,
+    map_len: usize,
+    // Native address of each instruction's entry stub, for JALR and for
+    // re-entering after the interpreter ran an instruction
+    entries: Vec<usize>,
+}
+
+// The code is only read once mapped
+unsafe impl Send for JitCode {}
+unsafe impl Sync for JitCode {}
+
+// Shared with the native code, which keeps a pointer to it in r14
+#[repr(C)]
+struct JitState {
+    registers: *mut u32,
+    memory: *mut u8,
+    ro: *const u8,
+    entries: *const usize,
+    fuel_used: u64,
+    fuel_limit: u64,
+    cycles: u64,
+    // Where the interpreter has to take over
+    ppc: u64,
+}
+
+const FUEL_USED: u8 = 32;
+const FUEL_LIMIT: u8 = 40;
+const CYCLES: u8 = 48;
+const PPC: u8 = 56;
+
+impl JitCode {
+    // `instructions` must be the slice the VMs running this code are built
+    // with. None if the host does not hand out executable memory.
+    pub fn new(instructions: &[PreDecodedInstruction]) -> Option<Self> {
+        let mut asm = Assembler::new(instructions.len());
+        let offsets = asm.program(instructions);
+        let code = asm.finish();
+        let map_len = code.len().next_multiple_of(PAGE_SIZE);
+        let map = unsafe {
+            let map = mmap(core::ptr::null_mut(), map_len, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+            if map == MAP_FAILED {
+                return None;
+            }
+            core::ptr::copy_nonoverlapping(code.as_ptr(), map as *mut u8, code.len());
+            if mprotect(map, map_len, PROT_READ | PROT_EXEC) != 0 {
+                munmap(map, map_len);
+                return None;
+            }
+            map as *mut u8
+        };
+        let entries = offsets.iter().map(|offset| map as usize + offset).collect();
+        Some(Self { map, map_len, entries })
+    }
+
+    // Runs from the VM's ppc until an instruction needs the interpreter
+    fn enter(&self, vm: &mut VM<'_>) {
+        let mut state = JitState {
+            registers: vm.registers.as_mut_ptr(),
+            memory: vm.memory_slab as *mut u8,
+            ro: vm.ro_slab as *const u8,
+            entries: self.entries.as_ptr(),
+            fuel_used: vm.fuel_used,
+            fuel_limit: vm.fuel_limit,
+            cycles: vm.cycle_count as u64,
+            ppc: 0,
+        };
+        unsafe {
+            let run: extern "sysv64" fn(*mut JitState, usize) = core::mem::transmute(self.map);
+            run(&mut state, self.entries[vm.ppc]);
+        }
+        vm.fuel_used = state.fuel_used;
+        vm.cycle_count = state.cycles as usize;
+        vm.ppc = state.ppc as usize;
+    }
+}
+
+impl Drop for JitCode {
+    fn drop(&mut self) {
+        unsafe { munmap(self.map as *mut c_void, self.map_len) };
+    }
+}
+
+impl<'a> VM<'a> {
+    pub fn run_jit<S: SyscallHandler + ?Sized>(&mut self, code: &JitCode, syscalls: &mut S, max_fuel: Option<u64>) -> ExecutionResult {
+        self.start(max_fuel);
+        self.resume_jit(code, syscalls)
+    }
+
+    // resume(), running `code` natively. Stops where resume() would.
+    pub fn resume_jit<S: SyscallHandler + ?Sized>(&mut self, code: &JitCode, syscalls: &mut S) -> ExecutionResult {
+        assert_eq!(code.entries.len(), self.pre_decoded_instructions.len(), "native code built for another program");
+        loop {
+            code.enter(self);
+            if let Some(result) = self.single_step(syscalls) {
+                return result;
+            }
+        }
+    }
+}
+
+const PAGE_SIZE: usize = 4096;
+const PROT_READ: i32 = 1;
+const PROT_WRITE: i32 = 2;
+const PROT_EXEC: i32 = 4;
+const MAP_PRIVATE: i32 = 2;
+const MAP_ANONYMOUS: i32 = 0x20;
+const MAP_FAILED: *mut c_void = !0 as *mut c_void;
+
+extern "C" {
+    fn mmap(addr: *mut c_void, len: usize, prot: i32, flags: i32, fd: i32, offset: i64) -> *mut c_void;
+    fn mprotect(addr: *mut c_void, len: usize, prot: i32) -> i32;
+    fn munmap(addr: *mut c_void, len: usize) -> i32;
+}
+
+// Host registers. rbx points at the guest registers, r12 and r13 at the
+// two memory slabs, r14 at the JitState and r15 at the entry table; eax,
+// ecx and edx are scratch.
+const EAX: u8 = 0;
+const ECX: u8 = 1;
+const EDX: u8 = 2;
+
+// Condition codes, as in jcc/setcc
+const CC_B: u8 = 0x2;
+const CC_AE: u8 = 0x3;
+const CC_E: u8 = 0x4;
+const CC_NE: u8 = 0x5;
+const CC_L: u8 = 0xC;
+const CC_GE: u8 = 0xD;
+
+#[derive(Clone, Copy)]
+enum Label {
+    // Pays for the rest of the block, then runs the body
+    Entry(usize),
+    Body(usize),
+    // Hands the instruction to the interpreter with nothing charged for it
+    Exit(usize),
+    // Refunds the rest of the block, then exits
+    Bail(usize),
+    Epilogue,
+}
+
+struct Assembler {
+    code: Vec<u8>,
+    // rel32 displacements to fill in once every label is placed
+    fixups: Vec<(usize, Label)>,
+    entry: Vec<usize>,
+    body: Vec<usize>,
+    exit: Vec<usize>,
+    bail: Vec<usize>,
+    // Loads and stores that bail when misaligned, stubbed after the bodies
+    bailing: Vec<usize>,
+    epilogue: usize,
+}
+
+impl Assembler {
+    fn new(len: usize) -> Self {
+        Self {
+            code: Vec::new(),
+            fixups: Vec::new(),
+            entry: vec![usize::MAX; len],
+            body: vec![usize::MAX; len],
+            exit: vec![usize::MAX; len],
+            bail: vec![usize::MAX; len],
+            bailing: Vec::new(),
+            epilogue: 0,
+        }
+    }
+
+    // Lays out the prologue, the bodies in program order with the entry
+    // stubs of block leaders right before them, then the other stubs.
+    // Returns the offset of every entry stub.
+    fn program(&mut self, instructions: &[PreDecodedInstruction]) -> Vec<usize> {
+        // extern "sysv64" fn(state: *mut JitState, entry: usize)
+        self.emit(&[0x53, 0x55, 0x41, 0x54, 0x41, 0x55, 0x41, 0x56, 0x41, 0x57]); // push rbx, rbp, r12-r15
+        self.emit(&[0x49, 0x89, 0xFE]); // mov r14, rdi
+        self.emit(&[0x49, 0x8B, 0x5E, 0x00]); // mov rbx, [r14]
+        self.emit(&[0x4D, 0x8B, 0x66, 0x08]); // mov r12, [r14 + 8]
+        self.emit(&[0x4D, 0x8B, 0x6E, 0x10]); // mov r13, [r14 + 16]
+        self.emit(&[0x4D, 0x8B, 0x7E, 0x18]); // mov r15, [r14 + 24]
+        self.emit(&[0xFF, 0xE6]); // jmp rsi
+        self.epilogue = self.code.len();
+        self.emit(&[0x41, 0x5F, 0x41, 0x5E, 0x41, 0x5D, 0x41, 0x5C, 0x5D, 0x5B, 0xC3]); // pop r15-r12, rbp, rbx; ret
+
+        let len = instructions.len();
+        for ppc in 0..len {
+            // Nothing falls into the middle of a block from elsewhere
+            if ppc == 0 || instructions[ppc - 1].block_len == 1 {
+                self.entry_stub(ppc, &instructions[ppc]);
+            }
+            self.body[ppc] = self.code.len();
+            // The JIT has no superinstructions of its own
+            let insn = instructions[ppc].unfused(instructions.get(ppc + 1).unwrap_or(&instructions[ppc]));
+            self.instruction(ppc, &insn, len);
+        }
+        for ppc in core::mem::take(&mut self.bailing) {
+            self.bail_stub(ppc, &instructions[ppc]);
+        }
+        for (ppc, insn) in instructions.iter().enumerate() {
+            if self.entry[ppc] == usize::MAX {
+                self.entry_stub(ppc, insn);
+                self.jmp(Label::Body(ppc));
+            }
+            self.exit[ppc] = self.code.len();
+            self.mov_state_imm(PPC, ppc as u32);
+            self.jmp(Label::Epilogue);
+        }
+        self.entry.clone()
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        for &(at, label) in &self.fixups {
+            let target = match label {
+                Label::Entry(ppc) => self.entry[ppc],
+                Label::Body(ppc) => self.body[ppc],
+                Label::Exit(ppc) => self.exit[ppc],
+                Label::Bail(ppc) => self.bail[ppc],
+                Label::Epilogue => self.epilogue,
+            };
+            let rel = target as i64 - (at as i64 + 4);
+            self.code[at..at + 4].copy_from_slice(&(rel as i32).to_le_bytes());
+        }
+        self.code
+    }
+
+    // Charges the block from `ppc` on if it fits, like resume() does
+    fn entry_stub(&mut self, ppc: usize, insn: &PreDecodedInstruction) {
+        self.entry[ppc] = self.code.len();
+        self.emit(&[0x49, 0x8B, 0x46, FUEL_LIMIT]); // mov rax, [r14 + fuel_limit]
+        self.emit(&[0x49, 0x2B, 0x46, FUEL_USED]); // sub rax, [r14 + fuel_used]
+        self.mov_imm(ECX, insn.block_cost);
+        self.emit(&[0x48, 0x39, 0xC8]); // cmp rax, rcx
+        self.jcc(CC_B, Label::Exit(ppc));
+        self.emit(&[0x49, 0x01, 0x4E, FUEL_USED]); // add [r14 + fuel_used], rcx
+        self.emit(&[0x49, 0x81, 0x46, CYCLES]); // add qword [r14 + cycles], imm32
+        self.imm32(insn.block_len as u32);
+    }
+
+    // The interpreter takes over at `ppc` as if the block had been entered
+    // there
+    fn bail_stub(&mut self, ppc: usize, insn: &PreDecodedInstruction) {
+        self.bail[ppc] = self.code.len();
+        self.mov_imm(ECX, insn.block_cost);
+        self.emit(&[0x49, 0x29, 0x4E, FUEL_USED]); // sub [r14 + fuel_used], rcx
+        self.emit(&[0x49, 0x81, 0x6E, CYCLES]); // sub qword [r14 + cycles], imm32
+        self.imm32(insn.block_len as u32);
+        self.jmp(Label::Exit(ppc));
+    }
+
+    fn instruction(&mut self, ppc: usize, insn: &PreDecodedInstruction, len: usize) {
+        let (rd, rs1, rs2, imm) = (insn.rd, insn.rs1, insn.rs2, insn.imm as u32);
+        // Out-of-range static targets land on the sentinel
+        let target = (insn.imm as usize).min(len - 1);
+        let link = ((ppc + 1) * 4) as u32;
+        match insn.kind {
+            // Probes do nothing under WrapMemory
+            InsnKind::PROBE => {},
+
+            InsnKind::ADD => self.register_op(&[0x03], rd, rs1, rs2),
+            InsnKind::SUB => self.register_op(&[0x2B], rd, rs1, rs2),
+            InsnKind::XOR => self.register_op(&[0x33], rd, rs1, rs2),
+            InsnKind::OR => self.register_op(&[0x0B], rd, rs1, rs2),
+            InsnKind::AND => self.register_op(&[0x23], rd, rs1, rs2),
+            InsnKind::MUL => self.register_op(&[0x0F, 0xAF], rd, rs1, rs2),
+            InsnKind::SLL => self.shift_op(0xE0, rd, rs1, rs2),
+            InsnKind::SRL => self.shift_op(0xE8, rd, rs1, rs2),
+            InsnKind::SRA => self.shift_op(0xF8, rd, rs1, rs2),
+            InsnKind::SLT => self.compare_op(CC_L, rd, rs1, Some(rs2), 0),
+            InsnKind::SLTU => self.compare_op(CC_B, rd, rs1, Some(rs2), 0),
+
+            InsnKind::ADDI => self.immediate_op(0x05, rd, rs1, imm),
+            InsnKind::XORI => self.immediate_op(0x35, rd, rs1, imm),
+            InsnKind::ORI => self.immediate_op(0x0D, rd, rs1, imm),
+            InsnKind::ANDI => self.immediate_op(0x25, rd, rs1, imm),
+            InsnKind::SLLI => self.shift_imm(0xE0, rd, rs1, imm),
+            InsnKind::SRLI => self.shift_imm(0xE8, rd, rs1, imm),
+            InsnKind::SRAI => self.shift_imm(0xF8, rd, rs1, imm),
+            InsnKind::SLTI => self.compare_op(CC_L, rd, rs1, None, imm),
+            InsnKind::SLTIU => self.compare_op(CC_B, rd, rs1, None, imm),
+            InsnKind::LUI => self.store_guest_imm(rd, imm),
+            InsnKind::AUIPC => self.store_guest_imm(rd, ((ppc as u32) * 4).wrapping_add(imm)),
+
+            InsnKind::MULH | InsnKind::MULHSU | InsnKind::MULHU => {
+                // The 64-bit product of the widened operands, high half
+                if insn.kind == InsnKind::MULHU {
+                    self.load_guest(EAX, rs1);
+                } else {
+                    self.emit(&[0x48, 0x63, 0x43, 4 * rs1]); // movsxd rax, rs1
+                }
+                if insn.kind == InsnKind::MULH {
+                    self.emit(&[0x48, 0x63, 0x4B, 4 * rs2]); // movsxd rcx, rs2
+                } else {
+                    self.load_guest(ECX, rs2);
+                }
+                self.emit(&[0x48, 0x0F, 0xAF, 0xC1]); // imul rax, rcx
+                self.emit(&[0x48, 0xC1, 0xE8, 0x20]); // shr rax, 32
+                self.store_guest(rd, EAX);
+            },
+            InsnKind::DIV | InsnKind::DIVU | InsnKind::REM | InsnKind::REMU => self.division(insn.kind, rd, rs1, rs2),
+
+            InsnKind::BEQ => self.branch(CC_E, rs1, rs2, target),
+            InsnKind::BNE => self.branch(CC_NE, rs1, rs2, target),
+            InsnKind::BLT => self.branch(CC_L, rs1, rs2, target),
+            InsnKind::BGE => self.branch(CC_GE, rs1, rs2, target),
+            InsnKind::BLTU => self.branch(CC_B, rs1, rs2, target),
+            InsnKind::BGEU => self.branch(CC_AE, rs1, rs2, target),
+            InsnKind::JAL => {
+                self.store_guest_imm(rd, link);
+                self.jmp(Label::Entry(target));
+            },
+            InsnKind::JALR => {
+                // rs1 may be rd, so it is read before the link is written.
+                // (rs1 + imm) & !1 then / 4 drops the same bits as >> 2.
+                self.load_guest(EAX, rs1);
+                self.emit(&[0x05]); // add eax, imm32
+                self.imm32(imm);
+                self.emit(&[0xC1, 0xE8, 0x02]); // shr eax, 2
+                self.mov_imm(ECX, (len - 1) as u32);
+                self.emit(&[0x39, 0xC8]); // cmp eax, ecx
+                self.emit(&[0x0F, 0x47, 0xC1]); // cmova eax, ecx
+                self.store_guest_imm(rd, link);
+                self.emit(&[0x41, 0xFF, 0x24, 0xC7]); // jmp [r15 + rax * 8]
+            },
+
+            InsnKind::LB => self.load(ppc, insn, 1, &[0x0F, 0xBE]),
+            InsnKind::LH => self.load(ppc, insn, 2, &[0x0F, 0xBF]),
+            InsnKind::LW => self.load(ppc, insn, 4, &[0x8B]),
+            InsnKind::LBU => self.load(ppc, insn, 1, &[0x0F, 0xB6]),
+            InsnKind::LHU => self.load(ppc, insn, 2, &[0x0F, 0xB7]),
+            InsnKind::SB => self.store(ppc, insn, &[0x41, 0x88]),
+            InsnKind::SH => self.store(ppc, insn, &[0x66, 0x41, 0x89]),
+            InsnKind::SW => self.store(ppc, insn, &[0x41, 0x89]),
+
+            // ecall, ebreak and invalid words
+            _ => self.bail_stub(ppc, insn),
+        }
+    }
+
+    // rd = rs1 op rs2, with op taking eax and a guest register
+    fn register_op(&mut self, op: &[u8], rd: u8, rs1: u8, rs2: u8) {
+        self.load_guest(EAX, rs1);
+        self.emit(op);
+        self.emit(&[0x43, 4 * rs2]); // eax, [rbx + rs2]
+        self.store_guest(rd, EAX);
+    }
+
+    fn immediate_op(&mut self, op: u8, rd: u8, rs1: u8, imm: u32) {
+        self.load_guest(EAX, rs1);
+        self.emit(&[op]); // op eax, imm32
+        self.imm32(imm);
+        self.store_guest(rd, EAX);
+    }
+
+    // x86 masks 32-bit shift counts to five bits, like RV32
+    fn shift_op(&mut self, op: u8, rd: u8, rs1: u8, rs2: u8) {
+        self.load_guest(EAX, rs1);
+        self.load_guest(ECX, rs2);
+        self.emit(&[0xD3, op]); // shl/shr/sar eax, cl
+        self.store_guest(rd, EAX);
+    }
+
+    fn shift_imm(&mut self, op: u8, rd: u8, rs1: u8, imm: u32) {
+        self.load_guest(EAX, rs1);
+        self.emit(&[0xC1, op, (imm & 0x1f) as u8]); // shl/shr/sar eax, imm8
+        self.store_guest(rd, EAX);
+    }
+
+    // rd = rs1 < rs2 (or imm), signed or not depending on `cc`
+    fn compare_op(&mut self, cc: u8, rd: u8, rs1: u8, rs2: Option<u8>, imm: u32) {
+        self.emit(&[0x31, 0xD2]); // xor edx, edx
+        self.load_guest(EAX, rs1);
+        match rs2 {
+            Some(rs2) => self.emit(&[0x3B, 0x43, 4 * rs2]), // cmp eax, [rbx + rs2]
+            None => {
+                self.emit(&[0x3D]); // cmp eax, imm32
+                self.imm32(imm);
+            },
+        }
+        self.emit(&[0x0F, 0x90 | cc, 0xC2]); // setcc dl
+        self.store_guest(rd, EDX);
+    }
+
+    // Division by zero and i32::MIN / -1 give RISC-V's results instead of
+    // the x86 fault
+    fn division(&mut self, kind: InsnKind, rd: u8, rs1: u8, rs2: u8) {
+        let signed = matches!(kind, InsnKind::DIV | InsnKind::REM);
+        let remainder = matches!(kind, InsnKind::REM | InsnKind::REMU);
+        self.load_guest(EAX, rs1);
+        self.load_guest(ECX, rs2);
+        self.emit(&[0x85, 0xC9]); // test ecx, ecx
+        let mut done = Vec::new();
+        if remainder {
+            // x % 0 = x, already in eax
+            done.push(self.jcc8(CC_E));
+        } else {
+            let nonzero = self.jcc8(CC_NE);
+            self.mov_imm(EAX, u32::MAX);
+            done.push(self.jmp8());
+            self.patch8(nonzero);
+        }
+        if signed {
+            self.emit(&[0x83, 0xF9, 0xFF]); // cmp ecx, -1
+            let other = self.jcc8(CC_NE);
+            if remainder {
+                self.emit(&[0x31, 0xC0]); // xor eax, eax
+            } else {
+                self.emit(&[0xF7, 0xD8]); // neg eax, which wraps for i32::MIN
+            }
+            done.push(self.jmp8());
+            self.patch8(other);
+            self.emit(&[0x99, 0xF7, 0xF9]); // cdq; idiv ecx
+        } else {
+            self.emit(&[0x31, 0xD2, 0xF7, 0xF1]); // xor edx, edx; div ecx
+        }
+        if remainder {
+            self.emit(&[0x89, 0xD0]); // mov eax, edx
+        }
+        for jump in done {
+            self.patch8(jump);
+        }
+        self.store_guest(rd, EAX);
+    }
+
+    // Static targets start a block, and so does the next instruction, whose
+    // entry stub comes right after
+    fn branch(&mut self, cc: u8, rs1: u8, rs2: u8, target: usize) {
+        self.load_guest(EAX, rs1);
+        self.emit(&[0x3B, 0x43, 4 * rs2]); // cmp eax, [rbx + rs2]
+        self.jcc(cc, Label::Entry(target));
+    }
+
+    // eax = rs1 + imm. Misaligned accesses go to the interpreter, which
+    // splits them into bytes.
+    fn address(&mut self, ppc: usize, insn: &PreDecodedInstruction, size: u32) {
+        self.load_guest(EAX, insn.rs1);
+        self.emit(&[0x05]); // add eax, imm32
+        self.imm32(insn.imm as u32);
+        if size > 1 {
+            self.emit(&[0xA9]); // test eax, imm32
+            self.imm32(size - 1);
+            self.jcc(CC_NE, Label::Bail(ppc));
+            self.bailing.push(ppc);
+        }
+    }
+
+    // Reads below PRIVATE_SIZE come from the VM's own slab, the rest from
+    // the template's if the VM was forked
+    fn load(&mut self, ppc: usize, insn: &PreDecodedInstruction, size: u32, op: &[u8]) {
+        self.address(ppc, insn, size);
+        self.emit(&[0x25]); // and eax, MEMORY_MASK
+        self.imm32(MEMORY_MASK);
+        self.emit(&[0x4C, 0x89, 0xE2]); // mov rdx, r12
+        self.emit(&[0x3D]); // cmp eax, PRIVATE_SIZE
+        self.imm32(PRIVATE_SIZE);
+        self.emit(&[0x49, 0x0F, 0x43, 0xD5]); // cmovae rdx, r13
+        self.emit(op);
+        self.emit(&[0x0C, 0x02]); // ecx, [rdx + rax]
+        self.store_guest(insn.rd, ECX);
+    }
+
+    fn store(&mut self, ppc: usize, insn: &PreDecodedInstruction, op: &[u8]) {
+        let size = match insn.kind {
+            InsnKind::SB => 1,
+            InsnKind::SH => 2,
+            _ => 4,
+        };
+        self.address(ppc, insn, size);
+        self.emit(&[0x25]); // and eax, RW_MASK
+        self.imm32(RW_MASK);
+        self.load_guest(ECX, insn.rs2);
+        self.emit(op);
+        self.emit(&[0x0C, 0x04]); // [r12 + rax], ecx
+    }
+
+    fn load_guest(&mut self, reg: u8, r: u8) {
+        self.emit(&[0x8B, 0x43 | reg << 3, 4 * r]); // mov reg, [rbx + 4 * r]
+    }
+
+    // x0 is never written, so it reads as zero from the register file
+    fn store_guest(&mut self, r: u8, reg: u8) {
+        if r != 0 {
+            self.emit(&[0x89, 0x43 | reg << 3, 4 * r]); // mov [rbx + 4 * r], reg
+        }
+    }
+
+    fn store_guest_imm(&mut self, r: u8, value: u32) {
+        if r != 0 {
+            self.emit(&[0xC7, 0x43, 4 * r]); // mov dword [rbx + 4 * r], imm32
+            self.imm32(value);
+        }
+    }
+
+    fn mov_imm(&mut self, reg: u8, value: u32) {
+        self.emit(&[0xB8 | reg]); // mov reg, imm32, zero-extended
+        self.imm32(value);
+    }
+
+    fn mov_state_imm(&mut self, offset: u8, value: u32) {
+        self.emit(&[0x49, 0xC7, 0x46, offset]); // mov qword [r14 + offset], imm32
+        self.imm32(value);
+    }
+
+    fn jmp(&mut self, label: Label) {
+        self.emit(&[0xE9]);
+        self.fixup(label);
+    }
+
+    fn jcc(&mut self, cc: u8, label: Label) {
+        self.emit(&[0x0F, 0x80 | cc]);
+        self.fixup(label);
+    }
+
+    fn fixup(&mut self, label: Label) {
+        self.fixups.push((self.code.len(), label));
+        self.imm32(0);
+    }
+
+    // Short forward jumps within one instruction, patched to land at the
+    // current position
+    fn jcc8(&mut self, cc: u8) -> usize {
+        self.emit(&[0x70 | cc, 0]);
+        self.code.len() - 1
+    }
+
+    fn jmp8(&mut self) -> usize {
+        self.emit(&[0xEB, 0]);
+        self.code.len() - 1
+    }
+
+    fn patch8(&mut self, at: usize) {
+        self.code[at] = (self.code.len() - at - 1) as u8;
+    }
+
+    fn emit(&mut self, bytes: &[u8]) {
+        self.code.extend_from_slice(bytes);
+    }
+
+    fn imm32(&mut self, value: u32) {
+        self.code.extend_from_slice(&value.to_le_bytes());
+    }
+}
@@ -1,134 +1,303 @@
 #[cfg(test)]
 mod tests;
-
+mod snapshot;
+mod fork;
+mod threaded;
+mod batch;
+#[cfg(feature = "std")]
+mod pool;
+#[cfg(all(feature = "std", target_os = "linux"))]
+mod cow;
+#[cfg(all(feature = "jit", target_arch = "x86_64", target_os = "linux"))]
+mod jit;
+
+pub use snapshot::{SNAPSHOT_MAGIC, SNAPSHOT_SIZE, SNAPSHOT_VERSION};
+pub use fork::VMTemplate;
+pub use threaded::{Handler, ThreadedCode};
+pub use batch::Batch;
+#[cfg(feature = "std")]
+pub use pool::{BatchRunner, Job, JobResult};
+#[cfg(all(feature = "std", target_os = "linux"))]
+pub use cow::{CowFork, SharedPrivate};
+#[cfg(all(feature = "jit", target_arch = "x86_64", target_os = "linux"))]
+pub use jit::JitCode;
+
+use alloc::vec::Vec;
 use core::marker::PhantomData;
 #[cfg(target_os = "zkvm")]
 use core::arch::asm;
 use crate::instructions::{InsnKind, PreDecodedInstruction};
 use crate::errors::RubicVError;
+use crate::hooks::{Hooks, NoHooks};
 use crate::memory::*;
+use crate::trace::TraceRecorder;
+use crate::watch::{WatchHit, Watchpoints};
+use crate::syscalls::{NoSyscalls, SyscallContext, SyscallHandler, SyscallResult, REG_A0, SYS_EXIT};
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq)]
 pub enum ExecutionResult {
     Success(u32),
     Breakpoint,
     CycleLimitExceeded,
+    // A syscall handler paused the run; the ppc is already past the ecall
+    Yield(u32),
+    // A watched load or store retired; the ppc is already past it
+    Watchpoint(WatchHit),
     Error(RubicVError),
 }
 
-pub trait ZeroEnforcement {
+impl ExecutionResult {
+    // Stops that can be continued with resume()
+    pub fn is_resumable(&self) -> bool {
+        matches!(self, Self::Breakpoint | Self::CycleLimitExceeded | Self::Yield(_) | Self::Watchpoint(_))
+    }
+}
+
+// What guest loads and stores outside their region do, picked at
+// compile time. WrapMemory masks them into range: reads wrap at MEMORY_SIZE
+// and writes at RW_SIZE. StrictMemory stops the run with an error naming
+// the faulting address instead. Both take the Alignment for accesses whose
+// address is not a multiple of their size; WrapMemory defaults to
+// SplitMisaligned, StrictMemory callers name it, e.g.
+// StrictMemory<TrapMisaligned>.
+pub trait MemoryPolicy {
+    type Alignment: Alignment;
+
     #[inline(always)]
-    fn enforce_zero(_registers: &mut [u32]) {
+    fn check_read(_addr: u32, _size: u8) -> Result<(), RubicVError> {
+        Ok(())
     }
+
+    #[inline(always)]
+    fn check_write(_addr: u32, _size: u8) -> Result<(), RubicVError> {
+        Ok(())
+    }
+}
+pub struct WrapMemory<A: Alignment = SplitMisaligned>(PhantomData<A>);
+pub struct StrictMemory<A: Alignment>(PhantomData<A>);
+
+impl<A: Alignment> MemoryPolicy for WrapMemory<A> {
+    type Alignment = A;
 }
-pub struct EnforceZero;
-pub struct NoEnforceZero;
 
-impl ZeroEnforcement for EnforceZero {
+impl<A: Alignment> MemoryPolicy for StrictMemory<A> {
+    type Alignment = A;
+
     #[inline(always)]
-    fn enforce_zero(registers: &mut [u32]) {
-        unsafe { *registers.get_unchecked_mut(0) = 0 };
+    fn check_read(addr: u32, size: u8) -> Result<(), RubicVError> {
+        if addr as u64 + size as u64 > MEMORY_SIZE as u64 {
+            return Err(RubicVError::MemoryReadOutOfBounds(addr));
+        }
+        Ok(())
+    }
+
+    // Code is executed from the predecoded program, so a store into the
+    // code area is always a guest bug
+    #[inline(always)]
+    fn check_write(addr: u32, size: u8) -> Result<(), RubicVError> {
+        let end = addr as u64 + size as u64;
+        if end > MEMORY_SIZE as u64 {
+            Err(RubicVError::MemoryWriteOutOfBounds(addr))
+        } else if end > RW_SIZE as u64 {
+            Err(RubicVError::WriteToReadOnlyMemory(addr))
+        } else if addr < CODE_START + CODE_SIZE {
+            Err(RubicVError::IllegalMemoryAccess(addr))
+        } else {
+            Ok(())
+        }
     }
 }
 
-impl ZeroEnforcement for NoEnforceZero {}
+pub trait Alignment {
+    // Trap rather than split the access into bytes
+    const TRAP: bool;
+}
+// Like RISC-V hardware that supports misaligned accesses: every byte goes to
+// its own address, and each one is masked separately under WrapMemory
+pub struct SplitMisaligned;
+// Loads fail with MemoryMisaligned and stores with MisalignedAccess
+pub struct TrapMisaligned;
+
+impl Alignment for SplitMisaligned {
+    const TRAP: bool = false;
+}
 
-pub enum VMType<'a> {
-    Enforced(VM<'a, EnforceZero>),
-    NotEnforced(VM<'a, NoEnforceZero>),
+impl Alignment for TrapMisaligned {
+    const TRAP: bool = true;
 }
 
 pub trait VMOperations {
     fn step(&mut self) -> Result<(), RubicVError>;
+    fn single_step(&mut self, syscalls: &mut dyn SyscallHandler) -> Option<ExecutionResult>;
     fn set_registers(&mut self, registers: &[u32]);
     fn read_u32(&self, addr: u32) -> u32;
-    fn run(&mut self, max_cycles: Option<u32>) -> ExecutionResult;
+    fn read_u8(&self, addr: u32) -> u8;
+    fn run(&mut self, max_fuel: Option<u64>) -> ExecutionResult;
+    fn run_with_syscalls(&mut self, syscalls: &mut dyn SyscallHandler, max_fuel: Option<u64>) -> ExecutionResult;
+    fn resume(&mut self, syscalls: &mut dyn SyscallHandler) -> ExecutionResult;
+    fn add_fuel(&mut self, fuel: u64);
+    fn set_register(&mut self, r: u8, value: u32);
+    fn write_u32(&mut self, addr: u32, value: u32);
+    fn write_u8(&mut self, addr: u32, value: u8);
     fn get_register(&self, r: u8) -> u32;
+    fn get_registers(&self) -> [u32; 32];
     fn get_ppc(&self) -> usize;
+    fn set_ppc(&mut self, ppc: usize);
     fn get_cycle_count(&self) -> usize;
+    fn get_fuel_used(&self) -> u64;
+    fn snapshot(&self) -> Vec<u8>;
+    fn load_snapshot(&mut self, snapshot: &[u8]) -> Result<(), RubicVError>;
+    // None unless the VM was built with Watchpoints hooks
+    fn watchpoints(&mut self) -> Option<&mut Watchpoints>;
 }
 
-impl<'a, T: ZeroEnforcement> VMOperations for VM<'a, T> {
+impl<'a, H: Hooks, M: MemoryPolicy> VMOperations for VM<'a, H, M> {
     fn step(&mut self) -> Result<(), RubicVError> {
         self.step()
     }
 
+    fn single_step(&mut self, syscalls: &mut dyn SyscallHandler) -> Option<ExecutionResult> {
+        self.single_step(syscalls)
+    }
+
     fn set_registers(&mut self, registers: &[u32]) {
-        self.registers.copy_from_slice(registers);
+        self.registers[1..].copy_from_slice(&registers[1..]);
     }
 
     fn read_u32(&self, addr: u32) -> u32 {
         self.read_u32(addr)
     }
 
-    fn run(&mut self, max_cycles: Option<u32>) -> ExecutionResult {
-        self.run(max_cycles)
+    fn read_u8(&self, addr: u32) -> u8 {
+        self.read_u8(addr)
+    }
+
+    fn run(&mut self, max_fuel: Option<u64>) -> ExecutionResult {
+        self.run(max_fuel)
+    }
+
+    fn run_with_syscalls(&mut self, syscalls: &mut dyn SyscallHandler, max_fuel: Option<u64>) -> ExecutionResult {
+        self.run_with_syscalls(syscalls, max_fuel)
+    }
+
+    fn resume(&mut self, syscalls: &mut dyn SyscallHandler) -> ExecutionResult {
+        self.resume(syscalls)
+    }
+
+    fn add_fuel(&mut self, fuel: u64) {
+        self.add_fuel(fuel)
+    }
+
+    fn set_register(&mut self, r: u8, value: u32) {
+        if r != 0 {
+            self.registers[r as usize] = value;
+        }
+    }
+
+    fn write_u32(&mut self, addr: u32, value: u32) {
+        self.write_u32(addr, value)
+    }
+
+    fn write_u8(&mut self, addr: u32, value: u8) {
+        self.write_u8(addr, value)
     }
+
     fn get_register(&self, r: u8) -> u32 {
         self.registers[r as usize]
     }
+    fn get_registers(&self) -> [u32; 32] {
+        self.registers
+    }
     fn get_ppc(&self) -> usize {
         self.ppc
     }
+    // single_step() reports a ppc outside the program as an error
+    fn set_ppc(&mut self, ppc: usize) {
+        self.ppc = ppc;
+    }
     fn get_cycle_count(&self) -> usize {
         self.cycle_count
     }
-}
-
-impl<'a> VMType<'a> {
-    pub fn as_operations(&mut self) -> &mut dyn VMOperations {
-        match self {
-            Self::Enforced(vm) => vm,
-            Self::NotEnforced(vm) => vm,
-        }
+    fn get_fuel_used(&self) -> u64 {
+        self.fuel_used
     }
-}
-
-impl<'a> VMType<'a> {
-    pub fn new(writes_to_x0: bool,
-               memory_slab: *mut [u8],
-               entry_point: usize,
-               instructions: &'a [PreDecodedInstruction]) -> Self {
-        if writes_to_x0 {
-            Self::Enforced(VM::<EnforceZero>::new(memory_slab, entry_point, instructions))
-        } else {
-            Self::NotEnforced(VM::<NoEnforceZero>::new(memory_slab, entry_point, instructions))
-        }
+    fn snapshot(&self) -> Vec<u8> {
+        self.snapshot()
+    }
+    fn load_snapshot(&mut self, snapshot: &[u8]) -> Result<(), RubicVError> {
+        self.load_snapshot(snapshot)
+    }
+    fn watchpoints(&mut self) -> Option<&mut Watchpoints> {
+        self.hooks.watchpoints()
     }
 }
 
-pub struct VM<'a, T: ZeroEnforcement> {
+pub struct VM<'a, H: Hooks = NoHooks, M: MemoryPolicy = WrapMemory> {
     pub registers: [u32; 32],
-    pub cycle_count: usize,
+    pub cycle_count: usize, // instructions retired
+    fuel_used: u64,
+    fuel_limit: u64,
 
     // writes are prevented w/ wraparound
     pub memory_slab: *mut [u8],
+    // reads at or above PRIVATE_SIZE, shared with the template of a forked VM
+    ro_slab: *const [u8],
 
     ppc: usize, // pre-decoded program counter
     pre_decoded_instructions: &'a [PreDecodedInstruction], // pre-decoded store
-    _phantom: PhantomData<T>,
+    pub hooks: H,
+    _phantom: PhantomData<M>,
 }
 
-impl<'a, T: ZeroEnforcement> VM<'a, T> {
+impl<'a> VM<'a> {
     pub fn new(memory_slab: *mut [u8],
                entry_point: usize,
-               pre_decoded_instructions: &[PreDecodedInstruction],
-    ) -> VM<T> {
-            VM::<T> {
+               pre_decoded_instructions: &'a [PreDecodedInstruction],
+    ) -> VM<'a> {
+        VM::with_hooks(memory_slab, entry_point, pre_decoded_instructions, NoHooks)
+    }
+}
+
+impl<'a, H: Hooks> VM<'a, H> {
+    pub fn with_hooks(memory_slab: *mut [u8],
+                      entry_point: usize,
+                      pre_decoded_instructions: &'a [PreDecodedInstruction],
+                      hooks: H,
+    ) -> VM<'a, H> {
+        VM::with_policy(memory_slab, entry_point, pre_decoded_instructions, hooks)
+    }
+}
+
+impl<'a, H: Hooks, M: MemoryPolicy> VM<'a, H, M> {
+    // For a memory policy other than WrapMemory:
+    //   VM::<NoHooks, StrictMemory<SplitMisaligned>>::with_policy(slab, entry, &insns, NoHooks)
+    pub fn with_policy(memory_slab: *mut [u8],
+                       entry_point: usize,
+                       pre_decoded_instructions: &'a [PreDecodedInstruction],
+                       hooks: H,
+    ) -> VM<'a, H, M> {
+            VM::<H, M> {
                 registers: [0; 32],
                 cycle_count: 0,
+                fuel_used: 0,
+                fuel_limit: u64::MAX,
                 memory_slab,
+                ro_slab: memory_slab,
                 ppc: entry_point,
                 pre_decoded_instructions,
+                hooks,
                 _phantom: PhantomData,
             }
     }
 
+    // The aligned accessors below ignore the low address bits; guest loads
+    // and stores go through read_sized and write_sized instead
     #[inline(always)]
     pub fn read_u32(&self, addr: u32) -> u32 {
         unsafe {
-            let index = (addr & MEMORY_MASK) as usize >> 2; // Divide by 4
-            let ptr = (self.memory_slab as *const u32).add(index);
+            let addr = addr & MEMORY_MASK;
+            let index = addr as usize >> 2; // Divide by 4
+            let ptr = (self.slab_for(addr) as *const u32).add(index);
             *ptr
         }
     }
@@ -136,8 +305,9 @@ impl<'a, T: ZeroEnforcement> VM<'a, T> {
     #[inline(always)]
     pub fn read_u16(&self, addr: u32) -> u16 {
         unsafe {
-            let index = (addr & MEMORY_MASK) as usize >> 1; // Divide by 2
-            let ptr = (self.memory_slab as *const u16).add(index);
+            let addr = addr & MEMORY_MASK;
+            let index = addr as usize >> 1; // Divide by 2
+            let ptr = (self.slab_for(addr) as *const u16).add(index);
             *ptr
         }
     }
@@ -145,8 +315,19 @@ impl<'a, T: ZeroEnforcement> VM<'a, T> {
     #[inline(always)]
     pub fn read_u8(&self, addr: u32) -> u8 {
         unsafe {
-            let index = (addr & MEMORY_MASK) as usize;
-            *(self.memory_slab as *const u8).add(index)
+            let addr = addr & MEMORY_MASK;
+            *self.slab_for(addr).add(addr as usize)
+        }
+    }
+
+    // Both slabs use the full address layout; they are the same slab unless
+    // the VM was forked, in which case memory_slab only holds the private part
+    #[inline(always)]
+    fn slab_for(&self, masked_addr: u32) -> *const u8 {
+        if masked_addr < PRIVATE_SIZE {
+            self.memory_slab as *const u8
+        } else {
+            self.ro_slab as *const u8
         }
     }
 
@@ -186,17 +367,78 @@ impl<'a, T: ZeroEnforcement> VM<'a, T> {
         }
     }
 
+    // What a guest load of `size` bytes reads, misaligned or not
+    #[inline(always)]
+    pub fn read_sized(&self, addr: u32, size: u8) -> u32 {
+        if addr & (size as u32 - 1) != 0 {
+            return (0..size as u32).fold(0, |value, i| value | (self.read_u8(addr.wrapping_add(i)) as u32) << (8 * i));
+        }
+        match size {
+            1 => self.read_u8(addr) as u32,
+            2 => self.read_u16(addr) as u32,
+            _ => self.read_u32(addr),
+        }
+    }
+
+    #[inline(always)]
+    pub fn write_sized(&mut self, addr: u32, size: u8, value: u32) {
+        if addr & (size as u32 - 1) != 0 {
+            for i in 0..size as u32 {
+                self.write_u8(addr.wrapping_add(i), (value >> (8 * i)) as u8);
+            }
+            return;
+        }
+        match size {
+            1 => self.write_u8(addr, value as u8),
+            2 => self.write_u16(addr, value as u16),
+            _ => self.write_u32(addr, value),
+        }
+    }
+
+    #[inline(always)]
+    fn check_load(addr: u32, size: u8) -> Result<(), RubicVError> {
+        M::check_read(addr, size)?;
+        if M::Alignment::TRAP && addr & (size as u32 - 1) != 0 {
+            return Err(RubicVError::MemoryMisaligned(addr));
+        }
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn check_store(addr: u32, size: u8) -> Result<(), RubicVError> {
+        M::check_write(addr, size)?;
+        if M::Alignment::TRAP && addr & (size as u32 - 1) != 0 {
+            return Err(RubicVError::MisalignedAccess(addr));
+        }
+        Ok(())
+    }
+
+    // Executes one instruction. A fused instruction only runs the first of
+    // the pair it stands for, so debuggers and traces see every instruction.
     #[inline(always)]
     pub fn step(&mut self) -> Result<(), RubicVError> {
-        let pre_decoded_insn = unsafe { self.pre_decoded_instructions.get_unchecked(self.ppc) };
+        self.execute::<false>().map(|_| ())
+    }
+
+    // Returns how many instructions retired: 2 for a fused pair under FUSED
+    #[inline(always)]
+    fn execute<const FUSED: bool>(&mut self) -> Result<usize, RubicVError> {
+        let mut pre_decoded_insn = unsafe { self.pre_decoded_instructions.get_unchecked(self.ppc) };
+        let unfused;
+        if !FUSED && pre_decoded_insn.kind.is_fused() {
+            // The sentinel is never fused, so there is a next instruction
+            unfused = pre_decoded_insn.unfused(unsafe { self.pre_decoded_instructions.get_unchecked(self.ppc + 1) });
+            pre_decoded_insn = &unfused;
+        }
         // println!("{:?}", pre_decoded_insn);
-        T::enforce_zero(&mut self.registers);
+        self.hooks.on_step(self.ppc, pre_decoded_insn, &self.registers);
 
         let rs1 = unsafe { *self.registers.get_unchecked(pre_decoded_insn.rs1 as usize) };
         let rs2 = unsafe { *self.registers.get_unchecked(pre_decoded_insn.rs2 as usize) };
         let rd = pre_decoded_insn.rd;
         let imm = pre_decoded_insn.imm;
         let mut next_ppc = self.ppc + 1;
+        let mut retired = 1;
         // println!(
         //     "Executing {:?} at ppc={}: rd={}, rs1={}, rs2={}, imm={}",
         //     pre_decoded_insn.kind, self.ppc, rd, pre_decoded_insn.rs1, pre_decoded_insn.rs2, imm
@@ -224,12 +466,12 @@ impl<'a, T: ZeroEnforcement> VM<'a, T> {
             InsnKind::SLTIU => unsafe { *self.registers.get_unchecked_mut(rd as usize) = if rs1 < (imm as u32) { 1 } else { 0 } },
 
             // Branch instructions (no register writes)
-            InsnKind::BEQ => if rs1 == rs2 { next_ppc = imm as usize },
-            InsnKind::BNE => if rs1 != rs2 { next_ppc = imm as usize },
-            InsnKind::BLT => if (rs1 as i32) < (rs2 as i32) { next_ppc = imm as usize },
-            InsnKind::BGE => if (rs1 as i32) >= (rs2 as i32) { next_ppc = imm as usize },
-            InsnKind::BLTU => if rs1 < rs2 { next_ppc = imm as usize },
-            InsnKind::BGEU => if rs1 >= rs2 { next_ppc = imm as usize },
+            InsnKind::BEQ => self.branch(rs1 == rs2, imm, &mut next_ppc),
+            InsnKind::BNE => self.branch(rs1 != rs2, imm, &mut next_ppc),
+            InsnKind::BLT => self.branch((rs1 as i32) < (rs2 as i32), imm, &mut next_ppc),
+            InsnKind::BGE => self.branch((rs1 as i32) >= (rs2 as i32), imm, &mut next_ppc),
+            InsnKind::BLTU => self.branch(rs1 < rs2, imm, &mut next_ppc),
+            InsnKind::BGEU => self.branch(rs1 >= rs2, imm, &mut next_ppc),
 
             // Jump instructions
             InsnKind::JAL => {
@@ -239,50 +481,85 @@ impl<'a, T: ZeroEnforcement> VM<'a, T> {
                     }
                 };
                 next_ppc = imm as usize;
+                self.hooks.on_branch(self.ppc, next_ppc, true);
             },
             InsnKind::JALR => {
-                unsafe { *self.registers.get_unchecked_mut(rd as usize) = ((self.ppc + 1) * 4) as u32 };
+                unsafe {
+                    if rd != 0 {
+                        *self.registers.get_unchecked_mut(rd as usize) = ((self.ppc + 1) * 4) as u32;
+                    }
+                };
                 let target_addr = rs1.wrapping_add(imm as u32) & !1;
                 // println!("ppc:{:?} rd: {:?} rs1:{:?} imm:{:?} target_addr:{:?}",self.ppc,rd, rs1,imm,target_addr);
-                next_ppc = (target_addr / 4) as usize;
+                // Targets past the end land on the program's trailing
+                // sentinel, which traps, without a branch on the hot path
+                next_ppc = ((target_addr / 4) as usize).min(self.pre_decoded_instructions.len() - 1);
+                self.hooks.on_branch(self.ppc, next_ppc, true);
             },
 
             // Load instructions
             InsnKind::LB => {
                 let addr = rs1.wrapping_add(imm as u32);
-                unsafe { *self.registers.get_unchecked_mut(rd as usize) = sign_extend(self.read_u8(addr) as u32, 8) };
+                Self::check_load(addr, 1)?;
+                let value = self.read_u8(addr) as u32;
+                self.hooks.on_mem_read(addr, 1, value);
+                unsafe { *self.registers.get_unchecked_mut(rd as usize) = sign_extend(value, 8) };
             },
             InsnKind::LH => {
                 let addr = rs1.wrapping_add(imm as u32);
-                unsafe { *self.registers.get_unchecked_mut(rd as usize) = sign_extend(self.read_u16(addr) as u32, 16) };
+                Self::check_load(addr, 2)?;
+                let value = self.read_sized(addr, 2);
+                self.hooks.on_mem_read(addr, 2, value);
+                unsafe { *self.registers.get_unchecked_mut(rd as usize) = sign_extend(value, 16) };
             },
             InsnKind::LW => {
                 let addr = rs1.wrapping_add(imm as u32);
+                Self::check_load(addr, 4)?;
                 // println!("LW from address: {:#x}, value: {:#x}", addr, self.read_u32(addr));
-                unsafe { *self.registers.get_unchecked_mut(rd as usize) = self.read_u32(addr) };
+                let value = self.read_sized(addr, 4);
+                self.hooks.on_mem_read(addr, 4, value);
+                unsafe { *self.registers.get_unchecked_mut(rd as usize) = value };
             },
             InsnKind::LBU => {
                 let addr = rs1.wrapping_add(imm as u32);
-                unsafe { *self.registers.get_unchecked_mut(rd as usize) = self.read_u8(addr) as u32 };
+                Self::check_load(addr, 1)?;
+                let value = self.read_u8(addr) as u32;
+                self.hooks.on_mem_read(addr, 1, value);
+                unsafe { *self.registers.get_unchecked_mut(rd as usize) = value };
             },
             InsnKind::LHU => {
                 let addr = rs1.wrapping_add(imm as u32);
-                unsafe { *self.registers.get_unchecked_mut(rd as usize) = self.read_u16(addr) as u32 };
+                Self::check_load(addr, 2)?;
+                let value = self.read_sized(addr, 2);
+                self.hooks.on_mem_read(addr, 2, value);
+                unsafe { *self.registers.get_unchecked_mut(rd as usize) = value };
+            },
+            InsnKind::PROBE => {
+                let addr = rs1.wrapping_add(imm as u32);
+                let size = pre_decoded_insn.rs2;
+                Self::check_load(addr, size)?;
+                self.hooks.on_mem_read(addr, size, self.read_sized(addr, size));
             },
 
             // Store instructions (no register writes)
             InsnKind::SB => {
                 let addr = rs1.wrapping_add(imm as u32);
+                Self::check_store(addr, 1)?;
+                self.hooks.on_mem_write(addr, 1, self.read_u8(addr & RW_MASK) as u32, rs2 & 0xFF);
                 self.write_u8(addr, rs2 as u8);
             },
             InsnKind::SH => {
                 let addr = rs1.wrapping_add(imm as u32);
-                self.write_u16(addr, rs2 as u16);
+                Self::check_store(addr, 2)?;
+                self.hooks.on_mem_write(addr, 2, self.read_sized(addr & RW_MASK, 2), rs2 & 0xFFFF);
+                self.write_sized(addr, 2, rs2);
             },
             InsnKind::SW => {
                 let addr = rs1.wrapping_add(imm as u32);
+                Self::check_store(addr, 4)?;
                 // println!("Store to address: {:#x}, value: {:#x}", addr, rs2);
-                self.write_u32(addr, rs2);
+                self.hooks.on_mem_write(addr, 4, self.read_sized(addr & RW_MASK, 4), rs2);
+                self.write_sized(addr, 4, rs2);
             },
 
             // AUIPC instruction
@@ -301,13 +578,13 @@ impl<'a, T: ZeroEnforcement> VM<'a, T> {
             },
 
             // System instructions
-            InsnKind::ECALL => return Err(RubicVError::SystemCall(self.registers[11])),
+            InsnKind::ECALL => return Err(RubicVError::SystemCall(self.registers[17])),
             InsnKind::EBREAK => return Err(RubicVError::Breakpoint),
 
             // M extension
             InsnKind::MUL => unsafe { *self.registers.get_unchecked_mut(rd as usize) = rs1.wrapping_mul(rs2) },
-            InsnKind::MULH => unsafe { *self.registers.get_unchecked_mut(rd as usize) = ((rs1 as i64).wrapping_mul(rs2 as i64) >> 32) as u32 },
-            InsnKind::MULHSU => unsafe { *self.registers.get_unchecked_mut(rd as usize) = ((rs1 as i64).wrapping_mul(rs2 as u64 as i64) >> 32) as u32 },
+            InsnKind::MULH => unsafe { *self.registers.get_unchecked_mut(rd as usize) = ((rs1 as i32 as i64).wrapping_mul(rs2 as i32 as i64) >> 32) as u32 },
+            InsnKind::MULHSU => unsafe { *self.registers.get_unchecked_mut(rd as usize) = ((rs1 as i32 as i64).wrapping_mul(rs2 as u64 as i64) >> 32) as u32 },
             InsnKind::MULHU => unsafe { *self.registers.get_unchecked_mut(rd as usize) = ((rs1 as u64).wrapping_mul(rs2 as u64) >> 32) as u32 },
             InsnKind::DIV => unsafe { *self.registers.get_unchecked_mut(rd as usize) =
                 if rs2 == 0 {
@@ -334,73 +611,317 @@ impl<'a, T: ZeroEnforcement> VM<'a, T> {
                 if rs2 == 0 { rs1 } else { rs1.wrapping_rem(rs2) }
             },
 
+            // Superinstructions, only reached under FUSED. None of them can
+            // trap, so a run never stops halfway through a pair.
+            InsnKind::LUI_ADDI => {
+                unsafe { *self.registers.get_unchecked_mut(rd as usize) = imm as u32 };
+                next_ppc += 1;
+                retired = 2;
+            },
+            InsnKind::AUIPC_JALR => {
+                let jalr = unsafe { self.pre_decoded_instructions.get_unchecked(self.ppc + 1) };
+                let base = ((self.ppc as u32) * 4).wrapping_add(imm as u32);
+                unsafe {
+                    *self.registers.get_unchecked_mut(rd as usize) = base;
+                    if jalr.rd != 0 {
+                        *self.registers.get_unchecked_mut(jalr.rd as usize) = ((self.ppc + 2) * 4) as u32;
+                    }
+                };
+                let target_addr = base.wrapping_add(jalr.imm as u32) & !1;
+                next_ppc = ((target_addr / 4) as usize).min(self.pre_decoded_instructions.len() - 1);
+                self.hooks.on_branch(self.ppc + 1, next_ppc, true);
+                retired = 2;
+            },
+            InsnKind::ADDI_BNE => {
+                let value = rs1.wrapping_add(imm as u32);
+                unsafe { *self.registers.get_unchecked_mut(rd as usize) = value };
+                // Read after the write, the BNE may compare rd with itself
+                let other = unsafe { *self.registers.get_unchecked(pre_decoded_insn.rs2 as usize) };
+                let target = unsafe { self.pre_decoded_instructions.get_unchecked(self.ppc + 1) }.imm;
+                next_ppc += 1;
+                if value != other {
+                    next_ppc = target as usize;
+                }
+                self.hooks.on_branch(self.ppc + 1, next_ppc, value != other);
+                retired = 2;
+            },
+            InsnKind::SLLI_ADD => {
+                let shifted = rs1 << (imm as u32 & 0x1f);
+                unsafe {
+                    *self.registers.get_unchecked_mut((imm >> 8) as usize) = shifted;
+                    let other = *self.registers.get_unchecked(pre_decoded_insn.rs2 as usize);
+                    *self.registers.get_unchecked_mut(rd as usize) = other.wrapping_add(shifted);
+                };
+                next_ppc += 1;
+                retired = 2;
+            },
+
             // Catch-all for unhandled instructions
             _ => return Err(RubicVError::IllegalInstruction),
         }
+        // Only loads and stores hit, and they never jump, so the trap
+        // handler can move on to the next instruction like for an ebreak
+        if let Some(hit) = self.hooks.take_watch_hit() {
+            return Err(RubicVError::Watchpoint(hit));
+        }
         // println!("Next ppc set to {}", next_ppc);
         self.ppc = next_ppc;
 
-        Ok(())
+        Ok(retired)
     }
 
-    #[cfg(target_os = "zkvm")]
-    pub fn run(&mut self, max_cycles: Option<u32>) -> ExecutionResult {
+    #[inline(always)]
+    fn branch(&mut self, taken: bool, target: i32, next_ppc: &mut usize) {
+        if taken {
+            *next_ppc = target as usize;
+        }
+        self.hooks.on_branch(self.ppc, *next_ppc, taken);
+    }
+
+    // Services an ecall that step() reported. SYS_EXIT is handled here, every
+    // other number goes to the host handler. Unless the run halts, the ppc moves past the ecall.
+    #[inline(always)]
+    pub fn dispatch_syscall<S: SyscallHandler + ?Sized>(&mut self, syscall: u32, syscalls: &mut S) -> Result<SyscallResult, RubicVError> {
+        self.hooks.on_syscall(syscall, &self.registers);
+        let result = if syscall == SYS_EXIT {
+            SyscallResult::Halt(self.registers[REG_A0])
+        } else {
+            let mut ctx = SyscallContext::new(&mut self.registers, self.memory_slab, self.ro_slab);
+            syscalls.handle(syscall, &mut ctx)?
+        };
+        if !matches!(result, SyscallResult::Halt(_)) {
+            self.ppc += 1;
+        }
+        Ok(result)
+    }
+
+    pub fn run(&mut self, max_fuel: Option<u64>) -> ExecutionResult {
+        self.run_with_syscalls(&mut NoSyscalls, max_fuel)
+    }
+
+    // Sets up the stack pointer and the fuel budget for a fresh run
+    fn start(&mut self, max_fuel: Option<u64>) {
         unsafe {
             *self.registers.get_unchecked_mut(2) = STACK_START;
+        }
+        self.fuel_limit = match max_fuel {
+            Some(max) => self.fuel_used.saturating_add(max),
+            None => u64::MAX,
+        };
+    }
 
-            let max = max_cycles.unwrap_or(u32::MAX);
-            let mut cycle_count: u32 = 0;
+    // Use resume() to carry on after a Yield, Breakpoint or CycleLimitExceeded
+    pub fn run_with_syscalls<S: SyscallHandler + ?Sized>(&mut self, syscalls: &mut S, max_fuel: Option<u64>) -> ExecutionResult {
+        self.start(max_fuel);
+        self.resume(syscalls)
+    }
 
-            loop {
-                asm!(
-                "addi {0}, {0}, 1",
-                inout(reg) cycle_count,
-                );
+    // Same as run_with_syscalls, but records every retired instruction
+    pub fn run_traced<S: SyscallHandler + ?Sized>(&mut self, syscalls: &mut S, max_fuel: Option<u64>, trace: &mut TraceRecorder) -> ExecutionResult {
+        self.start(max_fuel);
+        self.resume_traced(syscalls, trace)
+    }
 
-                if cycle_count >= max {
-                    self.cycle_count = cycle_count as usize;
-                    return ExecutionResult::CycleLimitExceeded;
-                }
+    // Traced runs are metered one instruction at a time, which stops at the
+    // same place as block metering
+    pub fn resume_traced<S: SyscallHandler + ?Sized>(&mut self, syscalls: &mut S, trace: &mut TraceRecorder) -> ExecutionResult {
+        loop {
+            let ppc = self.ppc;
+            let insn = unsafe { self.pre_decoded_instructions.get_unchecked(ppc) };
+            // What step() runs, for the trace
+            let insn = &insn.unfused(self.pre_decoded_instructions.get(ppc + 1).unwrap_or(insn));
+            if insn.cost as u64 > self.fuel_limit - self.fuel_used {
+                return ExecutionResult::CycleLimitExceeded;
+            }
+            self.fuel_used += insn.cost as u64;
+            let rs1 = self.registers[insn.rs1 as usize];
+            let rs2 = self.registers[insn.rs2 as usize];
 
-                match self.step() {
-                    Ok(()) => continue,
-                    Err(RubicVError::SystemCall(val)) => {
-                        self.cycle_count = cycle_count as usize;
-                        return ExecutionResult::Success(val)
-                    },
-                    Err(RubicVError::Breakpoint) => {
-                        self.cycle_count = cycle_count as usize;
-                        return ExecutionResult::Breakpoint
-                    },
-                    Err(e) => {
-                        self.cycle_count = cycle_count as usize;
-                        return ExecutionResult::Error(e)
-                    },
-                }
+            match self.step() {
+                Ok(()) => {
+                    self.cycle_count += 1;
+                    trace.record(self, ppc, insn, rs1, rs2);
+                },
+                Err(trap) => {
+                    if matches!(trap, RubicVError::SystemCall(_) | RubicVError::Breakpoint | RubicVError::Watchpoint(_)) {
+                        trace.record(self, ppc, insn, rs1, rs2);
+                    }
+                    if let Some(result) = self.handle_trap(trap, syscalls) {
+                        return result;
+                    }
+                },
             }
         }
     }
-    #[cfg(not(target_os = "zkvm"))]
-    pub fn run(&mut self, max_cycles: Option<u32>) -> ExecutionResult {
-        unsafe {
-            *self.registers.get_unchecked_mut(2) = STACK_START;
+
+    // Executes one instruction, metered on its own, and services the ecall or
+    // ebreak it raises. Returns None if execution can carry on, otherwise the
+    // result that stopped it. Meant for debuggers; runs should use resume().
+    pub fn single_step<S: SyscallHandler + ?Sized>(&mut self, syscalls: &mut S) -> Option<ExecutionResult> {
+        let Some(insn) = self.pre_decoded_instructions.get(self.ppc) else {
+            return Some(ExecutionResult::Error(RubicVError::InvalidInstruction));
+        };
+        if insn.cost as u64 > self.fuel_limit - self.fuel_used {
+            return Some(ExecutionResult::CycleLimitExceeded);
         }
-        let max = max_cycles.unwrap_or(u32::MAX);
+        self.fuel_used += insn.cost as u64;
+        match self.step() {
+            Ok(()) => {
+                self.cycle_count += 1;
+                None
+            },
+            Err(trap) => self.handle_trap(trap, syscalls),
+        }
+    }
+
+    pub fn add_fuel(&mut self, fuel: u64) {
+        self.fuel_limit = self.fuel_limit.saturating_add(fuel);
+    }
 
+    pub fn fuel_used(&self) -> u64 {
+        self.fuel_used
+    }
+
+    pub fn fuel_remaining(&self) -> u64 {
+        self.fuel_limit - self.fuel_used
+    }
+
+    // Turns a trap reported by step() into the result that ends this run,
+    // or None if execution carries on. Resumable stops leave the ppc on the
+    // next instruction to execute.
+    #[inline(always)]
+    fn handle_trap<S: SyscallHandler + ?Sized>(&mut self, trap: RubicVError, syscalls: &mut S) -> Option<ExecutionResult> {
+        match trap {
+            RubicVError::SystemCall(syscall) => {
+                self.cycle_count += 1;
+                match self.dispatch_syscall(syscall, syscalls) {
+                    Ok(SyscallResult::Continue) => None,
+                    Ok(SyscallResult::Yield) => Some(ExecutionResult::Yield(syscall)),
+                    Ok(SyscallResult::Halt(code)) => Some(ExecutionResult::Success(code)),
+                    Err(e) => Some(ExecutionResult::Error(e)),
+                }
+            },
+            RubicVError::Breakpoint => {
+                self.cycle_count += 1;
+                self.ppc += 1;
+                Some(ExecutionResult::Breakpoint)
+            },
+            RubicVError::Watchpoint(hit) => {
+                self.cycle_count += 1;
+                self.ppc += 1;
+                Some(ExecutionResult::Watchpoint(hit))
+            },
+            e => Some(ExecutionResult::Error(e)),
+        }
+    }
+
+    // Continues from the current ppc without touching registers.
+    //
+    // Fuel is charged per basic block: if the rest of the block fits in the
+    // budget it is paid up front and runs without further checks, otherwise
+    // instructions are charged one at a time so the run stops exactly where
+    // per-instruction metering would. An instruction only runs if its whole
+    // cost fits, so a CycleLimitExceeded leaves the ppc on it.
+    pub fn resume<S: SyscallHandler + ?Sized>(&mut self, syscalls: &mut S) -> ExecutionResult {
+        self.resume_with(syscalls, |vm, steps| {
+            // Fused pairs never straddle a block, so only whole blocks run them
+            let fused = H::FUSED && steps > 1;
+            let mut executed = 0;
+            while executed < steps {
+                executed += if fused { vm.execute::<true>()? } else { vm.execute::<false>()? };
+            }
+            Ok(())
+        })
+    }
+
+    // The metering loop behind resume(). `run` executes `steps` instructions
+    // from the ppc: the rest of its block, or just the one when fuel is short.
+    #[cfg(not(target_os = "zkvm"))]
+    #[inline(always)]
+    fn resume_with<S, R>(&mut self, syscalls: &mut S, run: R) -> ExecutionResult
+    where
+        S: SyscallHandler + ?Sized,
+        R: Fn(&mut Self, usize) -> Result<(), RubicVError>,
+    {
         loop {
-            self.cycle_count += 1;
-            if self.cycle_count >= max as usize {
+            let insn = unsafe { self.pre_decoded_instructions.get_unchecked(self.ppc) };
+            let remaining = self.fuel_limit - self.fuel_used;
+            let (charge, steps) = if insn.block_cost as u64 <= remaining {
+                (insn.block_cost as u64, insn.block_len as usize)
+            } else if insn.cost as u64 <= remaining {
+                (insn.cost as u64, 1)
+            } else {
                 return ExecutionResult::CycleLimitExceeded;
-            }
-            match self.step() {
-                Ok(()) => continue,
-                Err(RubicVError::SystemCall(val)) => return ExecutionResult::Success(val),
-                Err(RubicVError::Breakpoint) => return ExecutionResult::Breakpoint,
-                Err(e) => return ExecutionResult::Error(e),
+            };
+            self.fuel_used += charge;
+            self.cycle_count += steps;
+
+            if let Err(trap) = run(self, steps) {
+                // Hand back what was charged for the unexecuted rest of the
+                // block, which starts at the trapping instruction
+                if steps > 1 {
+                    let insn = unsafe { self.pre_decoded_instructions.get_unchecked(self.ppc) };
+                    self.fuel_used -= (insn.block_cost - insn.cost) as u64;
+                    self.cycle_count -= insn.block_len as usize;
+                } else {
+                    self.cycle_count -= 1;
+                }
+                if let Some(result) = self.handle_trap(trap, syscalls) {
+                    return result;
+                }
             }
         }
     }
 
+    // On the zkVM the cycle count is kept in a register and bumped with asm,
+    // and only written back to the VM around traps and when the run stops
+    #[cfg(target_os = "zkvm")]
+    #[inline(always)]
+    fn resume_with<S, R>(&mut self, syscalls: &mut S, run: R) -> ExecutionResult
+    where
+        S: SyscallHandler + ?Sized,
+        R: Fn(&mut Self, usize) -> Result<(), RubicVError>,
+    {
+        let mut cycle_count = self.cycle_count;
+
+        loop {
+            let insn = unsafe { self.pre_decoded_instructions.get_unchecked(self.ppc) };
+            let remaining = self.fuel_limit - self.fuel_used;
+            let (charge, steps) = if insn.block_cost as u64 <= remaining {
+                (insn.block_cost as u64, insn.block_len as usize)
+            } else if insn.cost as u64 <= remaining {
+                (insn.cost as u64, 1)
+            } else {
+                self.cycle_count = cycle_count;
+                return ExecutionResult::CycleLimitExceeded;
+            };
+            self.fuel_used += charge;
+
+            match run(self, steps) {
+                Ok(()) => unsafe {
+                    asm!(
+                    "add {0}, {0}, {1}",
+                    inout(reg) cycle_count,
+                    in(reg) steps,
+                    );
+                },
+                Err(trap) => {
+                    // Only what ran before the trapping instruction counts
+                    if steps > 1 {
+                        let insn = unsafe { self.pre_decoded_instructions.get_unchecked(self.ppc) };
+                        self.fuel_used -= (insn.block_cost - insn.cost) as u64;
+                        self.cycle_count = cycle_count + steps - insn.block_len as usize;
+                    } else {
+                        self.cycle_count = cycle_count;
+                    }
+                    if let Some(result) = self.handle_trap(trap, syscalls) {
+                        return result;
+                    }
+                    cycle_count = self.cycle_count;
+                },
+            }
+        }
+    }
 }
 #[inline(always)]
 fn sign_extend(value: u32, bits: u32) -> u32 {
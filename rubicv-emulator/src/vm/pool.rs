@@ -0,0 +1,98 @@
+use alloc::boxed::Box;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use crate::instructions::PredecodedProgram;
+use crate::memory::*;
+use crate::syscalls::{NoSyscalls, SyscallHandler};
+use super::{ExecutionResult, VM};
+
+// One run of the program: u32 args written from ARGS_START, and its budget
+#[derive(Clone, Debug, Default)]
+pub struct Job {
+    pub args: Vec<u32>,
+    pub max_fuel: Option<u64>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct JobResult {
+    pub result: ExecutionResult,
+    pub cycles: usize,
+    pub fuel_used: u64,
+    pub scratch: Vec<u8>, // SCRATCH_SIZE bytes from SCRATCH_START
+}
+
+// Runs many jobs of one program on a pool of worker threads. All jobs read
+// the image, a full memory layout holding the RO region, in place; each
+// worker owns one private region (RW and args) that it resets from the image
+// for every job, so a job costs PRIVATE_SIZE bytes of copying and no
+// allocation.
+pub struct BatchRunner<'p> {
+    program: &'p PredecodedProgram,
+    image: &'p [u8],
+    threads: usize,
+}
+
+impl<'p> BatchRunner<'p> {
+    pub fn new(program: &'p PredecodedProgram, image: &'p [u8]) -> Self {
+        assert!(image.len() >= MEMORY_SIZE as usize, "image must cover MEMORY_SIZE bytes");
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        Self { program, image, threads }
+    }
+
+    // Defaults to the available parallelism
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    pub fn run(&self, jobs: &[Job]) -> Vec<JobResult> {
+        self.run_with_syscalls(jobs, || NoSyscalls)
+    }
+
+    // Every job gets its own handler from `host`. Results are in job order;
+    // a job that yields stops there.
+    pub fn run_with_syscalls<S, F>(&self, jobs: &[Job], host: F) -> Vec<JobResult>
+    where
+        S: SyscallHandler,
+        F: Fn() -> S + Sync,
+    {
+        let next = AtomicUsize::new(0);
+        let results = Mutex::new((0..jobs.len()).map(|_| None).collect::<Vec<Option<JobResult>>>());
+        thread::scope(|scope| {
+            for _ in 0..self.threads.min(jobs.len()) {
+                scope.spawn(|| {
+                    let mut private: Box<[u8]> = vec![0u8; PRIVATE_SIZE as usize].into_boxed_slice();
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(job) = jobs.get(i) else { break };
+                        let result = self.run_job(job, &mut private, &mut host());
+                        results.lock().unwrap()[i] = Some(result);
+                    }
+                });
+            }
+        });
+        results.into_inner().unwrap().into_iter().map(Option::unwrap).collect()
+    }
+
+    fn run_job<S: SyscallHandler>(&self, job: &Job, private: &mut [u8], syscalls: &mut S) -> JobResult {
+        assert!(job.args.len() * 4 <= ARGS_SIZE as usize, "job args do not fit in the args region");
+        private.copy_from_slice(&self.image[..PRIVATE_SIZE as usize]);
+        for (i, arg) in job.args.iter().enumerate() {
+            let at = ARGS_START as usize + 4 * i;
+            private[at..at + 4].copy_from_slice(&arg.to_le_bytes());
+        }
+        let mut vm = VM::new(private as *mut [u8], self.program.entrypoint, &self.program.instructions);
+        vm.ro_slab = self.image as *const [u8];
+        let result = vm.run_with_syscalls(syscalls, job.max_fuel);
+        let scratch = SCRATCH_START as usize;
+        JobResult {
+            result,
+            cycles: vm.cycle_count,
+            fuel_used: vm.fuel_used,
+            scratch: private[scratch..scratch + SCRATCH_SIZE as usize].to_vec(),
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use alloc::vec::Vec;
+use crate::errors::RubicVError;
+use crate::hooks::{Hooks, NoHooks};
+use crate::instructions::{PreDecodedInstruction, PredecodedProgram};
+use crate::memory::RW_SIZE;
+use super::{MemoryPolicy, VM};
+
+// Snapshot layout, all integers little-endian:
+//
+//   magic        [u8; 4]  "RVSN"
+//   version      u16
+//   reserved     u16
+//   program hash u64      FNV-1a over the predecoded instructions
+//   ppc          u32
+//   cycle_count  u64
+//   fuel_used    u64
+//   fuel_limit   u64
+//   registers    [u32; 32]
+//   rw region    [u8; RW_SIZE]
+//   checksum     u32      CRC-32 of everything above
+pub const SNAPSHOT_MAGIC: [u8; 4] = *b"RVSN";
+pub const SNAPSHOT_VERSION: u16 = 1;
+const HEADER_SIZE: usize = 4 + 2 + 2 + 8 + 4 + 8 + 8 + 8 + 32 * 4;
+pub const SNAPSHOT_SIZE: usize = HEADER_SIZE + RW_SIZE as usize + 4;
+
+impl PredecodedProgram {
+    // Identifies the code a snapshot was taken against. Costs are included,
+    // since fuel accounting depends on them.
+    pub fn hash(&self) -> u64 {
+        hash_instructions(&self.instructions)
+    }
+}
+
+fn hash_instructions(instructions: &[PreDecodedInstruction]) -> u64 {
+    let mut hash = Fnv1a::new();
+    for insn in instructions {
+        hash.write(&[insn.kind as u8, insn.rd, insn.rs1, insn.rs2]);
+        hash.write(&insn.imm.to_le_bytes());
+        hash.write(&insn.cost.to_le_bytes());
+    }
+    hash.finish()
+}
+
+impl<'a> VM<'a> {
+    // Rebuilds a VM from `snapshot` against the program it was taken from.
+    // The RW region of `memory_slab` is overwritten, RO contents are the caller's.
+    pub fn restore(snapshot: &[u8],
+                   memory_slab: *mut [u8],
+                   program: &'a PredecodedProgram,
+    ) -> Result<VM<'a>, RubicVError> {
+        VM::restore_with_hooks(snapshot, memory_slab, program, NoHooks)
+    }
+}
+
+impl<'a, H: Hooks> VM<'a, H> {
+    pub fn restore_with_hooks(snapshot: &[u8],
+                              memory_slab: *mut [u8],
+                              program: &'a PredecodedProgram,
+                              hooks: H,
+    ) -> Result<VM<'a, H>, RubicVError> {
+        let mut vm = VM::with_hooks(memory_slab, 0, &program.instructions, hooks);
+        vm.load_snapshot(snapshot)?;
+        Ok(vm)
+    }
+}
+
+impl<'a, H: Hooks, M: MemoryPolicy> VM<'a, H, M> {
+    pub fn snapshot(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(SNAPSHOT_SIZE);
+        out.extend_from_slice(&SNAPSHOT_MAGIC);
+        out.extend_from_slice(&SNAPSHOT_VERSION.to_le_bytes());
+        out.extend_from_slice(&0u16.to_le_bytes());
+        out.extend_from_slice(&hash_instructions(self.pre_decoded_instructions).to_le_bytes());
+        out.extend_from_slice(&(self.ppc as u32).to_le_bytes());
+        out.extend_from_slice(&(self.cycle_count as u64).to_le_bytes());
+        out.extend_from_slice(&self.fuel_used.to_le_bytes());
+        out.extend_from_slice(&self.fuel_limit.to_le_bytes());
+        for r in self.registers {
+            out.extend_from_slice(&r.to_le_bytes());
+        }
+        let rw = unsafe { core::slice::from_raw_parts(self.memory_slab as *const u8, RW_SIZE as usize) };
+        out.extend_from_slice(rw);
+        let checksum = crc32(&out);
+        out.extend_from_slice(&checksum.to_le_bytes());
+        out
+    }
+
+    // Rewinds this VM in place to `snapshot`, taken against the same program.
+    // On error the VM is left untouched.
+    pub fn load_snapshot(&mut self, snapshot: &[u8]) -> Result<(), RubicVError> {
+        if snapshot.len() != SNAPSHOT_SIZE {
+            return Err(RubicVError::InvalidSnapshot);
+        }
+        let (body, checksum) = snapshot.split_at(SNAPSHOT_SIZE - 4);
+        if crc32(body).to_le_bytes() != checksum {
+            return Err(RubicVError::InvalidSnapshot);
+        }
+
+        let mut reader = Reader { bytes: body, pos: 0 };
+        if reader.take(4) != SNAPSHOT_MAGIC || reader.u16() != SNAPSHOT_VERSION {
+            return Err(RubicVError::InvalidSnapshot);
+        }
+        reader.u16();
+        if reader.u64() != hash_instructions(self.pre_decoded_instructions) {
+            return Err(RubicVError::SnapshotProgramMismatch);
+        }
+        let ppc = reader.u32() as usize;
+        if ppc >= self.pre_decoded_instructions.len() {
+            return Err(RubicVError::InvalidSnapshot);
+        }
+        let (cycle_count, fuel_used, fuel_limit) = (reader.u64() as usize, reader.u64(), reader.u64());
+        let registers: [u32; 32] = core::array::from_fn(|_| reader.u32());
+        // The VM never writes x0, so it is zero in any snapshot it took
+        if registers[0] != 0 {
+            return Err(RubicVError::InvalidSnapshot);
+        }
+        // Nor does it spend past its limit, which resume() relies on
+        if fuel_used > fuel_limit {
+            return Err(RubicVError::InvalidSnapshot);
+        }
+        self.ppc = ppc;
+        self.cycle_count = cycle_count;
+        self.fuel_used = fuel_used;
+        self.fuel_limit = fuel_limit;
+        self.registers = registers;
+        let rw = reader.take(RW_SIZE as usize);
+        unsafe { core::ptr::copy_nonoverlapping(rw.as_ptr(), self.memory_slab as *mut u8, RW_SIZE as usize) };
+        Ok(())
+    }
+}
+
+// Reads from a buffer whose length has already been checked
+struct Reader<'b> {
+    bytes: &'b [u8],
+    pos: usize,
+}
+
+impl<'b> Reader<'b> {
+    fn take(&mut self, n: usize) -> &'b [u8] {
+        let bytes = &self.bytes[self.pos..self.pos + n];
+        self.pos += n;
+        bytes
+    }
+
+    fn u16(&mut self) -> u16 {
+        u16::from_le_bytes(self.take(2).try_into().unwrap())
+    }
+
+    fn u32(&mut self) -> u32 {
+        u32::from_le_bytes(self.take(4).try_into().unwrap())
+    }
+
+    fn u64(&mut self) -> u64 {
+        u64::from_le_bytes(self.take(8).try_into().unwrap())
+    }
+}
+
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0 ^= *b as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+// CRC-32 (IEEE 802.3), bitwise
+pub(super) fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for b in bytes {
+        crc ^= *b as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
@@ -0,0 +1,94 @@
+use crate::aot::{translate, VmState};
+use crate::syscalls::*;
+use super::*;
+
+// Output of aot::translate for the programs below, compiled in
+#[allow(dead_code)]
+mod sum {
+    include!("test_data/aot_sum.rs");
+}
+#[allow(dead_code)]
+mod looping {
+    include!("test_data/aot_loop.rs");
+}
+#[allow(dead_code)]
+mod compute {
+    include!("test_data/aot_compute.rs");
+}
+#[allow(dead_code)]
+mod counter {
+    include!("test_data/aot_counter.rs");
+}
+
+type Run<S> = fn(&mut VmState, &mut S, Option<u64>) -> ExecutionResult;
+
+// Runs `program` through the interpreter and its translation on fresh
+// memory set up by `prepare`, checking both stop the same way. Returns the
+// result and the fuel the interpreter used.
+fn assert_aot_matches<S: SyscallHandler>(program: &PredecodedProgram, run: Run<S>, prepare: impl Fn(&mut [u8]), host: impl Fn() -> S, max_fuel: Option<u64>) -> (ExecutionResult, u64) {
+    let (mut interpreted_memory, mut translated_memory) = (setup_memory(), setup_memory());
+    prepare(&mut interpreted_memory.memory_slab);
+    prepare(&mut translated_memory.memory_slab);
+    let mut interpreted = setup_program_vm(&mut interpreted_memory, program);
+    let mut state = VmState::new(translated_memory.memory_slab.as_mut() as *mut [u8], program.entrypoint);
+    let result = interpreted.run_with_syscalls(&mut host(), max_fuel);
+    assert_eq!(run(&mut state, &mut host(), max_fuel), result);
+    assert_eq!(state.registers, interpreted.registers);
+    assert_eq!((state.ppc, state.cycle_count, state.fuel_used), (interpreted.get_ppc(), interpreted.cycle_count, interpreted.fuel_used()));
+    assert_eq!(translated_memory.memory_slab[..RO_START as usize], interpreted_memory.memory_slab[..RO_START as usize]);
+    (result, interpreted.fuel_used())
+}
+
+#[test]
+fn test_aot_translations_are_up_to_date() {
+    // Regenerate with `utils aot` if the translator changes
+    assert_eq!(translate(&sum_program()), include_str!("test_data/aot_sum.rs"));
+    assert_eq!(translate(&loop_program()), include_str!("test_data/aot_loop.rs"));
+    assert_eq!(translate(&compute_program()), include_str!("test_data/aot_compute.rs"));
+    assert_eq!(translate(&counter_program()), include_str!("test_data/aot_counter.rs"));
+}
+
+#[test]
+fn test_aot_sum_program() {
+    let program = sum_program();
+    for n in [0, 1, 7, 100] {
+        assert_eq!(assert_aot_matches(&program, sum::run_with_syscalls, write_args(&[n]), || NoSyscalls, None).0, ExecutionResult::Success(0));
+    }
+    assert_matches_for_any_fuel(|fuel| assert_aot_matches(&program, sum::run_with_syscalls, write_args(&[7]), || NoSyscalls, fuel));
+
+    let mut memory = setup_memory();
+    write_args(&[7])(&mut memory.memory_slab);
+    let mut state = VmState::new(memory.memory_slab.as_mut() as *mut [u8], sum::ENTRYPOINT);
+    assert_eq!(sum::run(&mut state, Some(100)), ExecutionResult::Success(0));
+    assert_eq!(u32::from_le_bytes(memory.memory_slab[SCRATCH_START as usize..SCRATCH_START as usize + 4].try_into().unwrap()), 21);
+}
+
+#[test]
+fn test_aot_matches_interpreter_on_test_programs() {
+    assert_eq!(assert_matches_for_any_fuel(|fuel| assert_aot_matches(&loop_program(), looping::run_with_syscalls, |_| {}, || NoSyscalls, fuel)), ExecutionResult::Success(0x12345678 + 180));
+    assert_eq!(assert_matches_for_any_fuel(|fuel| assert_aot_matches(&compute_program(), compute::run_with_syscalls, |_| {}, || NoSyscalls, fuel)), ExecutionResult::Success(u32::MAX));
+    // Host calls that write guest memory, mid-block refunds on every ecall
+    assert_eq!(assert_matches_for_any_fuel(|fuel| assert_aot_matches(&counter_program(), counter::run_with_syscalls, |_| {}, Counter::default, fuel)), ExecutionResult::Success(150));
+    // Without a handler the first host call is an error
+    assert_eq!(assert_aot_matches(&counter_program(), counter::run_with_syscalls, |_| {}, || NoSyscalls, None).0, ExecutionResult::Error(RubicVError::SystemCall(1)));
+}
+
+#[test]
+fn test_aot_resumes_after_running_out_of_fuel() {
+    let mut memory = setup_memory();
+    let mut state = VmState::new(memory.memory_slab.as_mut() as *mut [u8], looping::ENTRYPOINT);
+    let mut result = looping::run(&mut state, Some(10));
+    let mut runs = 1;
+    while result == ExecutionResult::CycleLimitExceeded {
+        result = looping::run(&mut state, Some(10));
+        runs += 1;
+    }
+    assert_eq!(result, ExecutionResult::Success(0x12345678 + 180));
+    assert!(runs > 1);
+
+    let mut memory = setup_memory();
+    let program = loop_program();
+    let mut vm = setup_program_vm(&mut memory, &program);
+    vm.run(None);
+    assert_eq!((state.cycle_count, state.fuel_used), (vm.cycle_count, vm.fuel_used()));
+}
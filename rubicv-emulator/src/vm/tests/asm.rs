@@ -0,0 +1,131 @@
+use crate::asm::*;
+use crate::instructions::{FastDecodeTable, RV32IM_ISA};
+use crate::metering::CostTable;
+use super::*;
+
+const SUM: &str = "
+    # sum of 0..n, n read from the args
+    .globl _start
+sum:
+    lui a0, 0x10            // ARGS_START
+    lw a0, 0(a0)
+    li a1, 0
+    li a2, 0
+    beq a0, zero, done
+loop:
+    add a1, a1, a2
+    addi a2, a2, 1
+    bne a0, a2, loop
+done:
+    lui a0, 0x2             // SCRATCH_START
+    sw a1, 0(a0)
+    ret
+
+_start:
+    call sum
+    li a0, 0
+    li a7, 0
+    ecall
+";
+
+#[test]
+fn test_encode_matches_reference_words() {
+    // As encoded by the GNU assembler
+    assert_eq!(encode(InsnKind::ADD, 3, 1, 2, 0), 0x0020_81b3);
+    assert_eq!(encode(InsnKind::SUB, 3, 1, 2, 0), 0x4020_81b3);
+    assert_eq!(encode(InsnKind::SLT, 3, 1, 2, 0), 0x0020_a1b3);
+    assert_eq!(encode(InsnKind::MUL, A0, A0, A1, 0), 0x02b5_0533);
+    assert_eq!(encode(InsnKind::ADDI, 2, 1, 0, 42), 0x02a0_8113);
+    assert_eq!(encode(InsnKind::SRAI, 5, 6, 0, 7), 0x4073_5293);
+    assert_eq!(encode(InsnKind::LUI, A0, 0, 0, 0x10000), 0x0001_0537);
+    assert_eq!(encode(InsnKind::LW, A0, A0, 0, 0), 0x0005_2503);
+    assert_eq!(encode(InsnKind::SW, 0, A0, A1, 0), 0x00b5_2023);
+    assert_eq!(encode(InsnKind::BNE, 0, A0, 12, -8), 0xfec5_1ce3);
+    assert_eq!(encode(InsnKind::JAL, 1, 0, 0, 2048), 0x0010_00ef);
+    assert_eq!(encode(InsnKind::ECALL, 0, 0, 0, 0), ECALL);
+    assert_eq!(encode(InsnKind::EBREAK, 0, 0, 0, 0), EBREAK);
+}
+
+#[test]
+fn test_encode_round_trips_every_kind() {
+    let decoder = FastDecodeTable::default();
+    let ppc = 10;
+    for spec in RV32IM_ISA.iter() {
+        let imm = match spec.kind {
+            InsnKind::BEQ | InsnKind::BNE | InsnKind::BLT | InsnKind::BGE
+            | InsnKind::BLTU | InsnKind::BGEU => -8,
+            InsnKind::JAL => 2048,
+            InsnKind::LUI | InsnKind::AUIPC => 0x12345000,
+            InsnKind::SLLI | InsnKind::SRLI | InsnKind::SRAI => 7,
+            InsnKind::SB | InsnKind::SH | InsnKind::SW => -12,
+            InsnKind::ADDI | InsnKind::XORI | InsnKind::ORI | InsnKind::ANDI | InsnKind::SLTI
+            | InsnKind::SLTIU | InsnKind::JALR | InsnKind::LB | InsnKind::LH | InsnKind::LW
+            | InsnKind::LBU | InsnKind::LHU => -5,
+            _ => 0,
+        };
+        assert!(imm_fits(spec.kind, imm), "{:?}", spec.kind);
+        let word = encode(spec.kind, 5, 6, 7, imm);
+        let decoded = decoder.predecode(word, ppc, &CostTable::default());
+        assert_eq!(decoded.kind, spec.kind, "{:08x}", word);
+
+        // branch and JAL targets come back as absolute ppc indices
+        let imm = match spec.kind {
+            kind if kind.is_branch() || kind == InsnKind::JAL => (decoded.imm - ppc as i32) * 4,
+            _ => decoded.imm,
+        };
+        assert_eq!(encode(decoded.kind, decoded.rd, decoded.rs1, decoded.rs2, imm), word, "{:?}", spec.kind);
+    }
+}
+
+#[test]
+fn test_assemble_sum_program() {
+    let program = PredecodedProgram::new(&assemble(SUM).unwrap()).unwrap();
+    assert_eq!(program.entrypoint, 11);
+
+    let mut memory = setup_memory();
+    let ro_mem_start = RO_START as usize;
+    memory.memory_slab[ro_mem_start..ro_mem_start + 4].copy_from_slice(&10u32.to_le_bytes());
+    let mut vm = setup_program_vm(&mut memory, &program);
+
+    assert_eq!(vm.run(Some(1000)), ExecutionResult::Success(0));
+    assert_eq!(vm.read_u32(SCRATCH_START), 45);
+}
+
+#[test]
+fn test_assemble_pseudo_instructions() {
+    let bytes = assemble("
+        li a0, 0x12345678
+        li a1, -1
+        li a2, 0x1000
+        mv a3, a2
+        nop
+        j end
+        .word 0xdeadbeef, end
+    end: ecall
+    ").unwrap();
+    let words: Vec<u32> = bytes.chunks(4).map(|c| u32::from_le_bytes(c.try_into().unwrap())).collect();
+    assert_eq!(words, [
+        0,
+        encode(InsnKind::LUI, 10, 0, 0, 0x12345000),
+        encode(InsnKind::ADDI, 10, 10, 0, 0x678),
+        encode(InsnKind::ADDI, 11, 0, 0, -1),
+        encode(InsnKind::LUI, 12, 0, 0, 0x1000),
+        encode(InsnKind::ADDI, 13, 12, 0, 0),
+        encode(InsnKind::ADDI, 0, 0, 0, 0),
+        encode(InsnKind::JAL, 0, 0, 0, 12),
+        0xdeadbeef,
+        0x24,
+        ECALL,
+    ]);
+}
+
+#[test]
+fn test_assembler_errors() {
+    let error = |line, reason| Err(RubicVError::AssemblerError { line, reason });
+    assert_eq!(assemble("nop\n  j nowhere"), error(2, "undefined label"));
+    assert_eq!(assemble("addi a0, a9, 1"), error(1, "unknown register"));
+    assert_eq!(assemble("addi a0, a0, 2048"), error(1, "immediate out of range"));
+    assert_eq!(assemble("a: nop\na: nop"), error(2, "duplicate label"));
+    assert_eq!(assemble("frob a0"), error(1, "unknown instruction"));
+    assert_eq!(assemble("add a0, a1"), error(1, "wrong operands"));
+}
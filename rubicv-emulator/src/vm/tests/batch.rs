@@ -0,0 +1,104 @@
+use crate::syscalls::*;
+use super::*;
+
+// One fork of `template` per arg, with the arg written to its private region
+fn fork_lanes<'a>(template: &VMTemplate<'a>, privates: &mut [Box<[u8]>], args: &[u32]) -> Vec<VM<'a>> {
+    privates.iter_mut().zip(args).map(|(private, &arg)| {
+        let vm = template.fork(private.as_mut());
+        let at = ARGS_START as usize;
+        private[at..at + 4].copy_from_slice(&arg.to_le_bytes());
+        vm
+    }).collect()
+}
+
+fn private_slabs(n: usize) -> Vec<Box<[u8]>> {
+    (0..n).map(|_| vec![0u8; PRIVATE_SIZE as usize].into_boxed_slice()).collect()
+}
+
+// Runs `program` once per arg on its own and once as a batch, checking every
+// lane stops like its own run, and carries on while any lane yields. Returns
+// the lanes' results and the most fuel any lane's own run used.
+fn assert_batch_matches<S: SyscallHandler>(program: &PredecodedProgram, args: &[u32], host: impl Fn() -> S, max_fuel: Option<u64>) -> (Vec<ExecutionResult>, u64) {
+    let mut memory = setup_memory();
+    let template = setup_program_vm(&mut memory, program).freeze();
+    let (mut scalar_privates, mut batch_privates) = (private_slabs(args.len()), private_slabs(args.len()));
+    let mut scalar = fork_lanes(&template, &mut scalar_privates, args);
+    let mut batch = Batch::new(fork_lanes(&template, &mut batch_privates, args));
+
+    let mut scalar_host = host();
+    let mut expected: Vec<ExecutionResult> = scalar.iter_mut().map(|vm| vm.run_with_syscalls(&mut scalar_host, max_fuel)).collect();
+    let mut results = batch.run(&mut host(), max_fuel);
+    loop {
+        assert_eq!(results, expected);
+        for (lane, vm) in batch.lanes().iter().zip(&scalar) {
+            assert_eq!(lane.registers, vm.registers);
+            assert_eq!((lane.get_ppc(), lane.cycle_count, lane.fuel_used()), (vm.get_ppc(), vm.cycle_count, vm.fuel_used()));
+        }
+        if !results.iter().any(|result| matches!(result, ExecutionResult::Yield(_))) {
+            break;
+        }
+        expected = scalar.iter_mut().map(|vm| vm.resume(&mut scalar_host)).collect();
+        results = batch.resume(&mut host());
+    }
+    let fuel_used = scalar.iter().map(|vm| vm.fuel_used()).max().unwrap();
+    drop((batch, scalar));
+    assert_eq!(batch_privates, scalar_privates);
+    if max_fuel.is_none() {
+        assert!(!results.contains(&ExecutionResult::CycleLimitExceeded));
+    }
+    (results, fuel_used)
+}
+
+// a0 = args[0]; odd args hit an ebreak first; exit(a0)
+fn odd_breaks_program() -> PredecodedProgram {
+    setup_program(&[
+        encode(InsnKind::LUI, T0, 0, 0, ARGS_START as i32),
+        encode(InsnKind::LW, A0, T0, 0, 0),
+        encode(InsnKind::ANDI, T1, A0, 0, 1),
+        encode(InsnKind::BEQ, 0, T1, 0, 8),
+        EBREAK,
+        li(A7, SYS_EXIT as i32),
+        ECALL,
+    ])
+}
+
+#[test]
+fn test_batch_runs_forked_lanes() {
+    let program = sum_program();
+    let mut memory = setup_memory();
+    let template = setup_program_vm(&mut memory, &program).freeze();
+    let args: Vec<u32> = (0..64).collect();
+    let mut privates = private_slabs(args.len());
+    let mut batch = Batch::new(fork_lanes(&template, &mut privates, &args));
+    assert!(batch.run(&mut NoSyscalls, None).iter().all(|result| *result == ExecutionResult::Success(0)));
+    for (lane, &n) in batch.lanes().iter().zip(&args) {
+        // sum of 0..n, left in scratch
+        assert_eq!(lane.read_u32(SCRATCH_START), n * n.saturating_sub(1) / 2);
+    }
+}
+
+#[test]
+fn test_batch_lanes_that_diverge_match_their_own_runs() {
+    let program = sum_program();
+    assert_matches_for_any_fuel(|fuel| assert_batch_matches(&program, &[0, 1, 3, 7, 20], || NoSyscalls, fuel));
+
+    let (results, _) = assert_batch_matches(&odd_breaks_program(), &[2, 3, 4, 5], || NoSyscalls, None);
+    assert_eq!(results, [ExecutionResult::Success(2), ExecutionResult::Breakpoint, ExecutionResult::Success(4), ExecutionResult::Breakpoint]);
+    assert_matches_for_any_fuel(|fuel| assert_batch_matches(&odd_breaks_program(), &[2, 3], || NoSyscalls, fuel));
+}
+
+#[test]
+fn test_batch_runs_test_programs() {
+    assert_matches_for_any_fuel(|fuel| assert_batch_matches(&compute_program(), &[0, 1], || NoSyscalls, fuel));
+    assert_matches_for_any_fuel(|fuel| assert_batch_matches(&loop_program(), &[0, 1], || NoSyscalls, fuel));
+    assert_matches_for_any_fuel(|fuel| assert_batch_matches(&fusable_program(), &[0, 1], || NoSyscalls, fuel));
+    // A store wrapped into the code area, and reads of the args
+    assert_eq!(assert_batch_matches(&stray_store_program(), &[7, 8], || NoSyscalls, None).0, [ExecutionResult::Success(7), ExecutionResult::Success(8)]);
+}
+
+#[test]
+fn test_batch_yields_and_resumes_every_lane() {
+    let (results, _) = assert_batch_matches(&warm_up_program(), &[1, 2, 3], || Marker, None);
+    assert_eq!(results, [ExecutionResult::Success(8), ExecutionResult::Success(9), ExecutionResult::Success(10)]);
+    assert_matches_for_any_fuel(|fuel| assert_batch_matches(&warm_up_program(), &[1, 2], || Marker, fuel));
+}
@@ -0,0 +1,103 @@
+use crate::instructions::BasicBlock;
+use crate::metering::CostTable;
+use crate::syscalls::*;
+use super::*;
+
+const S1: u8 = 9;
+
+// Reference metering: check and charge every instruction on its own
+fn run_per_step(vm: &mut VM, max_fuel: u64) -> ExecutionResult {
+    vm.registers[2] = STACK_START;
+    vm.fuel_limit = vm.fuel_used + max_fuel;
+    loop {
+        let cost = vm.pre_decoded_instructions[vm.ppc].cost as u64;
+        if cost > vm.fuel_limit - vm.fuel_used {
+            return ExecutionResult::CycleLimitExceeded;
+        }
+        vm.fuel_used += cost;
+        match vm.step() {
+            Ok(()) => vm.cycle_count += 1,
+            Err(trap) => {
+                if let Some(result) = vm.handle_trap(trap, &mut NoSyscalls) {
+                    return result;
+                }
+            },
+        }
+    }
+}
+
+fn assert_matches_per_step(program: &PredecodedProgram, args: &[u32]) {
+    let run = |per_step: bool, max_fuel: u64| {
+        let mut memory = setup_memory();
+        for (i, arg) in args.iter().enumerate() {
+            let at = RO_START as usize + i * 4;
+            memory.memory_slab[at..at + 4].copy_from_slice(&arg.to_le_bytes());
+        }
+        let mut vm = setup_program_vm(&mut memory, program);
+        let result = if per_step { run_per_step(&mut vm, max_fuel) } else { vm.run(Some(max_fuel)) };
+        (result, vm.ppc, vm.fuel_used(), vm.cycle_count, vm.registers)
+    };
+
+    let (result, _, total_fuel, _, _) = run(true, u64::MAX);
+    assert!(matches!(result, ExecutionResult::Success(_)));
+
+    for max_fuel in 0..=total_fuel + 1 {
+        assert_eq!(run(false, max_fuel), run(true, max_fuel), "budget {}", max_fuel);
+    }
+}
+
+#[test]
+fn test_split_blocks() {
+    let program = setup_program(&[
+        li(S0, 0),                            // 0  block 0
+        li(S1, 3),                            // 1
+        encode(InsnKind::ADDI, S0, S0, 0, 1), // 2  block 1 (branch target)
+        encode(InsnKind::BNE, 0, S0, S1, -4), // 3
+        li(A7, SYS_EXIT as i32),              // 4  block 2
+        ECALL,                                // 5
+    ]);
+
+    assert_eq!(program.blocks, [
+        BasicBlock { start: 0, len: 2, cost: 2 },
+        BasicBlock { start: 2, len: 2, cost: 2 },
+        BasicBlock { start: 4, len: 2, cost: 2 },
+    ]);
+    // Entering mid-block charges only the rest of it
+    assert_eq!(program.instructions[1].block_cost, 1);
+    assert_eq!(program.instructions[1].block_len, 1);
+    assert_eq!(program.instructions[5].block_len, 1);
+}
+
+#[test]
+fn test_block_metering_matches_per_step() {
+    let elf_bytes = include_bytes!("test_data/output.bin");
+    let costs = CostTable::default().with_memory_access(3).with_syscall(10);
+    let program = PredecodedProgram::with_cost_table(elf_bytes, &costs).unwrap();
+    assert_matches_per_step(&program, &[9]);
+
+    let program = PredecodedProgram::new(elf_bytes).unwrap();
+    assert_matches_per_step(&program, &[4]);
+}
+
+#[test]
+fn test_block_metering_stops_mid_block() {
+    let program = setup_program(&[
+        li(A0, 1),
+        li(A0, 2),
+        li(A0, 3),
+        li(A7, SYS_EXIT as i32),
+        ECALL,
+    ]);
+    let mut memory = setup_memory();
+    let mut vm = setup_program_vm(&mut memory, &program);
+
+    // The block costs 5, only 3 instructions fit
+    assert_eq!(vm.run(Some(3)), ExecutionResult::CycleLimitExceeded);
+    assert_eq!(vm.ppc, 3);
+    assert_eq!(vm.cycle_count, 3);
+    assert_eq!(vm.registers[A0 as usize], 3);
+
+    vm.add_fuel(2);
+    assert_eq!(vm.resume(&mut NoSyscalls), ExecutionResult::Success(3));
+    assert_eq!(vm.fuel_used(), 5);
+}
@@ -1,23 +1,12 @@
 use crate::instructions::{PredecodedProgram};
+use crate::metering::CostTable;
+use crate::syscalls::SYS_EXIT;
 use super::*;
 
-// R-type helper
-fn encode_r_type(rs1: u32, rs2: u32, rd: u32, func3: u32, func7: u32) -> u32 {
-    let opcode = 0x33;  // R-type opcode
-    (func7 << 25) | (rs2 << 20) | (rs1 << 15) | (func3 << 12) | (rd << 7) | opcode
-}
-
-// I-type helper
-fn encode_i_type(rs1: u32, rd: u32, func3: u32, imm: i32) -> u32 {
-    let opcode = 0x13;  // I-type opcode
-    let imm = (imm as u32) & 0xFFF; // 12-bit immediate
-    (imm << 20) | (rs1 << 15) | (func3 << 12) | (rd << 7) | opcode
-}
-
-fn setup_compute_vm<'a>(pre_decoded_program: &'a PredecodedProgram, registers: &'a[u32; 32]) -> VM<'a, EnforceZero> {
+fn setup_compute_vm<'a>(pre_decoded_program: &'a PredecodedProgram, registers: &'a[u32; 32]) -> VM<'a> {
     let mut memory = setup_memory();
 
-    let mut vm = VM::<EnforceZero>::new(
+    let mut vm = VM::new(
         memory.memory_slab.as_mut() as *mut [u8],
         pre_decoded_program.entrypoint,
         &pre_decoded_program.instructions
@@ -27,14 +16,6 @@ fn setup_compute_vm<'a>(pre_decoded_program: &'a PredecodedProgram, registers: &
 
 }
 
-fn setup_elf_bytes(code_bytes: &[u8]) -> Vec<u8> {
-    let entry_point = [0u8;4];
-    let mut elf_bytes = vec![];
-    elf_bytes.extend_from_slice(&entry_point);
-    elf_bytes.extend_from_slice(&code_bytes);
-    elf_bytes
-}
-
 #[test]
 fn test_add() {
     // ADD x3, x1, x2
@@ -99,3 +80,76 @@ fn test_signed_operations() {
 
     assert_eq!(vm.registers[3], 1);  // -1 < 0, so result is 1
 }
+
+#[test]
+fn test_mulh() {
+    // MULH x3, x1, x2: both operands signed
+    let instruction = encode_r_type(1, 2, 3, 0x1, 0x01);
+    let elf_bytes =  setup_elf_bytes(&instruction.to_le_bytes());
+    let predecoded_program = PredecodedProgram::new(&elf_bytes).unwrap();
+
+    for (rs1, rs2, high) in [
+        (0xFFFFFFFE, 3, 0xFFFFFFFF),           // -2 * 3 = -6
+        (0x80000000, 0x80000000, 0x40000000),  // -2^31 * -2^31 = 2^62
+        (0x7FFFFFFF, 0xFFFFFFFF, 0xFFFFFFFF),  // (2^31 - 1) * -1
+        (0x10000, 0x10000, 1),
+    ] {
+        let mut registers = [0u32; 32];
+        registers[1] = rs1;
+        registers[2] = rs2;
+        let mut vm = setup_compute_vm(&predecoded_program, &registers);
+        vm.step().unwrap();
+        assert_eq!(vm.registers[3], high, "mulh {:#x}, {:#x}", rs1, rs2);
+    }
+}
+
+#[test]
+fn test_mulhsu() {
+    // MULHSU x3, x1, x2: rs1 signed, rs2 unsigned
+    let instruction = encode_r_type(1, 2, 3, 0x2, 0x01);
+    let elf_bytes =  setup_elf_bytes(&instruction.to_le_bytes());
+    let predecoded_program = PredecodedProgram::new(&elf_bytes).unwrap();
+
+    for (rs1, rs2, high) in [
+        (0xFFFFFFFF, 0xFFFFFFFF, 0xFFFFFFFF),  // -1 * (2^32 - 1)
+        (0xFFFFFFFE, 3, 0xFFFFFFFF),           // -2 * 3 = -6
+        (0x80000000, 0x80000000, 0xC0000000),  // -2^31 * 2^31 = -2^62
+        (0x7FFFFFFF, 0xFFFFFFFF, 0x7FFFFFFE),
+    ] {
+        let mut registers = [0u32; 32];
+        registers[1] = rs1;
+        registers[2] = rs2;
+        let mut vm = setup_compute_vm(&predecoded_program, &registers);
+        vm.step().unwrap();
+        assert_eq!(vm.registers[3], high, "mulhsu {:#x}, {:#x}", rs1, rs2);
+    }
+}
+
+#[test]
+fn test_writes_to_x0_are_discarded() {
+    let program = setup_program(&[
+        li(A0, 5),
+        encode(InsnKind::MUL, 0, A0, A0, 0),
+        encode(InsnKind::LUI, 0, 0, 0, 0x1000),
+        encode(InsnKind::DIVU, 0, A0, A0, 0),
+        // loads the nonzero word at address 0
+        encode(InsnKind::LW, 0, 0, 0, 0),
+        encode(InsnKind::JALR, 0, 0, 0, 6 * 4),
+        encode(InsnKind::ADD, A0, A0, 0, 0),
+        li(A7, SYS_EXIT as i32),
+        ECALL,
+    ]);
+    // Pure computations become nops that cost what the original did
+    let mul = &program.instructions[1];
+    assert_eq!((mul.kind, mul.rd, mul.rs1, mul.rs2), (InsnKind::ADDI, 0, 0, 0));
+    assert_eq!(mul.cost, CostTable::default().cost(InsnKind::MUL));
+    // Loads still read their address, as a probe of the load's size
+    let probe = &program.instructions[4];
+    assert_eq!((probe.kind, probe.rd, probe.rs2), (InsnKind::PROBE, 0, 4));
+    assert_eq!(probe.cost, CostTable::default().cost(InsnKind::LW));
+
+    let mut memory = setup_memory();
+    let mut vm = setup_program_vm(&mut memory, &program);
+    assert_eq!(vm.run(None), ExecutionResult::Success(5));
+    assert_eq!(vm.registers[0], 0);
+}
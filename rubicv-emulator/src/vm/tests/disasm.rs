@@ -0,0 +1,41 @@
+use alloc::format;
+use crate::disasm::*;
+use super::*;
+
+#[test]
+fn test_disassemble_words() {
+    assert_eq!(disassemble_word(0x00c585b3, 0x20), "add a1, a1, a2");
+    assert_eq!(disassemble_word(0xff010113, 0), "addi sp, sp, -16");
+    assert_eq!(disassemble_word(0x4035d513, 0), "srai a0, a1, 3");
+    assert_eq!(disassemble_word(0xfeb12e23, 0), "sw a1, -4(sp)");
+    assert_eq!(disassemble_word(0x00052503, 0), "lw a0, 0(a0)");
+    assert_eq!(disassemble_word(0x00010537, 0), "lui a0, 0x10");
+    assert_eq!(disassemble_word(0x02b54533, 0), "div a0, a0, a1");
+    // targets are relative to the word's own address
+    assert_eq!(disassemble_word(0xfec51ae3, 0x2c), "bne a0, a2, 0x20 <ppc 8>");
+    assert_eq!(disassemble_word(0x008000ef, 0x10), "jal ra, 0x18 <ppc 6>");
+    assert_eq!(disassemble_word(0x00000073, 0), "ecall");
+    assert_eq!(disassemble_word(0x00100073, 0), "ebreak");
+    assert_eq!(disassemble_word(0xc0001073, 0), "invalid");
+}
+
+#[test]
+fn test_numeric_register_names() {
+    let program = setup_program(&[encode(InsnKind::ADD, 10, 11, 12, 0)]);
+    let insn = &program.instructions[0];
+    assert_eq!(format!("{}", Disasm::new(insn)), "add a0, a1, a2");
+    assert_eq!(format!("{}", Disasm::new(insn).numeric()), "add x10, x11, x12");
+}
+
+#[test]
+fn test_listing_marks_entry_and_invalid_words() {
+    let program = PredecodedProgram::new(include_bytes!("test_data/output.bin")).unwrap();
+    let listing = format!("{}", Listing::new(&program));
+    let lines: Vec<&str> = listing.lines().collect();
+
+    assert_eq!(lines[11], "0000002c:  fec51ae3  bne a0, a2, 0x20 <ppc 8>");
+    assert_eq!(lines[17], "00000044 <entry>:");
+    assert_eq!(lines[18], "00000044:  02c080e7  jalr ra, 44(ra)");
+    let invalid = lines.iter().find(|l| l.ends_with("# INVALID"));
+    assert_eq!(invalid, Some(&"00000068:  c0001073  .word 0xc0001073  # INVALID"));
+}
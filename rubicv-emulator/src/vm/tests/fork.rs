@@ -0,0 +1,76 @@
+use super::*;
+
+#[test]
+fn test_fork_children_share_ro_and_own_rw() {
+    let program = warm_up_program();
+    let mut memory = setup_memory();
+    let ro = RO_SLAB_START as usize;
+    memory.memory_slab[ro..ro + 4].copy_from_slice(&1000u32.to_le_bytes());
+    let mut vm = setup_program_vm(&mut memory, &program);
+
+    assert_eq!(vm.run_with_syscalls(&mut Marker, Some(100)), ExecutionResult::Yield(MARKER));
+    let template = vm.freeze();
+
+    let mut results = vec![];
+    for arg in [1u32, 2, 3] {
+        let mut private = vec![0u8; PRIVATE_SIZE as usize].into_boxed_slice();
+        let mut child = template.fork(private.as_mut());
+        let args = ARGS_START as usize;
+        private[args..args + 4].copy_from_slice(&arg.to_le_bytes());
+
+        // children start without fuel
+        assert_eq!(child.resume(&mut Marker), ExecutionResult::CycleLimitExceeded);
+        child.add_fuel(100);
+        results.push(child.resume(&mut Marker));
+        assert_eq!(child.read_u32(SCRATCH_START), 1007 + arg);
+    }
+    assert_eq!(results, [ExecutionResult::Success(1008), ExecutionResult::Success(1009), ExecutionResult::Success(1010)]);
+
+    // the children's writes never reached the template's slab
+    assert_eq!(vm.read_u32(SCRATCH_START), 7);
+    vm.add_fuel(100);
+    assert_eq!(vm.resume(&mut Marker), ExecutionResult::Success(1007));
+}
+
+#[cfg(all(feature = "std", target_os = "linux"))]
+#[test]
+fn test_copy_on_write_forks_match_copied_forks() {
+    let program = warm_up_program();
+    let mut memory = setup_memory();
+    let ro = RO_SLAB_START as usize;
+    memory.memory_slab[ro..ro + 4].copy_from_slice(&1000u32.to_le_bytes());
+    let mut vm = setup_program_vm(&mut memory, &program);
+    assert_eq!(vm.run_with_syscalls(&mut Marker, Some(100)), ExecutionResult::Yield(MARKER));
+    let template = vm.freeze();
+    let shared = template.share().expect("memfd");
+
+    // Two live children, each writing scratch[0], do not see each other
+    let args = ARGS_START as usize;
+    let mut children: Vec<_> = [1u32, 2].iter().map(|arg| {
+        let mut child = shared.fork().expect("mmap");
+        child.private_mut()[args..args + 4].copy_from_slice(&arg.to_le_bytes());
+        child.add_fuel(100);
+        child
+    }).collect();
+    for (child, arg) in children.iter_mut().zip([1u32, 2]) {
+        let mut private = vec![0u8; PRIVATE_SIZE as usize].into_boxed_slice();
+        let mut copied = template.fork(private.as_mut());
+        private[args..args + 4].copy_from_slice(&arg.to_le_bytes());
+        copied.add_fuel(100);
+        assert_eq!(child.resume(&mut Marker), copied.resume(&mut Marker));
+        assert_eq!((child.registers, child.cycle_count, child.fuel_used()), (copied.registers, copied.cycle_count, copied.fuel_used()));
+        assert_eq!(child.private_mut(), private.as_ref());
+    }
+    assert_eq!(children[0].read_u32(SCRATCH_START), 1008);
+    assert_eq!(children[1].read_u32(SCRATCH_START), 1009);
+    drop(children);
+
+    // Nor does the template or a later child
+    assert_eq!(vm.read_u32(SCRATCH_START), 7);
+    let mut child = shared.fork().expect("mmap");
+    assert_eq!(child.read_u32(SCRATCH_START), 7);
+    // Forks outlive the memory file's handle
+    drop(shared);
+    child.add_fuel(100);
+    assert_eq!(child.resume(&mut Marker), ExecutionResult::Success(1007));
+}
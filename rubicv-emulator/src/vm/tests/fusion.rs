@@ -0,0 +1,71 @@
+use crate::hooks::Hooks;
+use crate::syscalls::*;
+use super::*;
+
+// Runs fused pairs as two instructions
+struct Precise;
+impl Hooks for Precise {}
+
+fn run_fused<'a>(memory: &mut TestMemory, program: &'a PredecodedProgram, max_fuel: Option<u64>) -> (ExecutionResult, VM<'a>) {
+    let mut vm = VM::new(memory.memory_slab.as_mut() as *mut [u8], program.entrypoint, &program.instructions);
+    let result = vm.run(max_fuel);
+    (result, vm)
+}
+
+fn run_precise<'a>(memory: &mut TestMemory, program: &'a PredecodedProgram, max_fuel: Option<u64>) -> (ExecutionResult, VM<'a, Precise>) {
+    let mut vm = VM::<Precise>::with_hooks(memory.memory_slab.as_mut() as *mut [u8], program.entrypoint, &program.instructions, Precise);
+    let result = vm.run(max_fuel);
+    (result, vm)
+}
+
+#[test]
+fn test_idioms_are_fused_in_place() {
+    let program = fusable_program();
+    let kinds: Vec<InsnKind> = program.instructions.iter().map(|insn| insn.kind).collect();
+    assert_eq!(kinds[..10], [
+        InsnKind::LUI_ADDI, InsnKind::ADDI, InsnKind::ADDI, InsnKind::ADDI,
+        InsnKind::SLLI_ADD, InsnKind::ADD, InsnKind::ADDI_BNE, InsnKind::BNE,
+        InsnKind::AUIPC_JALR, InsnKind::JALR,
+    ]);
+
+    // The originals are still there for tools
+    let lui = program.original(0).unwrap();
+    assert_eq!((lui.kind, lui.rd, lui.imm), (InsnKind::LUI, A0, 0x12345000));
+    let slli = program.original(4).unwrap();
+    assert_eq!((slli.kind, slli.rd, slli.rs1, slli.imm), (InsnKind::SLLI, T1, A2, 2));
+    assert_eq!(program.original(8).unwrap().kind, InsnKind::AUIPC);
+    assert!(program.verify().is_ok());
+}
+
+#[test]
+fn test_fused_runs_match_precise_runs() {
+    let program = fusable_program();
+    let (mut fused_memory, mut precise_memory) = (setup_memory(), setup_memory());
+    let (result, fused) = run_fused(&mut fused_memory, &program, None);
+    assert_eq!(result, ExecutionResult::Success(0x12345678 + 180));
+    let (_, precise) = run_precise(&mut precise_memory, &program, None);
+    assert_eq!((fused.cycle_count, fused.fuel_used()), (precise.cycle_count, precise.fuel_used()));
+
+    // Fuel can run out between the two halves of a pair
+    for max_fuel in 0..fused.fuel_used() {
+        let (fused_result, fused) = run_fused(&mut fused_memory, &program, Some(max_fuel));
+        let (precise_result, precise) = run_precise(&mut precise_memory, &program, Some(max_fuel));
+        assert_eq!(fused_result, precise_result);
+        assert_eq!((fused.get_ppc(), fused.cycle_count, fused.registers), (precise.get_ppc(), precise.cycle_count, precise.registers));
+    }
+}
+
+#[test]
+fn test_jump_into_second_half_of_pair() {
+    // jalr to 12 runs the ADDI on its own: a0 = 0 + 0x678
+    let program = setup_program(&[
+        li(T1, 12),
+        encode(InsnKind::JALR, 0, T1, 0, 0),
+        encode(InsnKind::LUI, A0, 0, 0, 0x12345000),
+        encode(InsnKind::ADDI, A0, A0, 0, 0x678),
+        li(A7, SYS_EXIT as i32),
+        ECALL,
+    ]);
+    assert_eq!(program.instructions[2].kind, InsnKind::LUI_ADDI);
+    assert_eq!(run_fused(&mut setup_memory(), &program, None).0, ExecutionResult::Success(0x678));
+}
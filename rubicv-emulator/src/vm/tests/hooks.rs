@@ -0,0 +1,88 @@
+use crate::hooks::Hooks;
+use crate::instructions::PreDecodedInstruction;
+use crate::syscalls::SYS_EXIT;
+use super::*;
+
+#[derive(Default)]
+struct Profiler {
+    steps: Vec<usize>,
+    reads: Vec<(u32, u8, u32)>,
+    writes: Vec<(u32, u8, u32)>,
+    branches: Vec<(usize, usize, bool)>,
+    syscalls: Vec<u32>,
+}
+
+impl Hooks for Profiler {
+    fn on_step(&mut self, ppc: usize, _insn: &PreDecodedInstruction, _registers: &[u32; 32]) {
+        self.steps.push(ppc);
+    }
+
+    fn on_mem_read(&mut self, addr: u32, size: u8, value: u32) {
+        self.reads.push((addr, size, value));
+    }
+
+    fn on_mem_write(&mut self, addr: u32, size: u8, _old: u32, value: u32) {
+        self.writes.push((addr, size, value));
+    }
+
+    fn on_branch(&mut self, from: usize, to: usize, taken: bool) {
+        self.branches.push((from, to, taken));
+    }
+
+    fn on_syscall(&mut self, syscall: u32, _registers: &[u32; 32]) {
+        self.syscalls.push(syscall);
+    }
+}
+
+#[test]
+fn test_hooks_observe_sum_program() {
+    let elf_bytes = include_bytes!("test_data/output.bin");
+    let predecoded_program = PredecodedProgram::new(elf_bytes).unwrap();
+    let num_iterations = 5u32;
+
+    let mut memory = setup_memory();
+    memory.memory_slab[RO_START as usize..RO_START as usize + 4].copy_from_slice(&num_iterations.to_le_bytes());
+    let mut vm = VM::<Profiler>::with_hooks(
+        memory.memory_slab.as_mut() as *mut [u8],
+        predecoded_program.entrypoint,
+        &predecoded_program.instructions,
+        Profiler::default(),
+    );
+
+    assert_eq!(vm.run(Some(1000)), ExecutionResult::Success(0));
+    let profile = &vm.hooks;
+
+    assert_eq!(profile.steps.len(), vm.cycle_count);
+    assert_eq!(profile.steps[0], predecoded_program.entrypoint);
+    assert_eq!(profile.syscalls, [SYS_EXIT]);
+    // n is read from the args region, the sum lands in scratch
+    assert_eq!(profile.reads[0], (ARGS_START, 4, num_iterations));
+    assert_eq!(profile.writes.last(), Some(&(SCRATCH_START, 4, 10)));
+    // the loop's back edge is taken on every iteration but the last
+    let back_edges = profile.branches.iter()
+        .filter(|(from, to, taken)| predecoded_program.instructions[*from].kind.is_branch() && to < from && *taken)
+        .count();
+    assert_eq!(back_edges, num_iterations as usize - 1);
+}
+
+#[test]
+fn test_hooks_see_untaken_branches() {
+    let program = setup_program(&[
+        li(A0, 1),
+        // not taken
+        encode(InsnKind::BEQ, 0, A0, 0, 8),
+        li(A7, SYS_EXIT as i32),
+        ECALL,
+    ]);
+    let mut memory = setup_memory();
+    let mut vm = VM::<Profiler>::with_hooks(
+        memory.memory_slab.as_mut() as *mut [u8],
+        program.entrypoint,
+        &program.instructions,
+        Profiler::default(),
+    );
+
+    assert_eq!(vm.run(None), ExecutionResult::Success(1));
+    assert_eq!(vm.hooks.branches, [(1, 2, false)]);
+    assert_eq!(vm.hooks.steps, [0, 1, 2, 3]);
+}
@@ -0,0 +1,132 @@
+use crate::syscalls::*;
+use super::*;
+
+// Runs `program` through resume() and natively on fresh memory set up by
+// `prepare`, checking every stop matches, and carries on after yields.
+// Returns the last result and the fuel the interpreter used.
+fn assert_jit_matches<S: SyscallHandler>(program: &PredecodedProgram, prepare: impl Fn(&mut [u8]), host: impl Fn() -> S, max_fuel: Option<u64>) -> (ExecutionResult, u64) {
+    let code = JitCode::new(&program.instructions).unwrap();
+    let (mut interpreted_memory, mut native_memory) = (setup_memory(), setup_memory());
+    prepare(&mut interpreted_memory.memory_slab);
+    prepare(&mut native_memory.memory_slab);
+    let mut interpreted = setup_program_vm(&mut interpreted_memory, program);
+    let mut native = setup_program_vm(&mut native_memory, program);
+    let (mut interpreted_host, mut native_host) = (host(), host());
+    let mut result = interpreted.run_with_syscalls(&mut interpreted_host, max_fuel);
+    let mut native_result = native.run_jit(&code, &mut native_host, max_fuel);
+    loop {
+        assert_eq!(native_result, result);
+        assert_eq!(native.registers, interpreted.registers);
+        assert_eq!((native.get_ppc(), native.cycle_count, native.fuel_used()), (interpreted.get_ppc(), interpreted.cycle_count, interpreted.fuel_used()));
+        assert_eq!(native_memory.memory_slab[..PRIVATE_SIZE as usize], interpreted_memory.memory_slab[..PRIVATE_SIZE as usize]);
+        if !matches!(result, ExecutionResult::Yield(_)) {
+            return (result, interpreted.fuel_used());
+        }
+        result = interpreted.resume(&mut interpreted_host);
+        native_result = native.resume_jit(&code, &mut native_host);
+    }
+}
+
+#[test]
+fn test_jit_sum_program() {
+    let program = sum_program();
+    for n in [0, 1, 7, 100] {
+        assert_eq!(assert_jit_matches(&program, write_args(&[n]), || NoSyscalls, None).0, ExecutionResult::Success(0));
+    }
+    assert_matches_for_any_fuel(|fuel| assert_jit_matches(&program, write_args(&[7]), || NoSyscalls, fuel));
+}
+
+#[test]
+fn test_jit_matches_interpreter_on_test_programs() {
+    assert_eq!(assert_matches_for_any_fuel(|fuel| assert_jit_matches(&compute_program(), |_| {}, || NoSyscalls, fuel)), ExecutionResult::Success(u32::MAX));
+    assert_eq!(assert_matches_for_any_fuel(|fuel| assert_jit_matches(&loop_program(), |_| {}, || NoSyscalls, fuel)), ExecutionResult::Success(0x12345678 + 180));
+    assert_eq!(assert_matches_for_any_fuel(|fuel| assert_jit_matches(&fusable_program(), |_| {}, || NoSyscalls, fuel)), ExecutionResult::Success(0x12345678 + 180));
+    // A store wrapped into the code area, and reads of the args
+    assert_eq!(assert_matches_for_any_fuel(|fuel| assert_jit_matches(&stray_store_program(), write_args(&[7]), || NoSyscalls, fuel)), ExecutionResult::Success(7));
+    // Yields and host calls that write guest memory
+    assert_eq!(assert_matches_for_any_fuel(|fuel| assert_jit_matches(&warm_up_program(), write_args(&[5]), || Marker, fuel)), ExecutionResult::Success(12));
+    assert_eq!(assert_matches_for_any_fuel(|fuel| assert_jit_matches(&counter_program(), |_| {}, Counter::default, fuel)), ExecutionResult::Success(150));
+}
+
+#[test]
+fn test_jit_memory_accesses() {
+    let scratch = |slab: &mut [u8]| {
+        slab[RO_START as usize] = 0x2A;
+        slab[SCRATCH_START as usize..SCRATCH_START as usize + 8].copy_from_slice(&[0x81, 2, 3, 4, 5, 6, 7, 0x88]);
+    };
+    let loads = [InsnKind::LB, InsnKind::LH, InsnKind::LW, InsnKind::LBU, InsnKind::LHU];
+    let stores = [InsnKind::SB, InsnKind::SH, InsnKind::SW];
+    // Aligned, misaligned, past the RW region and past the end of memory
+    for base in [SCRATCH_START, SCRATCH_START + 1, SCRATCH_START + 3, SCRATCH_START + 6, RO_START, RW_SIZE - 2, MEMORY_SIZE - 1, MEMORY_SIZE + 4] {
+        for kind in loads {
+            assert_jit_matches(&access_program(base, encode(kind, A0, T0, 0, 0)), scratch, || NoSyscalls, None);
+            // x0 loads are dropped natively
+            assert_jit_matches(&access_program(base, encode(kind, 0, T0, 0, 0)), scratch, || NoSyscalls, None);
+        }
+        for kind in stores {
+            assert_jit_matches(&access_program(base, encode(kind, 0, T0, T0, 0)), scratch, || NoSyscalls, None);
+        }
+    }
+}
+
+#[test]
+fn test_jit_traps() {
+    // Everything after the ebreak was charged with its block and is handed back
+    let program = setup_program(&[
+        li(A0, 1),
+        EBREAK,
+        li(A0, 2),
+        li(A7, SYS_EXIT as i32),
+        ECALL,
+    ]);
+    assert_eq!(assert_matches_for_any_fuel(|fuel| assert_jit_matches(&program, |_| {}, || NoSyscalls, fuel)), ExecutionResult::Breakpoint);
+
+    // A wild jalr lands on the sentinel, as does running off the end
+    let program = setup_program(&[
+        encode(InsnKind::LUI, T0, 0, 0, 0x7000),
+        encode(InsnKind::JALR, 0, T0, 0, 0),
+    ]);
+    assert_eq!(assert_jit_matches(&program, |_| {}, || NoSyscalls, None).0, ExecutionResult::Error(RubicVError::IllegalInstruction));
+    let program = setup_program(&[li(A0, 1)]);
+    assert_eq!(assert_jit_matches(&program, |_| {}, || NoSyscalls, None).0, ExecutionResult::Error(RubicVError::IllegalInstruction));
+}
+
+#[test]
+fn test_jit_random_compute() {
+    let kinds = [
+        InsnKind::ADD, InsnKind::SUB, InsnKind::XOR, InsnKind::OR, InsnKind::AND,
+        InsnKind::SLL, InsnKind::SRL, InsnKind::SRA, InsnKind::SLT, InsnKind::SLTU,
+        InsnKind::ADDI, InsnKind::XORI, InsnKind::ORI, InsnKind::ANDI, InsnKind::SLLI,
+        InsnKind::SRLI, InsnKind::SRAI, InsnKind::SLTI, InsnKind::SLTIU, InsnKind::LUI,
+        InsnKind::AUIPC, InsnKind::MUL, InsnKind::MULH, InsnKind::MULHSU, InsnKind::MULHU,
+        InsnKind::DIV, InsnKind::DIVU, InsnKind::REM, InsnKind::REMU,
+    ];
+    // Operands that hit the overflow and division edge cases
+    let seeds = [0, 1, -1, 2, -2, 31, 32, i32::MIN, i32::MAX, 0x1234_5678, -0x800];
+    let mut state = 0x2545_F491_4F6C_DD1Du64;
+    let mut random = move |n: usize| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state % n as u64) as usize
+    };
+    for _ in 0..200 {
+        let mut words: Vec<u32> = (1..12).flat_map(|r| {
+            let value = seeds[random(seeds.len())];
+            let low = (value << 20) >> 20;
+            [encode(InsnKind::LUI, r, 0, 0, value.wrapping_sub(low)), encode(InsnKind::ADDI, r, r, 0, low)]
+        }).collect();
+        for _ in 0..40 {
+            let kind = kinds[random(kinds.len())];
+            let (rd, rs1, rs2) = (random(12) as u8, random(12) as u8, random(12) as u8);
+            let imm = match kind {
+                InsnKind::SLLI | InsnKind::SRLI | InsnKind::SRAI => random(32) as i32,
+                InsnKind::LUI | InsnKind::AUIPC => (seeds[random(seeds.len())] as u32 & 0xFFFF_F000) as i32,
+                _ => seeds[random(seeds.len())].clamp(-2048, 2047),
+            };
+            words.push(encode(kind, rd, rs1, rs2, imm));
+        }
+        words.extend([li(A7, SYS_EXIT as i32), ECALL]);
+        assert_jit_matches(&setup_program(&words), |_| {}, || NoSyscalls, None);
+    }
+}
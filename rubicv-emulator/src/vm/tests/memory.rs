@@ -1,7 +1,7 @@
 use super::*;
 
-fn setup_vm(memory: &mut TestMemory) -> VM<EnforceZero> {
-    VM::<EnforceZero>::new(
+fn setup_vm(memory: &mut TestMemory) -> VM<'_> {
+    VM::new(
         memory.memory_slab.as_mut() as *mut [u8],
         0,
         &[]
@@ -103,7 +103,88 @@ fn test_ro_region_integrity() {
     vm.write_u32(RO_START, 0xDEADBEEF);
 
     // Verify RO region is unchanged
-    for i in 0..4 {
-        assert_eq!(vm.read_u8(RO_START + i as u32), original_values[i]);
+    for (i, original) in original_values.iter().enumerate() {
+        assert_eq!(vm.read_u8(RO_START + i as u32), *original);
     }
-}
\ No newline at end of file
+}
+fn run_with<M: MemoryPolicy>(base: u32, access: u32) -> (ExecutionResult, usize) {
+    let program = access_program(base, access);
+    let mut memory = setup_memory();
+    memory.memory_slab[RO_START as usize] = 0x2A;
+    memory.memory_slab[SCRATCH_START as usize..SCRATCH_START as usize + 8].copy_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]);
+    let mut vm = VM::<NoHooks, M>::with_policy(
+        memory.memory_slab.as_mut() as *mut [u8],
+        program.entrypoint,
+        &program.instructions,
+        NoHooks,
+    );
+    let result = vm.run(None);
+    (result, vm.get_ppc())
+}
+
+fn run_strict(base: u32, access: u32) -> (ExecutionResult, usize) {
+    run_with::<StrictMemory<SplitMisaligned>>(base, access)
+}
+
+#[test]
+fn test_strict_memory_reports_faulting_address() {
+    let store = encode(InsnKind::SW, 0, T0, A0, 0);
+    let load = encode(InsnKind::LBU, A0, T0, 0, 0);
+
+    // Same accesses that WrapMemory silently aliases
+    assert_eq!(run_strict(RO_START, store), (ExecutionResult::Error(RubicVError::WriteToReadOnlyMemory(RO_START)), 2));
+    assert_eq!(run_strict(RW_SIZE - 2, store), (ExecutionResult::Error(RubicVError::WriteToReadOnlyMemory(RW_SIZE - 2)), 2));
+    assert_eq!(run_strict(MEMORY_SIZE, store), (ExecutionResult::Error(RubicVError::MemoryWriteOutOfBounds(MEMORY_SIZE)), 2));
+    assert_eq!(run_strict(0x40, store), (ExecutionResult::Error(RubicVError::IllegalMemoryAccess(0x40)), 2));
+    assert_eq!(run_strict(MEMORY_SIZE + 3, load), (ExecutionResult::Error(RubicVError::MemoryReadOutOfBounds(MEMORY_SIZE + 3)), 2));
+    // A load into x0 is kept for its fault
+    assert_eq!(run_strict(MEMORY_SIZE, encode(InsnKind::LW, 0, T0, 0, 0)), (ExecutionResult::Error(RubicVError::MemoryReadOutOfBounds(MEMORY_SIZE)), 2));
+
+    // In-range accesses behave as before
+    assert_eq!(run_strict(SCRATCH_START, store).0, ExecutionResult::Success(0));
+    assert_eq!(run_strict(RO_START, load).0, ExecutionResult::Success(0x2A));
+    assert_eq!(run_strict(MEMORY_SIZE - 1, load).0, ExecutionResult::Success(0));
+}
+
+#[test]
+fn test_misaligned_accesses_are_split_by_default() {
+    let lw = encode(InsnKind::LW, A0, T0, 0, 0);
+    let lh = encode(InsnKind::LH, A0, T0, 0, 0);
+    assert_eq!(run_with::<WrapMemory>(SCRATCH_START + 1, lw).0, ExecutionResult::Success(0x05040302));
+    assert_eq!(run_with::<WrapMemory>(SCRATCH_START + 3, lh).0, ExecutionResult::Success(0x0504));
+
+    let mut memory = setup_memory();
+    memory.memory_slab[0] = 0x2A;
+    let mut vm = setup_vm(&mut memory);
+    // Each byte is masked on its own, so this one wraps to address 0
+    assert_eq!(vm.read_sized(MEMORY_SIZE - 1, 2), 0x2A00);
+    vm.write_sized(SCRATCH_START + 3, 4, 0x11223344);
+    assert_eq!(vm.read_u32(SCRATCH_START), 0x44000000);
+    assert_eq!(vm.read_u32(SCRATCH_START + 4), 0x00112233);
+    assert_eq!(vm.read_sized(SCRATCH_START + 3, 4), 0x11223344);
+    vm.write_sized(SCRATCH_START + 9, 2, 0xAABB);
+    assert_eq!(vm.read_u32(SCRATCH_START + 8), 0x00AABB00);
+}
+
+#[test]
+fn test_misaligned_accesses_trap() {
+    let lw = encode(InsnKind::LW, A0, T0, 0, 0);
+    let lhu = encode(InsnKind::LHU, A0, T0, 0, 0);
+    let lb = encode(InsnKind::LB, A0, T0, 0, 0);
+    let sh = encode(InsnKind::SH, 0, T0, A0, 0);
+    let sw = encode(InsnKind::SW, 0, T0, A0, 0);
+    type Trap = WrapMemory<TrapMisaligned>;
+
+    assert_eq!(run_with::<Trap>(SCRATCH_START + 1, lw), (ExecutionResult::Error(RubicVError::MemoryMisaligned(SCRATCH_START + 1)), 2));
+    assert_eq!(run_with::<Trap>(SCRATCH_START + 3, lhu), (ExecutionResult::Error(RubicVError::MemoryMisaligned(SCRATCH_START + 3)), 2));
+    assert_eq!(run_with::<Trap>(SCRATCH_START + 1, sh), (ExecutionResult::Error(RubicVError::MisalignedAccess(SCRATCH_START + 1)), 2));
+    assert_eq!(run_with::<Trap>(SCRATCH_START + 2, sw), (ExecutionResult::Error(RubicVError::MisalignedAccess(SCRATCH_START + 2)), 2));
+
+    assert_eq!(run_with::<Trap>(SCRATCH_START + 4, lw).0, ExecutionResult::Success(0x08070605));
+    assert_eq!(run_with::<Trap>(SCRATCH_START + 2, lhu).0, ExecutionResult::Success(0x0403));
+    assert_eq!(run_with::<Trap>(SCRATCH_START + 3, lb).0, ExecutionResult::Success(4));
+    assert_eq!(run_with::<Trap>(SCRATCH_START + 2, sh).0, ExecutionResult::Success(0));
+
+    // Range checks come first under StrictMemory
+    assert_eq!(run_with::<StrictMemory<TrapMisaligned>>(RW_SIZE - 2, sw).0, ExecutionResult::Error(RubicVError::WriteToReadOnlyMemory(RW_SIZE - 2)));
+}
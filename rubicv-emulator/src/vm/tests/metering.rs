@@ -0,0 +1,102 @@
+use crate::instructions::{InsnKind, INSN_KINDS, RV32IM_ISA};
+use crate::metering::CostTable;
+use crate::syscalls::*;
+use super::*;
+
+fn mixed_program() -> Vec<u32> {
+    vec![
+        li(A0, 84),                            // ADDI 1
+        li(A1, 2),                             // ADDI 1
+        encode(InsnKind::DIV, A2, A0, A1, 0),  // DIV  2
+        encode(InsnKind::XOR, A3, A2, A1, 0),  // XOR  2
+        encode(InsnKind::SW, 0, 0, A2, 0x100), // SW   1
+        encode(InsnKind::LW, A0, 0, 0, 0x100), // LW   1
+        li(A7, SYS_EXIT as i32),               // ADDI 1
+        ECALL,                                 // ECALL 1
+    ]
+}
+
+#[test]
+fn test_default_costs_follow_isa_cycles() {
+    let program = setup_program(&mixed_program());
+    let mut memory = setup_memory();
+    let mut vm = setup_program_vm(&mut memory, &program);
+
+    match vm.run(None) {
+        ExecutionResult::Success(code) => assert_eq!(code, 42),
+        other => panic!("Unexpected execution result: {:?}", other),
+    }
+    assert_eq!(vm.cycle_count, 8);
+    assert_eq!(vm.fuel_used(), 10);
+}
+
+#[test]
+fn test_memory_and_syscall_surcharges() {
+    let costs = CostTable::default()
+        .with_cost(InsnKind::DIV, 20)
+        .with_memory_access(5)
+        .with_syscall(50);
+    assert_eq!(costs.cost(InsnKind::LW), 6);
+    assert_eq!(costs.cost(InsnKind::SB), 6);
+    assert_eq!(costs.cost(InsnKind::ECALL), 51);
+    assert_eq!(costs.cost(InsnKind::EBREAK), 1);
+    assert_eq!(costs.cost(InsnKind::ADD), 1);
+
+    let program = PredecodedProgram::with_cost_table(&setup_program_bytes(&mixed_program()), &costs).unwrap();
+    let mut memory = setup_memory();
+    let mut vm = setup_program_vm(&mut memory, &program);
+
+    assert!(matches!(vm.run(None), ExecutionResult::Success(42)));
+    assert_eq!(vm.cycle_count, 8);
+    // 1 + 1 + 20 + 2 + 6 + 6 + 1 + 51
+    assert_eq!(vm.fuel_used(), 88);
+}
+
+#[test]
+fn test_every_kind_has_a_cost() {
+    let costs = CostTable::default().with_memory_access(5).with_syscall(50);
+    for (i, spec) in RV32IM_ISA.iter().enumerate() {
+        assert_eq!(spec.kind as usize, i);
+        assert!(costs.cost(spec.kind) >= spec.cycles as u32, "{:?}", spec.kind);
+    }
+    assert_eq!(RV32IM_ISA.len(), INSN_KINDS);
+
+    // The predecoder's own kinds cost what they replace
+    assert_eq!(costs.cost(InsnKind::LUI_ADDI), costs.cost(InsnKind::LUI));
+    assert_eq!(costs.cost(InsnKind::AUIPC_JALR), costs.cost(InsnKind::AUIPC));
+    assert_eq!(costs.cost(InsnKind::ADDI_BNE), costs.cost(InsnKind::ADDI));
+    assert_eq!(costs.cost(InsnKind::SLLI_ADD), costs.cost(InsnKind::SLLI));
+    assert_eq!(costs.cost(InsnKind::PROBE), 6);
+
+    let costs = costs.with_cost(InsnKind::LUI, 3).with_cost(InsnKind::LW, 4);
+    assert_eq!(costs.cost(InsnKind::LUI_ADDI), 3);
+    assert_eq!(costs.cost(InsnKind::PROBE), 9);
+}
+
+#[test]
+#[should_panic(expected = "PROBE is priced as the instruction it replaces")]
+fn test_with_cost_rejects_predecoder_kinds() {
+    let _ = CostTable::default().with_cost(InsnKind::PROBE, 3);
+}
+
+#[test]
+fn test_budget_stops_before_unaffordable_instruction() {
+    let program = setup_program(&mixed_program());
+    let mut memory = setup_memory();
+    let mut vm = setup_program_vm(&mut memory, &program);
+
+    // ADDI, ADDI fit; the DIV (2) does not fit in the remaining 1
+    assert!(matches!(vm.run(Some(3)), ExecutionResult::CycleLimitExceeded));
+    assert_eq!(vm.ppc, 2);
+    assert_eq!(vm.fuel_used(), 2);
+    assert_eq!(vm.fuel_remaining(), 1);
+    assert_eq!(vm.cycle_count, 2);
+
+    vm.add_fuel(7);
+    match vm.resume(&mut NoSyscalls) {
+        ExecutionResult::Success(code) => assert_eq!(code, 42),
+        other => panic!("Unexpected execution result: {:?}", other),
+    }
+    assert_eq!(vm.fuel_used(), 10);
+    assert_eq!(vm.fuel_remaining(), 0);
+}
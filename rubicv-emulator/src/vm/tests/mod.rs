@@ -2,11 +2,35 @@ use super::*;
 mod memory;
 mod compute;
 mod e2e;
+mod syscalls;
+mod resume;
+mod metering;
+mod blocks;
+mod trace;
+mod hooks;
+mod snapshot;
+mod fork;
+mod disasm;
+mod asm;
+mod replay;
+mod watch;
+mod verify;
+mod fusion;
+mod threaded;
+mod aot;
+mod batch;
+#[cfg(feature = "std")]
+mod pool;
+#[cfg(all(feature = "jit", target_arch = "x86_64", target_os = "linux"))]
+mod jit;
 // mod pre_decode;
 
 use alloc::vec;
 use alloc::vec::Vec;
 use alloc::boxed::Box;
+use crate::asm::encode;
+use crate::instructions::{InsnKind, PredecodedProgram};
+use crate::syscalls::*;
 
 struct TestMemory {
     memory_slab: Box<[u8]>, // One-time 4MB heap allocation, fixed size
@@ -19,4 +43,251 @@ fn setup_memory() -> TestMemory {
     TestMemory {
         memory_slab
     }
-}
\ No newline at end of file
+}
+
+// R-type helper
+fn encode_r_type(rs1: u32, rs2: u32, rd: u32, func3: u32, func7: u32) -> u32 {
+    let opcode = 0x33;  // R-type opcode
+    (func7 << 25) | (rs2 << 20) | (rs1 << 15) | (func3 << 12) | (rd << 7) | opcode
+}
+
+// I-type helper
+fn encode_i_type(rs1: u32, rd: u32, func3: u32, imm: i32) -> u32 {
+    let opcode = 0x13;  // I-type opcode
+    let imm = (imm as u32) & 0xFFF; // 12-bit immediate
+    (imm << 20) | (rs1 << 15) | (func3 << 12) | (rd << 7) | opcode
+}
+
+fn setup_elf_bytes(code_bytes: &[u8]) -> Vec<u8> {
+    let entry_point = [0u8;4];
+    let mut elf_bytes = vec![];
+    elf_bytes.extend_from_slice(&entry_point);
+    elf_bytes.extend_from_slice(code_bytes);
+    elf_bytes
+}
+
+const ECALL: u32 = 0x0000_0073;
+const EBREAK: u32 = 0x0010_0073;
+const RA: u8 = 1;
+const T0: u8 = 5;
+const T1: u8 = 6;
+const S0: u8 = 8;
+const A0: u8 = 10;
+const A1: u8 = 11;
+const A2: u8 = 12;
+const A3: u8 = 13;
+const A7: u8 = 17;
+
+fn li(rd: u8, imm: i32) -> u32 {
+    encode(InsnKind::ADDI, rd, 0, 0, imm)
+}
+
+fn setup_program_bytes(words: &[u32]) -> Vec<u8> {
+    let code: Vec<u8> = words.iter().flat_map(|w| w.to_le_bytes()).collect();
+    setup_elf_bytes(&code)
+}
+
+fn setup_program(words: &[u32]) -> PredecodedProgram {
+    PredecodedProgram::new(&setup_program_bytes(words)).unwrap()
+}
+
+fn setup_program_vm<'a>(memory: &mut TestMemory, program: &'a PredecodedProgram) -> VM<'a> {
+    VM::new(
+        memory.memory_slab.as_mut() as *mut [u8],
+        program.entrypoint,
+        &program.instructions
+    )
+}
+
+// Fills the args region from the start with `values`
+fn write_args(values: &[u32]) -> impl Fn(&mut [u8]) + '_ {
+    move |slab| {
+        for (i, value) in values.iter().enumerate() {
+            let at = ARGS_START as usize + 4 * i;
+            slab[at..at + 4].copy_from_slice(&value.to_le_bytes());
+        }
+    }
+}
+
+// Calls `assert_matches`, which runs a program through the interpreter and
+// another backend and checks they agree, without a fuel limit and then with
+// every budget below the fuel the interpreter used. Returns the result of
+// the unlimited run.
+fn assert_matches_for_any_fuel<R>(assert_matches: impl Fn(Option<u64>) -> (R, u64)) -> R {
+    let (result, fuel_used) = assert_matches(None);
+    for max_fuel in 0..fuel_used {
+        assert_matches(Some(max_fuel));
+    }
+    result
+}
+
+// The programs below are shared by the tests that check the backends
+// against the interpreter
+
+// sum of 0..args[0], left in scratch; exit(0)
+fn sum_program() -> PredecodedProgram {
+    PredecodedProgram::new(include_bytes!("test_data/output.bin")).unwrap()
+}
+
+// Returns a different value on every call and mirrors it into scratch
+#[derive(Default)]
+struct Counter {
+    calls: u32,
+}
+
+impl SyscallHandler for Counter {
+    fn handle(&mut self, _syscall: u32, ctx: &mut SyscallContext) -> Result<SyscallResult, RubicVError> {
+        self.calls += 1;
+        ctx.set_return(0, self.calls * 10);
+        ctx.write_u32(SCRATCH_START, self.calls * 10);
+        Ok(SyscallResult::Continue)
+    }
+}
+
+// s0 = sum of five host calls; exit(s0)
+fn counter_program() -> PredecodedProgram {
+    setup_program(&[
+        li(T1, 5),
+        li(A7, 1),
+        ECALL,
+        encode(InsnKind::ADD, S0, S0, A0, 0),
+        encode(InsnKind::ADDI, T0, T0, 0, 1),
+        encode(InsnKind::BNE, 0, T0, T1, -12),
+        encode(InsnKind::ADD, A0, S0, 0, 0),
+        li(A7, SYS_EXIT as i32),
+        ECALL,
+    ])
+}
+
+// The syscall warm_up_program yields on
+const MARKER: u32 = 1;
+
+// Stops the guest once its setup is done
+struct Marker;
+
+impl SyscallHandler for Marker {
+    fn handle(&mut self, syscall: u32, _ctx: &mut SyscallContext) -> Result<SyscallResult, RubicVError> {
+        match syscall {
+            MARKER => Ok(SyscallResult::Yield),
+            n => Err(RubicVError::SystemCall(n)),
+        }
+    }
+}
+
+// setup: scratch[0] = 7, then marker
+// request: scratch[0] = args[0] + scratch[0] + ro_slab[0]; exit(scratch[0])
+fn warm_up_program() -> PredecodedProgram {
+    setup_program(&[
+        encode(InsnKind::LUI, S0, 0, 0, SCRATCH_START as i32),
+        li(T0, 7),
+        encode(InsnKind::SW, 0, S0, T0, 0),
+        li(A7, MARKER as i32),
+        ECALL,
+        encode(InsnKind::LUI, T1, 0, 0, ARGS_START as i32),
+        encode(InsnKind::LW, A0, T1, 0, 0),
+        encode(InsnKind::LW, T0, S0, 0, 0),
+        encode(InsnKind::ADD, A0, A0, T0, 0),
+        encode(InsnKind::LW, T0, T1, 0, (RO_SLAB_START - ARGS_START) as i32),
+        encode(InsnKind::ADD, A0, A0, T0, 0),
+        encode(InsnKind::SW, 0, S0, A0, 0),
+        li(A7, SYS_EXIT as i32),
+        ECALL,
+    ])
+}
+
+// Signed and unsigned halves of products, division edge cases and
+// shifts by more than 31
+fn compute_program() -> PredecodedProgram {
+    setup_program(&[
+        li(T0, -3),
+        li(T1, 7),
+        encode(InsnKind::MULH, A0, T0, T1, 0),
+        encode(InsnKind::MULHSU, A1, T0, T1, 0),
+        encode(InsnKind::MULHU, A2, T0, T1, 0),
+        encode(InsnKind::ADD, A3, A0, A1, 0),
+        encode(InsnKind::ADD, A3, A3, A2, 0),
+        encode(InsnKind::DIV, A0, T0, 0, 0),
+        encode(InsnKind::REMU, A1, T1, 0, 0),
+        encode(InsnKind::LUI, A2, 0, 0, i32::MIN),
+        encode(InsnKind::DIV, A2, A2, T0, 0),
+        encode(InsnKind::SRA, A2, T0, T1, 0),
+        encode(InsnKind::SLTU, T1, T1, T0, 0),
+        encode(InsnKind::SRAI, T0, T0, 0, 1),
+        li(A7, SYS_EXIT as i32),
+        ECALL,
+    ])
+}
+
+// Loops, a call, loads and stores, and fused pairs that fuel can split
+fn loop_program() -> PredecodedProgram {
+    setup_program(&[
+        encode(InsnKind::LUI, A0, 0, 0, 0x12345000),
+        encode(InsnKind::ADDI, A0, A0, 0, 0x678),
+        li(A2, 0),
+        li(A1, 10),
+        encode(InsnKind::LUI, T0, 0, 0, SCRATCH_START as i32),
+        // loop: a3 += a2 << 2, stored at scratch + a2
+        encode(InsnKind::SLLI, T1, A2, 0, 2),
+        encode(InsnKind::ADD, A3, T1, A3, 0),
+        encode(InsnKind::ADD, T1, T0, A2, 0),
+        encode(InsnKind::SB, 0, T1, A3, 0),
+        encode(InsnKind::ADDI, A2, A2, 0, 1),
+        encode(InsnKind::BNE, 0, A2, A1, -20),
+        // call 17, then jump to the exit
+        encode(InsnKind::AUIPC, 1, 0, 0, 0),
+        encode(InsnKind::JALR, 1, 1, 0, 20),
+        encode(InsnKind::LW, T1, T0, 0, 4),
+        encode(InsnKind::LB, A1, T0, 0, 9),
+        encode(InsnKind::JAL, 0, 0, 0, 12),
+        encode(InsnKind::ADD, A0, A0, A3, 0),
+        encode(InsnKind::JALR, 0, 1, 0, 0),
+        li(A7, SYS_EXIT as i32),
+        ECALL,
+    ])
+}
+
+// 0x12345678 plus 4 * (0 + 1 + .. + 9), added up in a call
+fn fusable_program() -> PredecodedProgram {
+    setup_program(&[
+        encode(InsnKind::LUI, A0, 0, 0, 0x12345000),
+        encode(InsnKind::ADDI, A0, A0, 0, 0x678),
+        li(A2, 0),
+        li(A1, 10),
+        // loop: a3 += a2 << 2
+        encode(InsnKind::SLLI, T1, A2, 0, 2),
+        encode(InsnKind::ADD, A3, T1, A3, 0),
+        encode(InsnKind::ADDI, A2, A2, 0, 1),
+        encode(InsnKind::BNE, 0, A2, A1, -12),
+        // call 11, then jump to the exit
+        encode(InsnKind::AUIPC, RA, 0, 0, 0),
+        encode(InsnKind::JALR, RA, RA, 0, 12),
+        encode(InsnKind::JAL, 0, 0, 0, 12),
+        encode(InsnKind::ADD, A0, A0, A3, 0),
+        encode(InsnKind::JALR, 0, RA, 0, 0),
+        li(A7, SYS_EXIT as i32),
+        ECALL,
+    ])
+}
+
+// A store through a pointer into the args region wraps into the code area
+fn stray_store_program() -> PredecodedProgram {
+    setup_program(&[
+        encode(InsnKind::LUI, T0, 0, 0, ARGS_START as i32),
+        li(T1, 0x55),
+        encode(InsnKind::SW, 0, T0, T1, 0x10),
+        encode(InsnKind::LW, A0, T0, 0, 0),
+        li(A7, SYS_EXIT as i32),
+        ECALL,
+    ])
+}
+
+// t0 = base; then the access; exit(a0)
+fn access_program(base: u32, access: u32) -> PredecodedProgram {
+    setup_program(&[
+        encode(InsnKind::LUI, T0, 0, 0, base.wrapping_add(0x800) as i32 & !0xFFF),
+        encode(InsnKind::ADDI, T0, T0, 0, ((base << 20) as i32) >> 20),
+        access,
+        li(A7, SYS_EXIT as i32),
+        ECALL,
+    ])
+}
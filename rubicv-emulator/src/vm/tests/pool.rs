@@ -0,0 +1,94 @@
+use crate::syscalls::*;
+use super::*;
+
+const T2: u8 = 7;
+
+// What a job gives when run on its own, on a full copy of the image
+fn run_alone<S: SyscallHandler>(program: &PredecodedProgram, image: &[u8], job: &Job, host: &mut S) -> JobResult {
+    let mut memory = setup_memory();
+    memory.memory_slab.copy_from_slice(image);
+    for (i, arg) in job.args.iter().enumerate() {
+        let at = ARGS_START as usize + 4 * i;
+        memory.memory_slab[at..at + 4].copy_from_slice(&arg.to_le_bytes());
+    }
+    let mut vm = setup_program_vm(&mut memory, program);
+    let result = vm.run_with_syscalls(host, job.max_fuel);
+    let (cycles, fuel_used) = (vm.cycle_count, vm.fuel_used());
+    let scratch = SCRATCH_START as usize;
+    JobResult { result, cycles, fuel_used, scratch: memory.memory_slab[scratch..scratch + SCRATCH_SIZE as usize].to_vec() }
+}
+
+fn arg_jobs(args: impl IntoIterator<Item = u32>, max_fuel: Option<u64>) -> Vec<Job> {
+    args.into_iter().map(|arg| Job { args: vec![arg], max_fuel }).collect()
+}
+
+// scratch[0] = args[0] + ro_slab[0]; scratch[1] += 1; exit(scratch[0])
+fn ro_reader_program() -> PredecodedProgram {
+    setup_program(&[
+        encode(InsnKind::LUI, T1, 0, 0, ARGS_START as i32),
+        encode(InsnKind::LW, A0, T1, 0, 0),
+        encode(InsnKind::LW, T0, T1, 0, (RO_SLAB_START - ARGS_START) as i32),
+        encode(InsnKind::ADD, A0, A0, T0, 0),
+        encode(InsnKind::LUI, S0, 0, 0, SCRATCH_START as i32),
+        encode(InsnKind::SW, 0, S0, A0, 0),
+        encode(InsnKind::LW, T2, S0, 0, 4),
+        encode(InsnKind::ADDI, T2, T2, 0, 1),
+        encode(InsnKind::SW, 0, S0, T2, 4),
+        li(A7, SYS_EXIT as i32),
+        ECALL,
+    ])
+}
+
+#[test]
+fn test_pool_runs_jobs_in_order() {
+    let program = PredecodedProgram::new(include_bytes!("test_data/output.bin")).unwrap();
+    let image = setup_memory();
+    let jobs = arg_jobs(0..100, None);
+    let results = BatchRunner::new(&program, &image.memory_slab).threads(4).run(&jobs);
+    for (n, (job, result)) in jobs.iter().zip(&results).enumerate() {
+        assert_eq!(*result, run_alone(&program, &image.memory_slab, job, &mut NoSyscalls));
+        // sum of 0..n
+        assert_eq!(result.scratch[..4], ((n * n.saturating_sub(1) / 2) as u32).to_le_bytes());
+    }
+}
+
+#[test]
+fn test_pool_jobs_share_the_image_and_own_their_rw_region() {
+    let program = ro_reader_program();
+    let mut image = setup_memory();
+    let ro = RO_SLAB_START as usize;
+    image.memory_slab[ro..ro + 4].copy_from_slice(&1000u32.to_le_bytes());
+    // Initial RW contents come from the image for every job
+    let scratch = SCRATCH_START as usize;
+    image.memory_slab[scratch + 4..scratch + 8].copy_from_slice(&41u32.to_le_bytes());
+
+    // One worker reuses its private region for every job
+    for threads in [1, 3] {
+        let results = BatchRunner::new(&program, &image.memory_slab).threads(threads).run(&arg_jobs(0..10, None));
+        for (n, result) in results.iter().enumerate() {
+            assert_eq!(result.result, ExecutionResult::Success(1000 + n as u32));
+            assert_eq!(result.scratch[4..8], 42u32.to_le_bytes());
+        }
+    }
+    // Nothing was written to the image
+    assert_eq!(image.memory_slab[scratch..scratch + 4], [0; 4]);
+}
+
+#[test]
+fn test_pool_jobs_have_their_own_host_and_budget() {
+    let program = counter_program();
+    let image = setup_memory();
+    let runner = BatchRunner::new(&program, &image.memory_slab).threads(2);
+    // Every job starts with a fresh Counter
+    let jobs: Vec<Job> = [None, Some(10), Some(0), None].into_iter().map(|max_fuel| Job { args: vec![], max_fuel }).collect();
+    let results = runner.run_with_syscalls(&jobs, Counter::default);
+    for (job, result) in jobs.iter().zip(&results) {
+        assert_eq!(*result, run_alone(&program, &image.memory_slab, job, &mut Counter::default()));
+    }
+    assert_eq!(results[0].result, ExecutionResult::Success(150));
+    assert_eq!(results[1].result, ExecutionResult::CycleLimitExceeded);
+
+    let program = warm_up_program();
+    let results = BatchRunner::new(&program, &image.memory_slab).run_with_syscalls(&arg_jobs(0..3, None), || Marker);
+    assert!(results.iter().all(|result| result.result == ExecutionResult::Yield(1)));
+}
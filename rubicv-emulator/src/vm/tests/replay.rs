@@ -0,0 +1,49 @@
+use crate::replay::TimeTravel;
+use super::*;
+
+fn run_to_exit(history: &mut TimeTravel, vm: &mut dyn VMOperations, host: &mut Counter) -> u32 {
+    loop {
+        match history.step(vm, host) {
+            None => {}
+            Some(ExecutionResult::Success(code)) => return code,
+            Some(other) => panic!("Unexpected execution result: {:?}", other),
+        }
+    }
+}
+
+#[test]
+fn test_reverse_execution_replays_syscalls() {
+    let program = counter_program();
+    let mut memory = setup_memory();
+    let mut vm = setup_program_vm(&mut memory, &program);
+    let mut host = Counter::default();
+    let mut history = TimeTravel::new(&vm, 4);
+
+    assert_eq!(run_to_exit(&mut history, &mut vm, &mut host), 150);
+    let end_cycle = vm.cycle_count;
+    assert_eq!(history.log().records.len(), 5);
+    assert_eq!(history.log().records[4].writes.len(), 1);
+
+    // back to the last time the sum was about to be updated
+    assert!(history.reverse_until(&mut vm, |vm| vm.get_ppc() == 3).unwrap());
+    assert_eq!(vm.registers[A0 as usize], 50);
+    assert_eq!(vm.registers[S0 as usize], 100);
+    assert_eq!(vm.read_u32(SCRATCH_START), 50);
+
+    // and one more instruction, to before the ecall
+    assert!(history.reverse_step(&mut vm).unwrap());
+    assert_eq!(vm.get_ppc(), 2);
+    assert_eq!(vm.registers[A0 as usize], 40);
+    assert_eq!(vm.read_u32(SCRATCH_START), 40);
+
+    // forward again without asking the host
+    assert_eq!(run_to_exit(&mut history, &mut vm, &mut host), 150);
+    assert_eq!(host.calls, 5);
+    assert_eq!(vm.cycle_count, end_cycle);
+
+    assert!(!history.reverse_until(&mut vm, |_| false).unwrap());
+    assert_eq!(vm.cycle_count, 0);
+    assert_eq!(vm.get_ppc(), program.entrypoint);
+    assert_eq!(vm.read_u32(SCRATCH_START), 0);
+    assert!(!history.reverse_step(&mut vm).unwrap());
+}
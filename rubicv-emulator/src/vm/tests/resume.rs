@@ -0,0 +1,139 @@
+use crate::metering::CostTable;
+use crate::syscalls::*;
+use super::*;
+
+const S1: u8 = 9;
+
+// Pauses the guest on every syscall 1
+struct Generator;
+
+impl SyscallHandler for Generator {
+    fn handle(&mut self, syscall: u32, _ctx: &mut SyscallContext) -> Result<SyscallResult, RubicVError> {
+        match syscall {
+            1 => Ok(SyscallResult::Yield),
+            n => Err(RubicVError::SystemCall(n)),
+        }
+    }
+}
+
+#[test]
+fn test_resume_after_yield() {
+    // for i in 0..3 { yield(i) }; exit(host_total)
+    let program = setup_program(&[
+        li(S0, 0),
+        li(S1, 3),
+        encode(InsnKind::ADDI, A0, S0, 0, 0),
+        li(A7, 1),
+        ECALL,
+        encode(InsnKind::ADDI, S0, S0, 0, 1),
+        encode(InsnKind::BNE, 0, S0, S1, -16),
+        encode(InsnKind::ADDI, A0, A1, 0, 0),
+        li(A7, SYS_EXIT as i32),
+        ECALL,
+    ]);
+    let mut memory = setup_memory();
+    let mut vm = setup_program_vm(&mut memory, &program);
+    let mut generator = Generator;
+
+    let mut yielded = vec![];
+    let mut result = vm.run_with_syscalls(&mut generator, Some(1000));
+    while let ExecutionResult::Yield(1) = result {
+        yielded.push(vm.registers[A0 as usize]);
+        // host keeps a running total in a1 for the guest
+        vm.registers[A1 as usize] += vm.registers[A0 as usize];
+        result = vm.resume(&mut generator);
+    }
+
+    assert_eq!(yielded, [0, 1, 2]);
+    match result {
+        ExecutionResult::Success(code) => assert_eq!(code, 3),
+        other => panic!("Unexpected execution result: {:?}", other),
+    }
+}
+
+#[test]
+fn test_resume_after_breakpoint() {
+    let program = setup_program(&[
+        li(A0, 1),
+        EBREAK,
+        encode(InsnKind::ADDI, A0, A0, 0, 1),
+        li(A7, SYS_EXIT as i32),
+        ECALL,
+    ]);
+    let mut memory = setup_memory();
+    let mut vm = setup_program_vm(&mut memory, &program);
+
+    let result = vm.run(Some(100));
+    assert!(matches!(result, ExecutionResult::Breakpoint));
+    assert!(result.is_resumable());
+    assert_eq!(vm.ppc, 2);
+    assert_eq!(vm.registers[A0 as usize], 1);
+
+    vm.registers[A0 as usize] = 10;
+    match vm.resume(&mut NoSyscalls) {
+        ExecutionResult::Success(code) => assert_eq!(code, 11),
+        other => panic!("Unexpected execution result: {:?}", other),
+    }
+}
+
+#[test]
+fn test_resume_in_fuel_slices() {
+    let elf_bytes = include_bytes!("test_data/output.bin");
+    // one unit of fuel per instruction
+    let predecoded_program = PredecodedProgram::with_cost_table(elf_bytes, &CostTable::uniform(1)).unwrap();
+    let num_iterations = 50u32;
+
+    // Reference run in one go
+    let mut memory = setup_memory();
+    memory.memory_slab[RO_START as usize..RO_START as usize + 4].copy_from_slice(&num_iterations.to_le_bytes());
+    let mut vm = setup_program_vm(&mut memory, &predecoded_program);
+    assert!(matches!(vm.run(None), ExecutionResult::Success(0)));
+    let total_cycles = vm.cycle_count;
+
+    // Same program metered in slices of 7 instructions
+    let mut memory = setup_memory();
+    memory.memory_slab[RO_START as usize..RO_START as usize + 4].copy_from_slice(&num_iterations.to_le_bytes());
+    let mut vm = setup_program_vm(&mut memory, &predecoded_program);
+    let mut slices = 1;
+    let mut result = vm.run(Some(7));
+    while let ExecutionResult::CycleLimitExceeded = result {
+        assert_eq!(vm.cycle_count, slices * 7);
+        vm.add_fuel(7);
+        slices += 1;
+        result = vm.resume(&mut NoSyscalls);
+    }
+
+    assert!(matches!(result, ExecutionResult::Success(0)));
+    assert_eq!(vm.cycle_count, total_cycles);
+    assert_eq!(vm.fuel_used(), total_cycles as u64);
+    assert_eq!(slices, total_cycles.div_ceil(7));
+    assert_eq!(vm.read_u32(SCRATCH_START), (num_iterations - 1) * num_iterations / 2);
+}
+
+#[test]
+fn test_single_step_through_breakpoint() {
+    let program = setup_program(&[
+        li(A0, 1),
+        EBREAK,
+        li(A7, SYS_EXIT as i32),
+        ECALL,
+    ]);
+    let mut memory = setup_memory();
+    let mut vm = setup_program_vm(&mut memory, &program);
+
+    let mut stops = vec![];
+    loop {
+        let stop = vm.single_step(&mut NoSyscalls);
+        stops.push((vm.ppc, stop));
+        if matches!(stops.last(), Some((_, Some(ExecutionResult::Success(_))))) {
+            break;
+        }
+    }
+    assert_eq!(stops, [
+        (1, None),
+        (2, Some(ExecutionResult::Breakpoint)),
+        (3, None),
+        (3, Some(ExecutionResult::Success(1))),
+    ]);
+    assert_eq!(vm.cycle_count, 4);
+}
@@ -0,0 +1,76 @@
+use crate::metering::CostTable;
+use crate::vm::snapshot::crc32;
+use super::*;
+
+fn sum_memory(n: u32) -> TestMemory {
+    let mut memory = setup_memory();
+    let ro_mem_start = RO_START as usize;
+    memory.memory_slab[ro_mem_start..ro_mem_start + 4].copy_from_slice(&n.to_le_bytes());
+    memory
+}
+
+#[test]
+fn test_snapshot_restore_continues_run() {
+    let program = PredecodedProgram::new(include_bytes!("test_data/output.bin")).unwrap();
+    let mut memory = sum_memory(20);
+    let mut vm = setup_program_vm(&mut memory, &program);
+
+    // stop part way through the loop
+    assert_eq!(vm.run(Some(40)), ExecutionResult::CycleLimitExceeded);
+    let snapshot = vm.snapshot();
+    assert_eq!(snapshot.len(), SNAPSHOT_SIZE);
+    assert_eq!(snapshot[..4], SNAPSHOT_MAGIC);
+
+    vm.add_fuel(1000);
+    assert_eq!(vm.resume(&mut NoSyscalls), ExecutionResult::Success(0));
+
+    // a fresh slab only needs the RO contents, RW comes from the snapshot
+    let mut other_memory = sum_memory(20);
+    let mut restored = VM::restore(&snapshot, other_memory.memory_slab.as_mut(), &program).unwrap();
+    restored.add_fuel(1000);
+    assert_eq!(restored.resume(&mut NoSyscalls), ExecutionResult::Success(0));
+
+    assert_eq!(restored.registers, vm.registers);
+    assert_eq!(restored.get_ppc(), vm.get_ppc());
+    assert_eq!(restored.cycle_count, vm.cycle_count);
+    assert_eq!(restored.fuel_used(), vm.fuel_used());
+    assert_eq!(restored.read_u32(SCRATCH_START), 19 * 20 / 2);
+}
+
+#[test]
+fn test_restore_rejects_corrupt_snapshot() {
+    let program = setup_program(&[li(A0, 1), ECALL]);
+    let mut memory = setup_memory();
+    let vm = setup_program_vm(&mut memory, &program);
+    let snapshot = vm.snapshot();
+    let mut other_memory = setup_memory();
+    let slab = other_memory.memory_slab.as_mut() as *mut [u8];
+
+    let mut flipped = snapshot.clone();
+    flipped[40] ^= 1;
+    assert_eq!(VM::restore(&flipped, slab, &program).err(), Some(RubicVError::InvalidSnapshot));
+    assert_eq!(VM::restore(&snapshot[..100], slab, &program).err(), Some(RubicVError::InvalidSnapshot));
+    // States the VM cannot be in, with the checksum fixed up
+    let resealed = |edit: &dyn Fn(&mut [u8])| {
+        let mut bytes = snapshot.clone();
+        edit(&mut bytes);
+        let checksum = crc32(&bytes[..SNAPSHOT_SIZE - 4]);
+        bytes[SNAPSHOT_SIZE - 4..].copy_from_slice(&checksum.to_le_bytes());
+        bytes
+    };
+    let x0_set = resealed(&|bytes| bytes[44] = 1);
+    assert_eq!(VM::restore(&x0_set, slab, &program).err(), Some(RubicVError::InvalidSnapshot));
+    // fuel_used past fuel_limit
+    let overspent = resealed(&|bytes| {
+        bytes[28..36].copy_from_slice(&2u64.to_le_bytes());
+        bytes[36..44].copy_from_slice(&1u64.to_le_bytes());
+    });
+    assert_eq!(VM::restore(&overspent, slab, &program).err(), Some(RubicVError::InvalidSnapshot));
+
+    // same code with a different cost table is a different program
+    let repriced = PredecodedProgram::with_cost_table(&setup_program_bytes(&[li(A0, 1), ECALL]), &CostTable::uniform(3)).unwrap();
+    assert_eq!(VM::restore(&snapshot, slab, &repriced).err(), Some(RubicVError::SnapshotProgramMismatch));
+    let other = setup_program(&[li(A0, 2), ECALL]);
+    assert_eq!(VM::restore(&snapshot, slab, &other).err(), Some(RubicVError::SnapshotProgramMismatch));
+    assert!(VM::restore(&snapshot, slab, &program).is_ok());
+}
@@ -0,0 +1,107 @@
+use crate::syscalls::*;
+use super::*;
+
+#[derive(Default)]
+struct HostServices {
+    calls: Vec<(u32, [u32; SYSCALL_ARGS])>,
+}
+
+impl SyscallHandler for HostServices {
+    fn handle(&mut self, syscall: u32, ctx: &mut SyscallContext) -> Result<SyscallResult, RubicVError> {
+        self.calls.push((syscall, ctx.args()));
+        match syscall {
+            // add(a0, a1) -> a0
+            1 => {
+                let sum = ctx.arg(0).wrapping_add(ctx.arg(1));
+                ctx.set_return(0, sum);
+                Ok(SyscallResult::Continue)
+            },
+            // fill a word of guest memory at a0
+            2 => {
+                let addr = ctx.arg(0);
+                ctx.write_u32(addr, 0xC0FFEE);
+                Ok(SyscallResult::Continue)
+            },
+            3 => Ok(SyscallResult::Halt(ctx.arg(0) + 100)),
+            n => Err(RubicVError::SystemCall(n)),
+        }
+    }
+}
+
+#[test]
+fn test_syscall_continues_execution() {
+    let program = setup_program(&[
+        li(A7, 1),
+        li(A0, 20),
+        li(A1, 22),
+        ECALL,
+        li(A7, SYS_EXIT as i32),
+        ECALL,
+    ]);
+    let mut memory = setup_memory();
+    let mut vm = setup_program_vm(&mut memory, &program);
+    let mut host = HostServices::default();
+
+    match vm.run_with_syscalls(&mut host, Some(100)) {
+        ExecutionResult::Success(code) => assert_eq!(code, 42),
+        other => panic!("Unexpected execution result: {:?}", other),
+    }
+    // exit is handled by the VM, not the host
+    assert_eq!(host.calls.len(), 1);
+    assert_eq!(host.calls[0].0, 1);
+    assert_eq!(host.calls[0].1[..2], [20, 22]);
+}
+
+#[test]
+fn test_syscall_writes_guest_memory() {
+    let program = setup_program(&[
+        encode(InsnKind::LUI, A0, 0, 0, SCRATCH_START as i32),
+        li(A7, 2),
+        ECALL,
+        encode(InsnKind::LW, A0, A0, 0, 0),
+        li(A7, SYS_EXIT as i32),
+        ECALL,
+    ]);
+    let mut memory = setup_memory();
+    let mut vm = setup_program_vm(&mut memory, &program);
+
+    match vm.run_with_syscalls(&mut HostServices::default(), Some(100)) {
+        ExecutionResult::Success(code) => assert_eq!(code, 0xC0FFEE),
+        other => panic!("Unexpected execution result: {:?}", other),
+    }
+    assert_eq!(vm.read_u32(SCRATCH_START), 0xC0FFEE);
+}
+
+#[test]
+fn test_syscall_halt() {
+    let program = setup_program(&[
+        li(A7, 3),
+        li(A0, 5),
+        ECALL,
+        li(A0, 1),
+        li(A7, SYS_EXIT as i32),
+        ECALL,
+    ]);
+    let mut memory = setup_memory();
+    let mut vm = setup_program_vm(&mut memory, &program);
+
+    match vm.run_with_syscalls(&mut HostServices::default(), Some(100)) {
+        ExecutionResult::Success(code) => assert_eq!(code, 105),
+        other => panic!("Unexpected execution result: {:?}", other),
+    }
+}
+
+#[test]
+fn test_unhandled_syscall() {
+    let program = setup_program(&[
+        li(A7, 9),
+        ECALL,
+    ]);
+    let mut memory = setup_memory();
+    let mut vm = setup_program_vm(&mut memory, &program);
+
+    match vm.run(Some(100)) {
+        ExecutionResult::Error(RubicVError::SystemCall(9)) => {},
+        other => panic!("Unexpected execution result: {:?}", other),
+    }
+}
@@ -0,0 +1,186 @@
+// Generated by `utils aot`, do not edit
+use rubicv_emulator::aot::{Meter, Program, VmState};
+use rubicv_emulator::errors::RubicVError;
+use rubicv_emulator::syscalls::SyscallHandler;
+use rubicv_emulator::vm::ExecutionResult;
+
+pub const ENTRYPOINT: usize = 0;
+
+pub static PROGRAM: Program = Program { meters: &METERS, block, step };
+
+pub fn run(state: &mut VmState, max_fuel: Option<u64>) -> ExecutionResult {
+    PROGRAM.run(state, max_fuel)
+}
+
+pub fn run_with_syscalls<S: SyscallHandler + ?Sized>(state: &mut VmState, syscalls: &mut S, max_fuel: Option<u64>) -> ExecutionResult {
+    PROGRAM.run_with_syscalls(state, syscalls, max_fuel)
+}
+
+static METERS: [Meter; 17] = [
+    Meter { cost: 1, block_cost: 21, block_len: 16 },
+    Meter { cost: 1, block_cost: 20, block_len: 15 },
+    Meter { cost: 1, block_cost: 19, block_len: 14 },
+    Meter { cost: 1, block_cost: 18, block_len: 13 },
+    Meter { cost: 1, block_cost: 17, block_len: 12 },
+    Meter { cost: 1, block_cost: 16, block_len: 11 },
+    Meter { cost: 1, block_cost: 15, block_len: 10 },
+    Meter { cost: 2, block_cost: 14, block_len: 9 },
+    Meter { cost: 2, block_cost: 12, block_len: 8 },
+    Meter { cost: 1, block_cost: 10, block_len: 7 },
+    Meter { cost: 2, block_cost: 9, block_len: 6 },
+    Meter { cost: 2, block_cost: 7, block_len: 5 },
+    Meter { cost: 1, block_cost: 5, block_len: 4 },
+    Meter { cost: 2, block_cost: 4, block_len: 3 },
+    Meter { cost: 1, block_cost: 2, block_len: 2 },
+    Meter { cost: 1, block_cost: 1, block_len: 1 },
+    Meter { cost: 0, block_cost: 0, block_len: 1 },
+];
+
+#[allow(clippy::all)]
+fn block(s: &mut VmState, ppc: usize) -> Result<(), RubicVError> {
+    match ppc {
+        0 => {
+            // 0x0000: addi t0, zero, -3
+            s.registers[5] = s.registers[0].wrapping_add(0xfffffffd);
+            // 0x0004: addi t1, zero, 7
+            s.registers[6] = s.registers[0].wrapping_add(0x7);
+            // 0x0008: mulh a0, t0, t1
+            s.registers[10] = ((s.registers[5] as i32 as i64).wrapping_mul(s.registers[6] as i32 as i64) >> 32) as u32;
+            // 0x000c: mulhsu a1, t0, t1
+            s.registers[11] = ((s.registers[5] as i32 as i64).wrapping_mul(s.registers[6] as i64) >> 32) as u32;
+            // 0x0010: mulhu a2, t0, t1
+            s.registers[12] = ((s.registers[5] as u64).wrapping_mul(s.registers[6] as u64) >> 32) as u32;
+            // 0x0014: add a3, a0, a1
+            s.registers[13] = s.registers[10].wrapping_add(s.registers[11]);
+            // 0x0018: add a3, a3, a2
+            s.registers[13] = s.registers[13].wrapping_add(s.registers[12]);
+            // 0x001c: div a0, t0, zero
+            s.registers[10] = rubicv_emulator::aot::div(s.registers[5], s.registers[0]);
+            // 0x0020: remu a1, t1, zero
+            s.registers[11] = rubicv_emulator::aot::remu(s.registers[6], s.registers[0]);
+            // 0x0024: lui a2, 0x80000
+            s.registers[12] = 0x80000000;
+            // 0x0028: div a2, a2, t0
+            s.registers[12] = rubicv_emulator::aot::div(s.registers[12], s.registers[5]);
+            // 0x002c: sra a2, t0, t1
+            s.registers[12] = ((s.registers[5] as i32) >> (s.registers[6] & 0x1f)) as u32;
+            // 0x0030: sltu t1, t1, t0
+            s.registers[6] = (s.registers[6] < s.registers[5]) as u32;
+            // 0x0034: srai t0, t0, 1
+            s.registers[5] = ((s.registers[5] as i32) >> 1) as u32;
+            // 0x0038: addi a7, zero, 0
+            s.registers[17] = s.registers[0].wrapping_add(0x0);
+            // 0x003c: ecall
+            s.ppc = 15;
+            Err(RubicVError::SystemCall(s.registers[17]))
+        }
+        _ => unreachable!(),
+    }
+}
+
+#[allow(clippy::all)]
+fn step(s: &mut VmState, ppc: usize) -> Result<(), RubicVError> {
+    match ppc {
+        0 => {
+            // 0x0000: addi t0, zero, -3
+            s.registers[5] = s.registers[0].wrapping_add(0xfffffffd);
+            s.ppc = 1;
+            Ok(())
+        }
+        1 => {
+            // 0x0004: addi t1, zero, 7
+            s.registers[6] = s.registers[0].wrapping_add(0x7);
+            s.ppc = 2;
+            Ok(())
+        }
+        2 => {
+            // 0x0008: mulh a0, t0, t1
+            s.registers[10] = ((s.registers[5] as i32 as i64).wrapping_mul(s.registers[6] as i32 as i64) >> 32) as u32;
+            s.ppc = 3;
+            Ok(())
+        }
+        3 => {
+            // 0x000c: mulhsu a1, t0, t1
+            s.registers[11] = ((s.registers[5] as i32 as i64).wrapping_mul(s.registers[6] as i64) >> 32) as u32;
+            s.ppc = 4;
+            Ok(())
+        }
+        4 => {
+            // 0x0010: mulhu a2, t0, t1
+            s.registers[12] = ((s.registers[5] as u64).wrapping_mul(s.registers[6] as u64) >> 32) as u32;
+            s.ppc = 5;
+            Ok(())
+        }
+        5 => {
+            // 0x0014: add a3, a0, a1
+            s.registers[13] = s.registers[10].wrapping_add(s.registers[11]);
+            s.ppc = 6;
+            Ok(())
+        }
+        6 => {
+            // 0x0018: add a3, a3, a2
+            s.registers[13] = s.registers[13].wrapping_add(s.registers[12]);
+            s.ppc = 7;
+            Ok(())
+        }
+        7 => {
+            // 0x001c: div a0, t0, zero
+            s.registers[10] = rubicv_emulator::aot::div(s.registers[5], s.registers[0]);
+            s.ppc = 8;
+            Ok(())
+        }
+        8 => {
+            // 0x0020: remu a1, t1, zero
+            s.registers[11] = rubicv_emulator::aot::remu(s.registers[6], s.registers[0]);
+            s.ppc = 9;
+            Ok(())
+        }
+        9 => {
+            // 0x0024: lui a2, 0x80000
+            s.registers[12] = 0x80000000;
+            s.ppc = 10;
+            Ok(())
+        }
+        10 => {
+            // 0x0028: div a2, a2, t0
+            s.registers[12] = rubicv_emulator::aot::div(s.registers[12], s.registers[5]);
+            s.ppc = 11;
+            Ok(())
+        }
+        11 => {
+            // 0x002c: sra a2, t0, t1
+            s.registers[12] = ((s.registers[5] as i32) >> (s.registers[6] & 0x1f)) as u32;
+            s.ppc = 12;
+            Ok(())
+        }
+        12 => {
+            // 0x0030: sltu t1, t1, t0
+            s.registers[6] = (s.registers[6] < s.registers[5]) as u32;
+            s.ppc = 13;
+            Ok(())
+        }
+        13 => {
+            // 0x0034: srai t0, t0, 1
+            s.registers[5] = ((s.registers[5] as i32) >> 1) as u32;
+            s.ppc = 14;
+            Ok(())
+        }
+        14 => {
+            // 0x0038: addi a7, zero, 0
+            s.registers[17] = s.registers[0].wrapping_add(0x0);
+            s.ppc = 15;
+            Ok(())
+        }
+        15 => {
+            // 0x003c: ecall
+            s.ppc = 15;
+            Err(RubicVError::SystemCall(s.registers[17]))
+        }
+        16 => {
+            // 0x0040: invalid
+            s.ppc = 16;
+            Err(RubicVError::IllegalInstruction)
+        }
+        _ => unreachable!(),
+    }
+}
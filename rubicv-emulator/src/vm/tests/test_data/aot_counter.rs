@@ -0,0 +1,126 @@
+// Generated by `utils aot`, do not edit
+use rubicv_emulator::aot::{Meter, Program, VmState};
+use rubicv_emulator::errors::RubicVError;
+use rubicv_emulator::syscalls::SyscallHandler;
+use rubicv_emulator::vm::ExecutionResult;
+
+pub const ENTRYPOINT: usize = 0;
+
+pub static PROGRAM: Program = Program { meters: &METERS, block, step };
+
+pub fn run(state: &mut VmState, max_fuel: Option<u64>) -> ExecutionResult {
+    PROGRAM.run(state, max_fuel)
+}
+
+pub fn run_with_syscalls<S: SyscallHandler + ?Sized>(state: &mut VmState, syscalls: &mut S, max_fuel: Option<u64>) -> ExecutionResult {
+    PROGRAM.run_with_syscalls(state, syscalls, max_fuel)
+}
+
+static METERS: [Meter; 10] = [
+    Meter { cost: 1, block_cost: 2, block_len: 2 },
+    Meter { cost: 1, block_cost: 1, block_len: 1 },
+    Meter { cost: 1, block_cost: 1, block_len: 1 },
+    Meter { cost: 1, block_cost: 3, block_len: 3 },
+    Meter { cost: 1, block_cost: 2, block_len: 2 },
+    Meter { cost: 1, block_cost: 1, block_len: 1 },
+    Meter { cost: 1, block_cost: 3, block_len: 3 },
+    Meter { cost: 1, block_cost: 2, block_len: 2 },
+    Meter { cost: 1, block_cost: 1, block_len: 1 },
+    Meter { cost: 0, block_cost: 0, block_len: 1 },
+];
+
+#[allow(clippy::all)]
+fn block(s: &mut VmState, ppc: usize) -> Result<(), RubicVError> {
+    match ppc {
+        0 => {
+            // 0x0000: addi t1, zero, 5
+            s.registers[6] = s.registers[0].wrapping_add(0x5);
+            // 0x0004: addi a7, zero, 1
+            s.registers[17] = s.registers[0].wrapping_add(0x1);
+            s.ppc = 2;
+            Ok(())
+        }
+        3 => {
+            // 0x000c: add s0, s0, a0
+            s.registers[8] = s.registers[8].wrapping_add(s.registers[10]);
+            // 0x0010: addi t0, t0, 1
+            s.registers[5] = s.registers[5].wrapping_add(0x1);
+            // 0x0014: bne t0, t1, 0x8 <ppc 2>
+            s.ppc = if s.registers[5] != s.registers[6] { 2 } else { 6 };
+            Ok(())
+        }
+        6 => {
+            // 0x0018: add a0, s0, zero
+            s.registers[10] = s.registers[8].wrapping_add(s.registers[0]);
+            // 0x001c: addi a7, zero, 0
+            s.registers[17] = s.registers[0].wrapping_add(0x0);
+            // 0x0020: ecall
+            s.ppc = 8;
+            Err(RubicVError::SystemCall(s.registers[17]))
+        }
+        _ => unreachable!(),
+    }
+}
+
+#[allow(clippy::all)]
+fn step(s: &mut VmState, ppc: usize) -> Result<(), RubicVError> {
+    match ppc {
+        0 => {
+            // 0x0000: addi t1, zero, 5
+            s.registers[6] = s.registers[0].wrapping_add(0x5);
+            s.ppc = 1;
+            Ok(())
+        }
+        1 => {
+            // 0x0004: addi a7, zero, 1
+            s.registers[17] = s.registers[0].wrapping_add(0x1);
+            s.ppc = 2;
+            Ok(())
+        }
+        2 => {
+            // 0x0008: ecall
+            s.ppc = 2;
+            Err(RubicVError::SystemCall(s.registers[17]))
+        }
+        3 => {
+            // 0x000c: add s0, s0, a0
+            s.registers[8] = s.registers[8].wrapping_add(s.registers[10]);
+            s.ppc = 4;
+            Ok(())
+        }
+        4 => {
+            // 0x0010: addi t0, t0, 1
+            s.registers[5] = s.registers[5].wrapping_add(0x1);
+            s.ppc = 5;
+            Ok(())
+        }
+        5 => {
+            // 0x0014: bne t0, t1, 0x8 <ppc 2>
+            s.ppc = if s.registers[5] != s.registers[6] { 2 } else { 6 };
+            Ok(())
+        }
+        6 => {
+            // 0x0018: add a0, s0, zero
+            s.registers[10] = s.registers[8].wrapping_add(s.registers[0]);
+            s.ppc = 7;
+            Ok(())
+        }
+        7 => {
+            // 0x001c: addi a7, zero, 0
+            s.registers[17] = s.registers[0].wrapping_add(0x0);
+            s.ppc = 8;
+            Ok(())
+        }
+        8 => {
+            // 0x0020: ecall
+            s.ppc = 8;
+            Err(RubicVError::SystemCall(s.registers[17]))
+        }
+        9 => {
+            // 0x0024: invalid
+            s.ppc = 9;
+            Err(RubicVError::IllegalInstruction)
+        }
+        _ => unreachable!(),
+    }
+}
@@ -0,0 +1,240 @@
+// Generated by `utils aot`, do not edit
+use rubicv_emulator::aot::{Meter, Program, VmState};
+use rubicv_emulator::errors::RubicVError;
+use rubicv_emulator::syscalls::SyscallHandler;
+use rubicv_emulator::vm::ExecutionResult;
+
+pub const ENTRYPOINT: usize = 0;
+
+pub static PROGRAM: Program = Program { meters: &METERS, block, step };
+
+pub fn run(state: &mut VmState, max_fuel: Option<u64>) -> ExecutionResult {
+    PROGRAM.run(state, max_fuel)
+}
+
+pub fn run_with_syscalls<S: SyscallHandler + ?Sized>(state: &mut VmState, syscalls: &mut S, max_fuel: Option<u64>) -> ExecutionResult {
+    PROGRAM.run_with_syscalls(state, syscalls, max_fuel)
+}
+
+static METERS: [Meter; 21] = [
+    Meter { cost: 1, block_cost: 5, block_len: 5 },
+    Meter { cost: 1, block_cost: 4, block_len: 4 },
+    Meter { cost: 1, block_cost: 3, block_len: 3 },
+    Meter { cost: 1, block_cost: 2, block_len: 2 },
+    Meter { cost: 1, block_cost: 1, block_len: 1 },
+    Meter { cost: 1, block_cost: 6, block_len: 6 },
+    Meter { cost: 1, block_cost: 5, block_len: 5 },
+    Meter { cost: 1, block_cost: 4, block_len: 4 },
+    Meter { cost: 1, block_cost: 3, block_len: 3 },
+    Meter { cost: 1, block_cost: 2, block_len: 2 },
+    Meter { cost: 1, block_cost: 1, block_len: 1 },
+    Meter { cost: 1, block_cost: 2, block_len: 2 },
+    Meter { cost: 1, block_cost: 1, block_len: 1 },
+    Meter { cost: 1, block_cost: 3, block_len: 3 },
+    Meter { cost: 1, block_cost: 2, block_len: 2 },
+    Meter { cost: 1, block_cost: 1, block_len: 1 },
+    Meter { cost: 1, block_cost: 2, block_len: 2 },
+    Meter { cost: 1, block_cost: 1, block_len: 1 },
+    Meter { cost: 1, block_cost: 2, block_len: 2 },
+    Meter { cost: 1, block_cost: 1, block_len: 1 },
+    Meter { cost: 0, block_cost: 0, block_len: 1 },
+];
+
+#[allow(clippy::all)]
+fn block(s: &mut VmState, ppc: usize) -> Result<(), RubicVError> {
+    match ppc {
+        0 => {
+            // 0x0000: lui a0, 0x12345
+            s.registers[10] = 0x12345000;
+            // 0x0004: addi a0, a0, 1656
+            s.registers[10] = s.registers[10].wrapping_add(0x678);
+            // 0x0008: addi a2, zero, 0
+            s.registers[12] = s.registers[0].wrapping_add(0x0);
+            // 0x000c: addi a1, zero, 10
+            s.registers[11] = s.registers[0].wrapping_add(0xa);
+            // 0x0010: lui t0, 0x2
+            s.registers[5] = 0x2000;
+            s.ppc = 5;
+            Ok(())
+        }
+        5 => {
+            // 0x0014: slli t1, a2, 2
+            s.registers[6] = s.registers[12] << 2;
+            // 0x0018: add a3, t1, a3
+            s.registers[13] = s.registers[6].wrapping_add(s.registers[13]);
+            // 0x001c: add t1, t0, a2
+            s.registers[6] = s.registers[5].wrapping_add(s.registers[12]);
+            // 0x0020: sb a3, 0(t1)
+            s.store(s.registers[6].wrapping_add(0x0), 1, s.registers[13]);
+            // 0x0024: addi a2, a2, 1
+            s.registers[12] = s.registers[12].wrapping_add(0x1);
+            // 0x0028: bne a2, a1, 0x14 <ppc 5>
+            s.ppc = if s.registers[12] != s.registers[11] { 5 } else { 11 };
+            Ok(())
+        }
+        11 => {
+            // 0x002c: auipc ra, 0x0
+            s.registers[1] = 0x2c;
+            // 0x0030: jalr ra, 20(ra)
+            let target = s.registers[1].wrapping_add(0x14) & !1;
+            s.registers[1] = 0x34;
+            s.ppc = ((target / 4) as usize).min(20);
+            Ok(())
+        }
+        13 => {
+            // 0x0034: lw t1, 4(t0)
+            s.registers[6] = s.load(s.registers[5].wrapping_add(0x4), 4);
+            // 0x0038: lb a1, 9(t0)
+            s.registers[11] = s.load(s.registers[5].wrapping_add(0x9), 1) as u8 as i8 as u32;
+            // 0x003c: jal zero, 0x48 <ppc 18>
+            s.ppc = 18;
+            Ok(())
+        }
+        16 => {
+            // 0x0040: add a0, a0, a3
+            s.registers[10] = s.registers[10].wrapping_add(s.registers[13]);
+            // 0x0044: jalr zero, 0(ra)
+            let target = s.registers[1].wrapping_add(0x0) & !1;
+            s.ppc = ((target / 4) as usize).min(20);
+            Ok(())
+        }
+        18 => {
+            // 0x0048: addi a7, zero, 0
+            s.registers[17] = s.registers[0].wrapping_add(0x0);
+            // 0x004c: ecall
+            s.ppc = 19;
+            Err(RubicVError::SystemCall(s.registers[17]))
+        }
+        _ => unreachable!(),
+    }
+}
+
+#[allow(clippy::all)]
+fn step(s: &mut VmState, ppc: usize) -> Result<(), RubicVError> {
+    match ppc {
+        0 => {
+            // 0x0000: lui a0, 0x12345
+            s.registers[10] = 0x12345000;
+            s.ppc = 1;
+            Ok(())
+        }
+        1 => {
+            // 0x0004: addi a0, a0, 1656
+            s.registers[10] = s.registers[10].wrapping_add(0x678);
+            s.ppc = 2;
+            Ok(())
+        }
+        2 => {
+            // 0x0008: addi a2, zero, 0
+            s.registers[12] = s.registers[0].wrapping_add(0x0);
+            s.ppc = 3;
+            Ok(())
+        }
+        3 => {
+            // 0x000c: addi a1, zero, 10
+            s.registers[11] = s.registers[0].wrapping_add(0xa);
+            s.ppc = 4;
+            Ok(())
+        }
+        4 => {
+            // 0x0010: lui t0, 0x2
+            s.registers[5] = 0x2000;
+            s.ppc = 5;
+            Ok(())
+        }
+        5 => {
+            // 0x0014: slli t1, a2, 2
+            s.registers[6] = s.registers[12] << 2;
+            s.ppc = 6;
+            Ok(())
+        }
+        6 => {
+            // 0x0018: add a3, t1, a3
+            s.registers[13] = s.registers[6].wrapping_add(s.registers[13]);
+            s.ppc = 7;
+            Ok(())
+        }
+        7 => {
+            // 0x001c: add t1, t0, a2
+            s.registers[6] = s.registers[5].wrapping_add(s.registers[12]);
+            s.ppc = 8;
+            Ok(())
+        }
+        8 => {
+            // 0x0020: sb a3, 0(t1)
+            s.store(s.registers[6].wrapping_add(0x0), 1, s.registers[13]);
+            s.ppc = 9;
+            Ok(())
+        }
+        9 => {
+            // 0x0024: addi a2, a2, 1
+            s.registers[12] = s.registers[12].wrapping_add(0x1);
+            s.ppc = 10;
+            Ok(())
+        }
+        10 => {
+            // 0x0028: bne a2, a1, 0x14 <ppc 5>
+            s.ppc = if s.registers[12] != s.registers[11] { 5 } else { 11 };
+            Ok(())
+        }
+        11 => {
+            // 0x002c: auipc ra, 0x0
+            s.registers[1] = 0x2c;
+            s.ppc = 12;
+            Ok(())
+        }
+        12 => {
+            // 0x0030: jalr ra, 20(ra)
+            let target = s.registers[1].wrapping_add(0x14) & !1;
+            s.registers[1] = 0x34;
+            s.ppc = ((target / 4) as usize).min(20);
+            Ok(())
+        }
+        13 => {
+            // 0x0034: lw t1, 4(t0)
+            s.registers[6] = s.load(s.registers[5].wrapping_add(0x4), 4);
+            s.ppc = 14;
+            Ok(())
+        }
+        14 => {
+            // 0x0038: lb a1, 9(t0)
+            s.registers[11] = s.load(s.registers[5].wrapping_add(0x9), 1) as u8 as i8 as u32;
+            s.ppc = 15;
+            Ok(())
+        }
+        15 => {
+            // 0x003c: jal zero, 0x48 <ppc 18>
+            s.ppc = 18;
+            Ok(())
+        }
+        16 => {
+            // 0x0040: add a0, a0, a3
+            s.registers[10] = s.registers[10].wrapping_add(s.registers[13]);
+            s.ppc = 17;
+            Ok(())
+        }
+        17 => {
+            // 0x0044: jalr zero, 0(ra)
+            let target = s.registers[1].wrapping_add(0x0) & !1;
+            s.ppc = ((target / 4) as usize).min(20);
+            Ok(())
+        }
+        18 => {
+            // 0x0048: addi a7, zero, 0
+            s.registers[17] = s.registers[0].wrapping_add(0x0);
+            s.ppc = 19;
+            Ok(())
+        }
+        19 => {
+            // 0x004c: ecall
+            s.ppc = 19;
+            Err(RubicVError::SystemCall(s.registers[17]))
+        }
+        20 => {
+            // 0x0050: invalid
+            s.ppc = 20;
+            Err(RubicVError::IllegalInstruction)
+        }
+        _ => unreachable!(),
+    }
+}
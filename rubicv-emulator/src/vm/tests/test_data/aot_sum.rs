@@ -0,0 +1,690 @@
+// Generated by `utils aot`, do not edit
+use rubicv_emulator::aot::{Meter, Program, VmState};
+use rubicv_emulator::errors::RubicVError;
+use rubicv_emulator::syscalls::SyscallHandler;
+use rubicv_emulator::vm::ExecutionResult;
+
+pub const ENTRYPOINT: usize = 17;
+
+pub static PROGRAM: Program = Program { meters: &METERS, block, step };
+
+pub fn run(state: &mut VmState, max_fuel: Option<u64>) -> ExecutionResult {
+    PROGRAM.run(state, max_fuel)
+}
+
+pub fn run_with_syscalls<S: SyscallHandler + ?Sized>(state: &mut VmState, syscalls: &mut S, max_fuel: Option<u64>) -> ExecutionResult {
+    PROGRAM.run_with_syscalls(state, syscalls, max_fuel)
+}
+
+static METERS: [Meter; 75] = [
+    Meter { cost: 1, block_cost: 3, block_len: 3 },
+    Meter { cost: 1, block_cost: 2, block_len: 2 },
+    Meter { cost: 1, block_cost: 1, block_len: 1 },
+    Meter { cost: 1, block_cost: 5, block_len: 5 },
+    Meter { cost: 1, block_cost: 4, block_len: 4 },
+    Meter { cost: 1, block_cost: 3, block_len: 3 },
+    Meter { cost: 1, block_cost: 2, block_len: 2 },
+    Meter { cost: 1, block_cost: 1, block_len: 1 },
+    Meter { cost: 1, block_cost: 4, block_len: 4 },
+    Meter { cost: 1, block_cost: 3, block_len: 3 },
+    Meter { cost: 1, block_cost: 2, block_len: 2 },
+    Meter { cost: 1, block_cost: 1, block_len: 1 },
+    Meter { cost: 1, block_cost: 4, block_len: 4 },
+    Meter { cost: 1, block_cost: 3, block_len: 3 },
+    Meter { cost: 1, block_cost: 2, block_len: 2 },
+    Meter { cost: 1, block_cost: 1, block_len: 1 },
+    Meter { cost: 1, block_cost: 1, block_len: 1 },
+    Meter { cost: 1, block_cost: 1, block_len: 1 },
+    Meter { cost: 1, block_cost: 5, block_len: 5 },
+    Meter { cost: 1, block_cost: 4, block_len: 4 },
+    Meter { cost: 1, block_cost: 3, block_len: 3 },
+    Meter { cost: 1, block_cost: 2, block_len: 2 },
+    Meter { cost: 1, block_cost: 1, block_len: 1 },
+    Meter { cost: 1, block_cost: 3, block_len: 3 },
+    Meter { cost: 1, block_cost: 2, block_len: 2 },
+    Meter { cost: 1, block_cost: 1, block_len: 1 },
+    Meter { cost: 0, block_cost: 0, block_len: 1 },
+    Meter { cost: 1, block_cost: 6, block_len: 6 },
+    Meter { cost: 1, block_cost: 5, block_len: 5 },
+    Meter { cost: 1, block_cost: 4, block_len: 4 },
+    Meter { cost: 1, block_cost: 3, block_len: 3 },
+    Meter { cost: 1, block_cost: 2, block_len: 2 },
+    Meter { cost: 1, block_cost: 1, block_len: 1 },
+    Meter { cost: 1, block_cost: 7, block_len: 7 },
+    Meter { cost: 1, block_cost: 6, block_len: 6 },
+    Meter { cost: 1, block_cost: 5, block_len: 5 },
+    Meter { cost: 1, block_cost: 4, block_len: 4 },
+    Meter { cost: 1, block_cost: 3, block_len: 3 },
+    Meter { cost: 1, block_cost: 2, block_len: 2 },
+    Meter { cost: 1, block_cost: 1, block_len: 1 },
+    Meter { cost: 0, block_cost: 0, block_len: 1 },
+    Meter { cost: 1, block_cost: 2, block_len: 3 },
+    Meter { cost: 1, block_cost: 1, block_len: 2 },
+    Meter { cost: 0, block_cost: 0, block_len: 1 },
+    Meter { cost: 0, block_cost: 0, block_len: 1 },
+    Meter { cost: 0, block_cost: 0, block_len: 1 },
+    Meter { cost: 1, block_cost: 1, block_len: 2 },
+    Meter { cost: 0, block_cost: 0, block_len: 1 },
+    Meter { cost: 0, block_cost: 0, block_len: 1 },
+    Meter { cost: 0, block_cost: 0, block_len: 1 },
+    Meter { cost: 1, block_cost: 2, block_len: 3 },
+    Meter { cost: 1, block_cost: 1, block_len: 2 },
+    Meter { cost: 0, block_cost: 0, block_len: 1 },
+    Meter { cost: 0, block_cost: 0, block_len: 1 },
+    Meter { cost: 0, block_cost: 0, block_len: 1 },
+    Meter { cost: 1, block_cost: 1, block_len: 2 },
+    Meter { cost: 0, block_cost: 0, block_len: 1 },
+    Meter { cost: 0, block_cost: 0, block_len: 1 },
+    Meter { cost: 0, block_cost: 0, block_len: 1 },
+    Meter { cost: 0, block_cost: 0, block_len: 1 },
+    Meter { cost: 0, block_cost: 0, block_len: 1 },
+    Meter { cost: 1, block_cost: 1, block_len: 2 },
+    Meter { cost: 0, block_cost: 0, block_len: 1 },
+    Meter { cost: 1, block_cost: 2, block_len: 3 },
+    Meter { cost: 1, block_cost: 1, block_len: 2 },
+    Meter { cost: 0, block_cost: 0, block_len: 1 },
+    Meter { cost: 1, block_cost: 1, block_len: 1 },
+    Meter { cost: 0, block_cost: 0, block_len: 1 },
+    Meter { cost: 1, block_cost: 2, block_len: 3 },
+    Meter { cost: 1, block_cost: 1, block_len: 2 },
+    Meter { cost: 0, block_cost: 0, block_len: 1 },
+    Meter { cost: 1, block_cost: 3, block_len: 3 },
+    Meter { cost: 1, block_cost: 2, block_len: 2 },
+    Meter { cost: 1, block_cost: 1, block_len: 1 },
+    Meter { cost: 0, block_cost: 0, block_len: 1 },
+];
+
+#[allow(clippy::all)]
+fn block(s: &mut VmState, ppc: usize) -> Result<(), RubicVError> {
+    match ppc {
+        0 => {
+            // 0x0000: probe 0(zero), 1
+            // 0x0004: addi sp, sp, -16
+            s.registers[2] = s.registers[2].wrapping_add(0xfffffff0);
+            // 0x0008: beq a0, zero, 0x40 <ppc 16>
+            s.ppc = if s.registers[10] == s.registers[0] { 16 } else { 3 };
+            Ok(())
+        }
+        3 => {
+            // 0x000c: lui a0, 0x10
+            s.registers[10] = 0x10000;
+            // 0x0010: lw a0, 0(a0)
+            s.registers[10] = s.load(s.registers[10].wrapping_add(0x0), 4);
+            // 0x0014: addi a1, zero, 0
+            s.registers[11] = s.registers[0].wrapping_add(0x0);
+            // 0x0018: addi a2, zero, 0
+            s.registers[12] = s.registers[0].wrapping_add(0x0);
+            // 0x001c: beq a0, zero, 0x30 <ppc 12>
+            s.ppc = if s.registers[10] == s.registers[0] { 12 } else { 8 };
+            Ok(())
+        }
+        8 => {
+            // 0x0020: add a1, a1, a2
+            s.registers[11] = s.registers[11].wrapping_add(s.registers[12]);
+            // 0x0024: addi a2, a2, 1
+            s.registers[12] = s.registers[12].wrapping_add(0x1);
+            // 0x0028: sw a1, 12(sp)
+            s.store(s.registers[2].wrapping_add(0xc), 4, s.registers[11]);
+            // 0x002c: bne a0, a2, 0x20 <ppc 8>
+            s.ppc = if s.registers[10] != s.registers[12] { 8 } else { 12 };
+            Ok(())
+        }
+        12 => {
+            // 0x0030: lui a0, 0x2
+            s.registers[10] = 0x2000;
+            // 0x0034: sw a1, 0(a0)
+            s.store(s.registers[10].wrapping_add(0x0), 4, s.registers[11]);
+            // 0x0038: addi sp, sp, 16
+            s.registers[2] = s.registers[2].wrapping_add(0x10);
+            // 0x003c: jalr zero, 0(ra)
+            let target = s.registers[1].wrapping_add(0x0) & !1;
+            s.ppc = ((target / 4) as usize).min(74);
+            Ok(())
+        }
+        18 => {
+            // 0x0048: addi sp, sp, -16
+            s.registers[2] = s.registers[2].wrapping_add(0xfffffff0);
+            // 0x004c: sw ra, 12(sp)
+            s.store(s.registers[2].wrapping_add(0xc), 4, s.registers[1]);
+            // 0x0050: addi a0, a0, 0
+            s.registers[10] = s.registers[10].wrapping_add(0x0);
+            // 0x0054: auipc ra, 0x0
+            s.registers[1] = 0x54;
+            // 0x0058: jalr ra, -80(ra)
+            let target = s.registers[1].wrapping_add(0xffffffb0) & !1;
+            s.registers[1] = 0x5c;
+            s.ppc = ((target / 4) as usize).min(74);
+            Ok(())
+        }
+        23 => {
+            // 0x005c: addi a0, zero, 0
+            s.registers[10] = s.registers[0].wrapping_add(0x0);
+            // 0x0060: addi a1, a0, 0
+            s.registers[11] = s.registers[10].wrapping_add(0x0);
+            // 0x0064: ecall
+            s.ppc = 25;
+            Err(RubicVError::SystemCall(s.registers[17]))
+        }
+        27 => {
+            // 0x006c: addi sp, sp, -16
+            s.registers[2] = s.registers[2].wrapping_add(0xfffffff0);
+            // 0x0070: sw ra, 12(sp)
+            s.store(s.registers[2].wrapping_add(0xc), 4, s.registers[1]);
+            // 0x0074: sw s0, 8(sp)
+            s.store(s.registers[2].wrapping_add(0x8), 4, s.registers[8]);
+            // 0x0078: addi s0, sp, 16
+            s.registers[8] = s.registers[2].wrapping_add(0x10);
+            // 0x007c: auipc ra, 0x0
+            s.registers[1] = 0x7c;
+            // 0x0080: jalr ra, 8(ra)
+            let target = s.registers[1].wrapping_add(0x8) & !1;
+            s.registers[1] = 0x84;
+            s.ppc = ((target / 4) as usize).min(74);
+            Ok(())
+        }
+        33 => {
+            // 0x0084: addi sp, sp, -16
+            s.registers[2] = s.registers[2].wrapping_add(0xfffffff0);
+            // 0x0088: sw ra, 12(sp)
+            s.store(s.registers[2].wrapping_add(0xc), 4, s.registers[1]);
+            // 0x008c: sw s0, 8(sp)
+            s.store(s.registers[2].wrapping_add(0x8), 4, s.registers[8]);
+            // 0x0090: addi s0, sp, 16
+            s.registers[8] = s.registers[2].wrapping_add(0x10);
+            // 0x0094: addi a0, zero, 1
+            s.registers[10] = s.registers[0].wrapping_add(0x1);
+            // 0x0098: addi a1, a0, 0
+            s.registers[11] = s.registers[10].wrapping_add(0x0);
+            // 0x009c: ecall
+            s.ppc = 39;
+            Err(RubicVError::SystemCall(s.registers[17]))
+        }
+        41 => {
+            // 0x00a4: addi zero, zero, 0
+            // 0x00a8: probe 0(zero), 1
+            // 0x00ac: invalid
+            s.ppc = 43;
+            Err(RubicVError::IllegalInstruction)
+        }
+        46 => {
+            // 0x00b8: addi zero, zero, 0
+            // 0x00bc: invalid
+            s.ppc = 47;
+            Err(RubicVError::IllegalInstruction)
+        }
+        50 => {
+            // 0x00c8: lbu s0, 256(t3)
+            s.registers[8] = s.load(s.registers[28].wrapping_add(0x100), 1);
+            // 0x00cc: addi zero, zero, 0
+            // 0x00d0: invalid
+            s.ppc = 52;
+            Err(RubicVError::IllegalInstruction)
+        }
+        55 => {
+            // 0x00dc: lbu s0, 256(t3)
+            s.registers[8] = s.load(s.registers[28].wrapping_add(0x100), 1);
+            // 0x00e0: invalid
+            s.ppc = 56;
+            Err(RubicVError::IllegalInstruction)
+        }
+        61 => {
+            // 0x00f4: lbu s0, 256(t3)
+            s.registers[8] = s.load(s.registers[28].wrapping_add(0x100), 1);
+            // 0x00f8: invalid
+            s.ppc = 62;
+            Err(RubicVError::IllegalInstruction)
+        }
+        63 => {
+            // 0x00fc: lbu s0, 128(s8)
+            s.registers[8] = s.load(s.registers[24].wrapping_add(0x80), 1);
+            // 0x0100: probe 0(zero), 1
+            // 0x0104: invalid
+            s.ppc = 65;
+            Err(RubicVError::IllegalInstruction)
+        }
+        68 => {
+            // 0x0110: sb zero, 0(zero)
+            s.store(s.registers[0].wrapping_add(0x0), 1, s.registers[0]);
+            // 0x0114: lbu s0, 256(t3)
+            s.registers[8] = s.load(s.registers[28].wrapping_add(0x100), 1);
+            // 0x0118: invalid
+            s.ppc = 70;
+            Err(RubicVError::IllegalInstruction)
+        }
+        71 => {
+            // 0x011c: lbu s0, 128(s8)
+            s.registers[8] = s.load(s.registers[24].wrapping_add(0x80), 1);
+            // 0x0120: probe 0(zero), 1
+            // 0x0124: probe 0(zero), 1
+            s.ppc = 74;
+            Ok(())
+        }
+        _ => unreachable!(),
+    }
+}
+
+#[allow(clippy::all)]
+fn step(s: &mut VmState, ppc: usize) -> Result<(), RubicVError> {
+    match ppc {
+        0 => {
+            // 0x0000: probe 0(zero), 1
+            s.ppc = 1;
+            Ok(())
+        }
+        1 => {
+            // 0x0004: addi sp, sp, -16
+            s.registers[2] = s.registers[2].wrapping_add(0xfffffff0);
+            s.ppc = 2;
+            Ok(())
+        }
+        2 => {
+            // 0x0008: beq a0, zero, 0x40 <ppc 16>
+            s.ppc = if s.registers[10] == s.registers[0] { 16 } else { 3 };
+            Ok(())
+        }
+        3 => {
+            // 0x000c: lui a0, 0x10
+            s.registers[10] = 0x10000;
+            s.ppc = 4;
+            Ok(())
+        }
+        4 => {
+            // 0x0010: lw a0, 0(a0)
+            s.registers[10] = s.load(s.registers[10].wrapping_add(0x0), 4);
+            s.ppc = 5;
+            Ok(())
+        }
+        5 => {
+            // 0x0014: addi a1, zero, 0
+            s.registers[11] = s.registers[0].wrapping_add(0x0);
+            s.ppc = 6;
+            Ok(())
+        }
+        6 => {
+            // 0x0018: addi a2, zero, 0
+            s.registers[12] = s.registers[0].wrapping_add(0x0);
+            s.ppc = 7;
+            Ok(())
+        }
+        7 => {
+            // 0x001c: beq a0, zero, 0x30 <ppc 12>
+            s.ppc = if s.registers[10] == s.registers[0] { 12 } else { 8 };
+            Ok(())
+        }
+        8 => {
+            // 0x0020: add a1, a1, a2
+            s.registers[11] = s.registers[11].wrapping_add(s.registers[12]);
+            s.ppc = 9;
+            Ok(())
+        }
+        9 => {
+            // 0x0024: addi a2, a2, 1
+            s.registers[12] = s.registers[12].wrapping_add(0x1);
+            s.ppc = 10;
+            Ok(())
+        }
+        10 => {
+            // 0x0028: sw a1, 12(sp)
+            s.store(s.registers[2].wrapping_add(0xc), 4, s.registers[11]);
+            s.ppc = 11;
+            Ok(())
+        }
+        11 => {
+            // 0x002c: bne a0, a2, 0x20 <ppc 8>
+            s.ppc = if s.registers[10] != s.registers[12] { 8 } else { 12 };
+            Ok(())
+        }
+        12 => {
+            // 0x0030: lui a0, 0x2
+            s.registers[10] = 0x2000;
+            s.ppc = 13;
+            Ok(())
+        }
+        13 => {
+            // 0x0034: sw a1, 0(a0)
+            s.store(s.registers[10].wrapping_add(0x0), 4, s.registers[11]);
+            s.ppc = 14;
+            Ok(())
+        }
+        14 => {
+            // 0x0038: addi sp, sp, 16
+            s.registers[2] = s.registers[2].wrapping_add(0x10);
+            s.ppc = 15;
+            Ok(())
+        }
+        15 => {
+            // 0x003c: jalr zero, 0(ra)
+            let target = s.registers[1].wrapping_add(0x0) & !1;
+            s.ppc = ((target / 4) as usize).min(74);
+            Ok(())
+        }
+        16 => {
+            // 0x0040: auipc ra, 0x0
+            s.registers[1] = 0x40;
+            s.ppc = 17;
+            Ok(())
+        }
+        17 => {
+            // 0x0044: jalr ra, 44(ra)
+            let target = s.registers[1].wrapping_add(0x2c) & !1;
+            s.registers[1] = 0x48;
+            s.ppc = ((target / 4) as usize).min(74);
+            Ok(())
+        }
+        18 => {
+            // 0x0048: addi sp, sp, -16
+            s.registers[2] = s.registers[2].wrapping_add(0xfffffff0);
+            s.ppc = 19;
+            Ok(())
+        }
+        19 => {
+            // 0x004c: sw ra, 12(sp)
+            s.store(s.registers[2].wrapping_add(0xc), 4, s.registers[1]);
+            s.ppc = 20;
+            Ok(())
+        }
+        20 => {
+            // 0x0050: addi a0, a0, 0
+            s.registers[10] = s.registers[10].wrapping_add(0x0);
+            s.ppc = 21;
+            Ok(())
+        }
+        21 => {
+            // 0x0054: auipc ra, 0x0
+            s.registers[1] = 0x54;
+            s.ppc = 22;
+            Ok(())
+        }
+        22 => {
+            // 0x0058: jalr ra, -80(ra)
+            let target = s.registers[1].wrapping_add(0xffffffb0) & !1;
+            s.registers[1] = 0x5c;
+            s.ppc = ((target / 4) as usize).min(74);
+            Ok(())
+        }
+        23 => {
+            // 0x005c: addi a0, zero, 0
+            s.registers[10] = s.registers[0].wrapping_add(0x0);
+            s.ppc = 24;
+            Ok(())
+        }
+        24 => {
+            // 0x0060: addi a1, a0, 0
+            s.registers[11] = s.registers[10].wrapping_add(0x0);
+            s.ppc = 25;
+            Ok(())
+        }
+        25 => {
+            // 0x0064: ecall
+            s.ppc = 25;
+            Err(RubicVError::SystemCall(s.registers[17]))
+        }
+        26 => {
+            // 0x0068: invalid
+            s.ppc = 26;
+            Err(RubicVError::IllegalInstruction)
+        }
+        27 => {
+            // 0x006c: addi sp, sp, -16
+            s.registers[2] = s.registers[2].wrapping_add(0xfffffff0);
+            s.ppc = 28;
+            Ok(())
+        }
+        28 => {
+            // 0x0070: sw ra, 12(sp)
+            s.store(s.registers[2].wrapping_add(0xc), 4, s.registers[1]);
+            s.ppc = 29;
+            Ok(())
+        }
+        29 => {
+            // 0x0074: sw s0, 8(sp)
+            s.store(s.registers[2].wrapping_add(0x8), 4, s.registers[8]);
+            s.ppc = 30;
+            Ok(())
+        }
+        30 => {
+            // 0x0078: addi s0, sp, 16
+            s.registers[8] = s.registers[2].wrapping_add(0x10);
+            s.ppc = 31;
+            Ok(())
+        }
+        31 => {
+            // 0x007c: auipc ra, 0x0
+            s.registers[1] = 0x7c;
+            s.ppc = 32;
+            Ok(())
+        }
+        32 => {
+            // 0x0080: jalr ra, 8(ra)
+            let target = s.registers[1].wrapping_add(0x8) & !1;
+            s.registers[1] = 0x84;
+            s.ppc = ((target / 4) as usize).min(74);
+            Ok(())
+        }
+        33 => {
+            // 0x0084: addi sp, sp, -16
+            s.registers[2] = s.registers[2].wrapping_add(0xfffffff0);
+            s.ppc = 34;
+            Ok(())
+        }
+        34 => {
+            // 0x0088: sw ra, 12(sp)
+            s.store(s.registers[2].wrapping_add(0xc), 4, s.registers[1]);
+            s.ppc = 35;
+            Ok(())
+        }
+        35 => {
+            // 0x008c: sw s0, 8(sp)
+            s.store(s.registers[2].wrapping_add(0x8), 4, s.registers[8]);
+            s.ppc = 36;
+            Ok(())
+        }
+        36 => {
+            // 0x0090: addi s0, sp, 16
+            s.registers[8] = s.registers[2].wrapping_add(0x10);
+            s.ppc = 37;
+            Ok(())
+        }
+        37 => {
+            // 0x0094: addi a0, zero, 1
+            s.registers[10] = s.registers[0].wrapping_add(0x1);
+            s.ppc = 38;
+            Ok(())
+        }
+        38 => {
+            // 0x0098: addi a1, a0, 0
+            s.registers[11] = s.registers[10].wrapping_add(0x0);
+            s.ppc = 39;
+            Ok(())
+        }
+        39 => {
+            // 0x009c: ecall
+            s.ppc = 39;
+            Err(RubicVError::SystemCall(s.registers[17]))
+        }
+        40 => {
+            // 0x00a0: invalid
+            s.ppc = 40;
+            Err(RubicVError::IllegalInstruction)
+        }
+        41 => {
+            // 0x00a4: addi zero, zero, 0
+            s.ppc = 42;
+            Ok(())
+        }
+        42 => {
+            // 0x00a8: probe 0(zero), 1
+            s.ppc = 43;
+            Ok(())
+        }
+        43 => {
+            // 0x00ac: invalid
+            s.ppc = 43;
+            Err(RubicVError::IllegalInstruction)
+        }
+        44 => {
+            // 0x00b0: invalid
+            s.ppc = 44;
+            Err(RubicVError::IllegalInstruction)
+        }
+        45 => {
+            // 0x00b4: invalid
+            s.ppc = 45;
+            Err(RubicVError::IllegalInstruction)
+        }
+        46 => {
+            // 0x00b8: addi zero, zero, 0
+            s.ppc = 47;
+            Ok(())
+        }
+        47 => {
+            // 0x00bc: invalid
+            s.ppc = 47;
+            Err(RubicVError::IllegalInstruction)
+        }
+        48 => {
+            // 0x00c0: invalid
+            s.ppc = 48;
+            Err(RubicVError::IllegalInstruction)
+        }
+        49 => {
+            // 0x00c4: invalid
+            s.ppc = 49;
+            Err(RubicVError::IllegalInstruction)
+        }
+        50 => {
+            // 0x00c8: lbu s0, 256(t3)
+            s.registers[8] = s.load(s.registers[28].wrapping_add(0x100), 1);
+            s.ppc = 51;
+            Ok(())
+        }
+        51 => {
+            // 0x00cc: addi zero, zero, 0
+            s.ppc = 52;
+            Ok(())
+        }
+        52 => {
+            // 0x00d0: invalid
+            s.ppc = 52;
+            Err(RubicVError::IllegalInstruction)
+        }
+        53 => {
+            // 0x00d4: invalid
+            s.ppc = 53;
+            Err(RubicVError::IllegalInstruction)
+        }
+        54 => {
+            // 0x00d8: invalid
+            s.ppc = 54;
+            Err(RubicVError::IllegalInstruction)
+        }
+        55 => {
+            // 0x00dc: lbu s0, 256(t3)
+            s.registers[8] = s.load(s.registers[28].wrapping_add(0x100), 1);
+            s.ppc = 56;
+            Ok(())
+        }
+        56 => {
+            // 0x00e0: invalid
+            s.ppc = 56;
+            Err(RubicVError::IllegalInstruction)
+        }
+        57 => {
+            // 0x00e4: invalid
+            s.ppc = 57;
+            Err(RubicVError::IllegalInstruction)
+        }
+        58 => {
+            // 0x00e8: invalid
+            s.ppc = 58;
+            Err(RubicVError::IllegalInstruction)
+        }
+        59 => {
+            // 0x00ec: invalid
+            s.ppc = 59;
+            Err(RubicVError::IllegalInstruction)
+        }
+        60 => {
+            // 0x00f0: invalid
+            s.ppc = 60;
+            Err(RubicVError::IllegalInstruction)
+        }
+        61 => {
+            // 0x00f4: lbu s0, 256(t3)
+            s.registers[8] = s.load(s.registers[28].wrapping_add(0x100), 1);
+            s.ppc = 62;
+            Ok(())
+        }
+        62 => {
+            // 0x00f8: invalid
+            s.ppc = 62;
+            Err(RubicVError::IllegalInstruction)
+        }
+        63 => {
+            // 0x00fc: lbu s0, 128(s8)
+            s.registers[8] = s.load(s.registers[24].wrapping_add(0x80), 1);
+            s.ppc = 64;
+            Ok(())
+        }
+        64 => {
+            // 0x0100: probe 0(zero), 1
+            s.ppc = 65;
+            Ok(())
+        }
+        65 => {
+            // 0x0104: invalid
+            s.ppc = 65;
+            Err(RubicVError::IllegalInstruction)
+        }
+        66 => {
+            // 0x0108: jalr zero, 0(zero)
+            let target = s.registers[0].wrapping_add(0x0) & !1;
+            s.ppc = ((target / 4) as usize).min(74);
+            Ok(())
+        }
+        67 => {
+            // 0x010c: invalid
+            s.ppc = 67;
+            Err(RubicVError::IllegalInstruction)
+        }
+        68 => {
+            // 0x0110: sb zero, 0(zero)
+            s.store(s.registers[0].wrapping_add(0x0), 1, s.registers[0]);
+            s.ppc = 69;
+            Ok(())
+        }
+        69 => {
+            // 0x0114: lbu s0, 256(t3)
+            s.registers[8] = s.load(s.registers[28].wrapping_add(0x100), 1);
+            s.ppc = 70;
+            Ok(())
+        }
+        70 => {
+            // 0x0118: invalid
+            s.ppc = 70;
+            Err(RubicVError::IllegalInstruction)
+        }
+        71 => {
+            // 0x011c: lbu s0, 128(s8)
+            s.registers[8] = s.load(s.registers[24].wrapping_add(0x80), 1);
+            s.ppc = 72;
+            Ok(())
+        }
+        72 => {
+            // 0x0120: probe 0(zero), 1
+            s.ppc = 73;
+            Ok(())
+        }
+        73 => {
+            // 0x0124: probe 0(zero), 1
+            s.ppc = 74;
+            Ok(())
+        }
+        74 => {
+            // 0x0128: invalid
+            s.ppc = 74;
+            Err(RubicVError::IllegalInstruction)
+        }
+        _ => unreachable!(),
+    }
+}
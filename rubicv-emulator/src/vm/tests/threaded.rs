@@ -0,0 +1,91 @@
+use crate::syscalls::*;
+use crate::watch::*;
+use super::*;
+
+// Runs `program` through resume() and through its threaded code on fresh
+// memory, checking both stop the same way. Returns the result and the fuel
+// the interpreter used.
+fn assert_threaded_matches(program: &PredecodedProgram, max_fuel: Option<u64>) -> (ExecutionResult, u64) {
+    let code = ThreadedCode::new(&program.instructions);
+    let (mut interpreted_memory, mut threaded_memory) = (setup_memory(), setup_memory());
+    let mut interpreted = setup_program_vm(&mut interpreted_memory, program);
+    let mut threaded = setup_program_vm(&mut threaded_memory, program);
+    let result = interpreted.run(max_fuel);
+    assert_eq!(threaded.run_threaded(&code, &mut NoSyscalls, max_fuel), result);
+    assert_eq!(threaded.registers, interpreted.registers);
+    assert_eq!((threaded.get_ppc(), threaded.cycle_count, threaded.fuel_used()), (interpreted.get_ppc(), interpreted.cycle_count, interpreted.fuel_used()));
+    assert_eq!(threaded_memory.memory_slab[..RO_START as usize], interpreted_memory.memory_slab[..RO_START as usize]);
+    (result, interpreted.fuel_used())
+}
+
+#[test]
+fn test_sum_program_threaded() {
+    let program = sum_program();
+    let code = ThreadedCode::new(&program.instructions);
+    let mut memory = setup_memory();
+    write_args(&[7])(&mut memory.memory_slab);
+    let mut vm = setup_program_vm(&mut memory, &program);
+    assert_eq!(vm.run_threaded(&code, &mut NoSyscalls, Some(100)), ExecutionResult::Success(0));
+    assert_eq!(vm.read_u32(SCRATCH_START), 21);
+}
+
+#[test]
+fn test_threaded_compute_matches_interpreter() {
+    let program = compute_program();
+    assert_threaded_matches(&program, None);
+
+    let mut memory = setup_memory();
+    let mut vm = setup_program_vm(&mut memory, &program);
+    vm.run_threaded(&ThreadedCode::new(&program.instructions), &mut NoSyscalls, None);
+    // -3 * 7 = -21: the high word is -1 signed or mixed, 6 unsigned
+    assert_eq!(vm.registers[A0 as usize], u32::MAX);
+    assert_eq!(vm.registers[A3 as usize], 4);
+}
+
+#[test]
+fn test_threaded_runs_match_interpreter_for_any_fuel() {
+    let program = loop_program();
+    let result = assert_matches_for_any_fuel(|max_fuel| assert_threaded_matches(&program, max_fuel));
+    assert_eq!(result, ExecutionResult::Success(0x12345678 + 180));
+}
+
+#[test]
+fn test_threaded_traps_mid_block() {
+    // Everything after the ebreak was charged with its block and is handed back
+    let program = setup_program(&[
+        li(A0, 1),
+        EBREAK,
+        li(A0, 2),
+        li(A7, SYS_EXIT as i32),
+        ECALL,
+    ]);
+    assert_eq!(assert_threaded_matches(&program, None).0, ExecutionResult::Breakpoint);
+
+    // A wild jalr lands on the sentinel
+    let program = setup_program(&[
+        encode(InsnKind::LUI, T0, 0, 0, 0x7000),
+        encode(InsnKind::JALR, 0, T0, 0, 0),
+    ]);
+    assert_threaded_matches(&program, None);
+}
+
+#[test]
+fn test_threaded_watchpoints() {
+    let program = setup_program(&[
+        encode(InsnKind::LUI, T0, 0, 0, SCRATCH_START as i32),
+        li(T1, 0x55),
+        encode(InsnKind::SW, 0, T0, T1, 8),
+        encode(InsnKind::LW, A0, T0, 0, 8),
+        li(A7, SYS_EXIT as i32),
+        ECALL,
+    ]);
+    let code = ThreadedCode::<Watchpoints>::new(&program.instructions);
+    let mut memory = setup_memory();
+    let mut vm = VM::<Watchpoints>::with_hooks(memory.memory_slab.as_mut() as *mut [u8], program.entrypoint, &program.instructions, Watchpoints::default());
+    vm.hooks.add(Watchpoint { addr: SCRATCH_START + 8, len: 4, kind: WatchKind::Write });
+    let hit = WatchHit { pc: 8, addr: SCRATCH_START + 8, size: 4, write: true, old: 0, new: 0x55 };
+    assert_eq!(vm.run_threaded(&code, &mut NoSyscalls, None), ExecutionResult::Watchpoint(hit));
+    assert_eq!((vm.get_ppc(), vm.cycle_count), (3, 3));
+    assert_eq!(vm.read_u32(SCRATCH_START + 8), 0x55);
+    assert_eq!(vm.resume_threaded(&code, &mut NoSyscalls), ExecutionResult::Success(0x55));
+}
@@ -0,0 +1,91 @@
+use alloc::string::String;
+use crate::syscalls::*;
+use crate::trace::*;
+use super::*;
+
+#[test]
+fn test_spike_commit_log() {
+    let program = setup_program(&[
+        li(A0, 5),
+        encode(InsnKind::SW, 0, 0, A0, 0x100),
+        encode(InsnKind::LW, A1, 0, 0, 0x100),
+        li(0, 1),
+        li(A7, SYS_EXIT as i32),
+        ECALL,
+    ]);
+    let mut memory = setup_memory();
+    let mut vm = setup_program_vm(&mut memory, &program);
+    let mut trace = TraceRecorder::new(&program);
+
+    assert_eq!(vm.run_traced(&mut NoSyscalls, None, &mut trace), ExecutionResult::Success(5));
+
+    let mut log = String::new();
+    trace.write_commit_log(&mut log).unwrap();
+    assert_eq!(log, "\
+core   0: 3 0x00000000 (0x00500513) x10 0x00000005
+core   0: 3 0x00000004 (0x10a02023) mem 0x00000100 0x00000005
+core   0: 3 0x00000008 (0x10002583) x11 0x00000005 mem 0x00000100
+core   0: 3 0x0000000c (0x00100013)
+core   0: 3 0x00000010 (0x00000893) x17 0x00000000
+core   0: 3 0x00000014 (0x00000073)
+");
+}
+
+#[test]
+fn test_traced_run_matches_plain_run() {
+    let elf_bytes = include_bytes!("test_data/output.bin");
+    let predecoded_program = PredecodedProgram::new(elf_bytes).unwrap();
+    let args = 12u32.to_le_bytes();
+
+    let mut memory = setup_memory();
+    memory.memory_slab[RO_START as usize..RO_START as usize + 4].copy_from_slice(&args);
+    let mut vm = setup_program_vm(&mut memory, &predecoded_program);
+    let plain = vm.run(Some(1000));
+    let (plain_fuel, plain_cycles) = (vm.fuel_used(), vm.cycle_count);
+
+    let mut memory = setup_memory();
+    memory.memory_slab[RO_START as usize..RO_START as usize + 4].copy_from_slice(&args);
+    let mut vm = setup_program_vm(&mut memory, &predecoded_program);
+    let mut trace = TraceRecorder::new(&predecoded_program);
+    let traced = vm.run_traced(&mut NoSyscalls, Some(1000), &mut trace);
+
+    assert_eq!(traced, plain);
+    assert_eq!(vm.fuel_used(), plain_fuel);
+    assert_eq!(vm.cycle_count, plain_cycles);
+    assert_eq!(trace.entries.len(), plain_cycles);
+    assert_eq!(trace.entries[0].pc, predecoded_program.entrypoint as u32 * 4);
+
+    // The sum is stored to scratch at the end
+    let store = trace.entries.iter().rev().find(|e| e.mem.is_some_and(|m| m.write)).unwrap();
+    assert_eq!(store.mem.unwrap().addr, SCRATCH_START);
+    assert_eq!(store.mem.unwrap().value, 11 * 12 / 2);
+
+    let decoded = TraceRecorder::from_bytes(&trace.to_bytes()).unwrap();
+    assert_eq!(decoded, trace.entries);
+}
+
+#[test]
+fn test_truncated_binary_trace() {
+    let program = setup_program(&[li(A0, 5), li(A7, SYS_EXIT as i32), ECALL]);
+    let mut memory = setup_memory();
+    let mut vm = setup_program_vm(&mut memory, &program);
+    let mut trace = TraceRecorder::new(&program);
+    vm.run_traced(&mut NoSyscalls, None, &mut trace);
+
+    let bytes = trace.to_bytes();
+    assert_eq!(TraceRecorder::from_bytes(&bytes[..bytes.len() - 1]), Err(RubicVError::InvalidTrace));
+}
+
+#[test]
+fn test_binary_trace_with_bad_access_size() {
+    // pc, insn, then flags for a load of 2^n bytes at 0
+    let entry = |n: u8| {
+        let mut bytes = [0u8; 17];
+        bytes[8] = (1 << 1) | (n << 4);
+        bytes
+    };
+    assert_eq!(TraceRecorder::from_bytes(&entry(2)).unwrap()[0].mem.unwrap().size, 4);
+    for n in 3..16 {
+        assert_eq!(TraceRecorder::from_bytes(&entry(n)), Err(RubicVError::InvalidTrace));
+    }
+}
@@ -0,0 +1,99 @@
+use crate::syscalls::*;
+use crate::verify::Capabilities;
+use super::*;
+
+const UNIMP: u32 = 0xc000_1073;
+
+fn exit(code: i32) -> [u32; 3] {
+    [li(A0, code), li(A7, SYS_EXIT as i32), ECALL]
+}
+
+fn rejected(words: &[u32]) -> (u32, &'static str) {
+    match setup_program(words).verify() {
+        Err(RubicVError::InvalidProgram { pc, reason }) => (pc, reason),
+        other => panic!("Unexpected verify result: {:?}", other),
+    }
+}
+
+#[test]
+fn test_verify_reports_capabilities() {
+    // 0: jal ra, 16; 4: auipc+jalr to 16; 12: exit(0); 24: unimp; 28: log; ret
+    let mut words = vec![
+        encode(InsnKind::JAL, RA, 0, 0, 28),
+        encode(InsnKind::AUIPC, RA, 0, 0, 0),
+        encode(InsnKind::JALR, RA, RA, 0, 24),
+    ];
+    words.extend(exit(0));
+    words.push(UNIMP);
+    words.extend([li(A7, 2), ECALL, encode(InsnKind::JALR, 0, RA, 0, 0)]);
+    let program = setup_program(&words);
+
+    assert_eq!(program.verify(), Ok(Capabilities {
+        kinds: vec![InsnKind::ADDI, InsnKind::JAL, InsnKind::JALR, InsnKind::AUIPC, InsnKind::ECALL],
+        syscalls: vec![SYS_EXIT, 2],
+        dynamic_syscalls: false,
+        indirect_jumps: false,
+    }));
+
+    // a7 and the jump target loaded from memory
+    let program = setup_program(&[
+        encode(InsnKind::LW, A7, 0, 0, ARGS_START as i32),
+        ECALL,
+        encode(InsnKind::LW, T0, 0, 0, ARGS_START as i32),
+        encode(InsnKind::JALR, 0, T0, 0, 0),
+    ]);
+    let capabilities = program.verify().unwrap();
+    assert!(capabilities.dynamic_syscalls && capabilities.indirect_jumps);
+    assert!(capabilities.syscalls.is_empty());
+}
+
+#[test]
+fn test_verify_rejects_unsafe_programs() {
+    assert_eq!(rejected(&[li(A0, 1), encode(InsnKind::BEQ, 0, A0, 0, 8)]), (4, "jump target outside the code"));
+    assert_eq!(rejected(&[encode(InsnKind::JAL, 0, 0, 0, -4)]), (0, "jump target outside the code"));
+    assert_eq!(rejected(&[encode(InsnKind::AUIPC, T0, 0, 0, 0x1000), encode(InsnKind::JALR, RA, T0, 0, 0)]), (4, "jump target outside the code"));
+    assert_eq!(rejected(&[encode(InsnKind::JALR, 0, 0, 0, 6), ECALL]), (0, "misaligned jump target"));
+    assert_eq!(rejected(&[li(A7, 1), ECALL, UNIMP]), (8, "invalid instruction reachable from the entrypoint"));
+    // only reachable words count
+    assert!(setup_program(&[encode(InsnKind::JAL, 0, 0, 0, 8), UNIMP, ECALL]).verify().is_ok());
+
+    let mut bytes = setup_program_bytes(&exit(0));
+    bytes[0..4].copy_from_slice(&12u32.to_le_bytes());
+    let program = PredecodedProgram::new(&bytes).unwrap();
+    assert_eq!(program.verify(), Err(RubicVError::InvalidProgram { pc: 12, reason: "entrypoint outside the code" }));
+}
+
+#[test]
+fn test_wild_jumps_trap_on_sentinel() {
+    let program = setup_program(&[encode(InsnKind::LUI, T0, 0, 0, 0x1000), encode(InsnKind::JALR, 0, T0, 0, 0)]);
+    assert_eq!(program.instructions.len(), program.words.len() + 1);
+    assert_eq!(program.instructions.last().unwrap().kind, InsnKind::INVALID);
+
+    let mut memory = setup_memory();
+    let mut vm = setup_program_vm(&mut memory, &program);
+    assert_eq!(vm.run(None), ExecutionResult::Error(RubicVError::IllegalInstruction));
+    assert_eq!(vm.get_ppc(), program.words.len());
+
+    // and so does running off the end
+    let program = setup_program(&[li(A0, 1)]);
+    let mut memory = setup_memory();
+    let mut vm = setup_program_vm(&mut memory, &program);
+    assert_eq!(vm.run(None), ExecutionResult::Error(RubicVError::IllegalInstruction));
+    assert_eq!(vm.get_ppc(), 1);
+}
+
+#[test]
+fn test_unwalked_branches_outside_the_code_trap_on_sentinel() {
+    // jalr x0, 0(ra) looks like a return, so verify() never walks the
+    // branch and jump it lands on
+    for bad in [encode(InsnKind::BEQ, 0, 0, 0, 0x800), encode(InsnKind::JAL, 0, 0, 0, -0x100)] {
+        let program = setup_program(&[li(RA, 8), encode(InsnKind::JALR, 0, RA, 0, 0), bad]);
+        assert!(program.verify().is_ok());
+        assert_eq!(program.instructions[2].imm as usize, program.words.len());
+
+        let mut memory = setup_memory();
+        let mut vm = setup_program_vm(&mut memory, &program);
+        assert_eq!(vm.run(None), ExecutionResult::Error(RubicVError::IllegalInstruction));
+        assert_eq!(vm.get_ppc(), program.words.len());
+    }
+}
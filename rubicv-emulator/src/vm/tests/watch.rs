@@ -0,0 +1,99 @@
+use crate::replay::TimeTravel;
+use crate::syscalls::*;
+use crate::watch::*;
+use super::*;
+
+fn watched_vm<'a>(memory: &mut TestMemory, program: &'a PredecodedProgram) -> VM<'a, Watchpoints> {
+    VM::<Watchpoints>::with_hooks(
+        memory.memory_slab.as_mut() as *mut [u8],
+        program.entrypoint,
+        &program.instructions,
+        Watchpoints::default(),
+    )
+}
+
+#[test]
+fn test_write_watchpoint_catches_stray_store() {
+    let program = stray_store_program();
+    let mut memory = setup_memory();
+    memory.memory_slab[0x10..0x14].copy_from_slice(&0xAAu32.to_le_bytes());
+    memory.memory_slab[ARGS_START as usize..ARGS_START as usize + 4].copy_from_slice(&7u32.to_le_bytes());
+    let mut vm = watched_vm(&mut memory, &program);
+    vm.hooks.add(Watchpoint { addr: 0, len: CODE_SIZE, kind: WatchKind::Write });
+    // loads from the args region are not writes
+    vm.hooks.add(Watchpoint { addr: ARGS_START, len: 4, kind: WatchKind::Write });
+
+    let hit = WatchHit { pc: 8, addr: 0x10, size: 4, write: true, old: 0xAA, new: 0x55 };
+    assert_eq!(vm.run(None), ExecutionResult::Watchpoint(hit));
+    assert_eq!(vm.hooks.triggered_by(&hit).map(|w| w.addr), Some(0));
+    assert_eq!(vm.get_ppc(), 3);
+    assert_eq!(vm.read_u32(0x10), 0x55);
+    assert_eq!(vm.cycle_count, 3);
+
+    assert_eq!(vm.resume(&mut NoSyscalls), ExecutionResult::Success(7));
+}
+
+#[test]
+fn test_read_and_access_watchpoints() {
+    let program = stray_store_program();
+    let mut memory = setup_memory();
+    memory.memory_slab[ARGS_START as usize..ARGS_START as usize + 4].copy_from_slice(&7u32.to_le_bytes());
+    let mut vm = watched_vm(&mut memory, &program);
+    vm.hooks.add(Watchpoint { addr: ARGS_START + 2, len: 1, kind: WatchKind::Read });
+
+    let hit = WatchHit { pc: 12, addr: ARGS_START, size: 4, write: false, old: 7, new: 7 };
+    assert_eq!(vm.run(None), ExecutionResult::Watchpoint(hit));
+    assert_eq!(vm.resume(&mut NoSyscalls), ExecutionResult::Success(7));
+
+    // an access watchpoint sees both, through the VMOperations handle too
+    let mut memory = setup_memory();
+    let mut vm = watched_vm(&mut memory, &program);
+    let watch = Watchpoint { addr: 0x10, len: 4, kind: WatchKind::Access };
+    vm.watchpoints().unwrap().add(watch);
+    assert!(matches!(vm.run(None), ExecutionResult::Watchpoint(WatchHit { pc: 8, write: true, .. })));
+    assert!(vm.watchpoints().unwrap().remove(&watch));
+    assert_eq!(vm.resume(&mut NoSyscalls), ExecutionResult::Success(0));
+
+    let mut memory = setup_memory();
+    assert!(setup_program_vm(&mut memory, &program).watchpoints().is_none());
+
+    // a load into x0 is still a read
+    let program = setup_program(&[
+        encode(InsnKind::LUI, T0, 0, 0, ARGS_START as i32),
+        encode(InsnKind::LHU, 0, T0, 0, 2),
+        li(A7, SYS_EXIT as i32),
+        ECALL,
+    ]);
+    let mut memory = setup_memory();
+    memory.memory_slab[ARGS_START as usize + 2..ARGS_START as usize + 4].copy_from_slice(&9u16.to_le_bytes());
+    let mut vm = watched_vm(&mut memory, &program);
+    vm.hooks.add(Watchpoint { addr: ARGS_START + 2, len: 1, kind: WatchKind::Read });
+    let hit = WatchHit { pc: 4, addr: ARGS_START + 2, size: 2, write: false, old: 9, new: 9 };
+    assert_eq!(vm.run(None), ExecutionResult::Watchpoint(hit));
+    assert_eq!(vm.resume(&mut NoSyscalls), ExecutionResult::Success(0));
+}
+
+#[test]
+fn test_reverse_continue_to_watchpoint() {
+    let program = stray_store_program();
+    let mut memory = setup_memory();
+    let mut vm = watched_vm(&mut memory, &program);
+    vm.hooks.add(Watchpoint { addr: 0x10, len: 4, kind: WatchKind::Write });
+    let mut history = TimeTravel::new(&vm, 2);
+
+    let mut hits = 0;
+    loop {
+        match history.step(&mut vm, &mut NoSyscalls) {
+            None => {}
+            Some(ExecutionResult::Watchpoint(_)) => hits += 1,
+            Some(ExecutionResult::Success(_)) => break,
+            Some(other) => panic!("Unexpected execution result: {:?}", other),
+        }
+    }
+    assert_eq!(hits, 1);
+
+    // back to just after the store
+    assert!(history.reverse_until(&mut vm, |_| false).unwrap());
+    assert_eq!(vm.get_ppc(), 3);
+    assert_eq!(vm.read_u32(0x10), 0x55);
+}
@@ -0,0 +1,409 @@
+use alloc::vec::Vec;
+use crate::errors::RubicVError;
+use crate::hooks::{Hooks, NoHooks};
+use crate::instructions::{InsnKind, PreDecodedInstruction};
+use crate::memory::RW_MASK;
+use crate::syscalls::SyscallHandler;
+use super::{sign_extend, ExecutionResult, MemoryPolicy, WrapMemory, VM};
+
+// Executes the instruction at the ppc and returns how many retired: 2 for a
+// fused pair
+pub type Handler<H, M> = for<'a> fn(&mut VM<'a, H, M>) -> Result<usize, RubicVError>;
+
+// Threaded-code form of a program for VMs with hooks H and memory policy M:
+// a handler per instruction, picked once when the program is loaded, so runs
+// call straight into it rather than going through VM::step's match. Handlers
+// only read the operands their instruction uses, and variants such as a JALR
+// that links or not are resolved up front.
+//
+// Metering is per basic block: once resume()'s loop has paid for one, its
+// handlers are called in turn from a loop until the block has retired, so
+// the stack stays flat however long the block is.
+//
+//   let code = ThreadedCode::new(&program.instructions);
+//   let mut vm = VM::new(slab, program.entrypoint, &program.instructions);
+//   vm.run_threaded(&code, &mut syscalls, Some(fuel));
+pub struct ThreadedCode<H: Hooks = NoHooks, M: MemoryPolicy = WrapMemory> {
+    handlers: Vec<Handler<H, M>>,
+}
+
+impl<H: Hooks, M: MemoryPolicy> ThreadedCode<H, M> {
+    // `instructions` must be the slice the VMs running this code are built with
+    pub fn new(instructions: &[PreDecodedInstruction]) -> Self {
+        Self { handlers: instructions.iter().map(handler_for::<H, M>).collect() }
+    }
+
+    // Runs `left` instructions from the ppc, all in one basic block
+    #[inline(always)]
+    fn run_block(&self, vm: &mut VM<'_, H, M>, mut left: usize) -> Result<(), RubicVError> {
+        while left > 0 {
+            let handler = unsafe { self.handlers.get_unchecked(vm.ppc) };
+            left -= handler(vm)?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a, H: Hooks, M: MemoryPolicy> VM<'a, H, M> {
+    pub fn run_threaded<S: SyscallHandler + ?Sized>(&mut self, code: &ThreadedCode<H, M>, syscalls: &mut S, max_fuel: Option<u64>) -> ExecutionResult {
+        self.start(max_fuel);
+        self.resume_threaded(code, syscalls)
+    }
+
+    // resume(), dispatching through `code`. Stops where resume() would.
+    pub fn resume_threaded<S: SyscallHandler + ?Sized>(&mut self, code: &ThreadedCode<H, M>, syscalls: &mut S) -> ExecutionResult {
+        assert_eq!(code.handlers.len(), self.pre_decoded_instructions.len(), "threaded code built for another program");
+        self.resume_with(syscalls, |vm, steps| {
+            // Instructions charged one at a time may be half of a fused pair
+            if steps == 1 {
+                return vm.execute::<false>().map(|_| ());
+            }
+            code.run_block(vm, steps)
+        })
+    }
+
+    #[inline(always)]
+    fn reg(&self, r: u8) -> u32 {
+        unsafe { *self.registers.get_unchecked(r as usize) }
+    }
+
+    #[inline(always)]
+    fn set_reg(&mut self, r: u8, value: u32) {
+        unsafe { *self.registers.get_unchecked_mut(r as usize) = value };
+    }
+
+    // Loads and stores are the only instructions a watchpoint can stop.
+    // Like step(), the ppc stays on the instruction for the trap handler.
+    #[inline(always)]
+    fn retire_access(&mut self) -> Result<(), RubicVError> {
+        if let Some(hit) = self.hooks.take_watch_hit() {
+            return Err(RubicVError::Watchpoint(hit));
+        }
+        self.ppc += 1;
+        Ok(())
+    }
+
+    #[inline(always)]
+    fn current(&self) -> &'a PreDecodedInstruction {
+        unsafe { self.pre_decoded_instructions.get_unchecked(self.ppc) }
+    }
+}
+
+fn handler_for<H: Hooks, M: MemoryPolicy>(insn: &PreDecodedInstruction) -> Handler<H, M> {
+    match insn.kind {
+        InsnKind::ADD => add,
+        InsnKind::SUB => sub,
+        InsnKind::XOR => xor,
+        InsnKind::OR => or,
+        InsnKind::AND => and,
+        InsnKind::SLL => sll,
+        InsnKind::SRL => srl,
+        InsnKind::SRA => sra,
+        InsnKind::SLT => slt,
+        InsnKind::SLTU => sltu,
+        InsnKind::MUL => mul,
+        InsnKind::MULH => mulh,
+        InsnKind::MULHSU => mulhsu,
+        InsnKind::MULHU => mulhu,
+        InsnKind::DIV => div,
+        InsnKind::DIVU => divu,
+        InsnKind::REM => rem,
+        InsnKind::REMU => remu,
+        InsnKind::ADDI => addi,
+        InsnKind::XORI => xori,
+        InsnKind::ORI => ori,
+        InsnKind::ANDI => andi,
+        InsnKind::SLLI => slli,
+        InsnKind::SRLI => srli,
+        InsnKind::SRAI => srai,
+        InsnKind::SLTI => slti,
+        InsnKind::SLTIU => sltiu,
+        InsnKind::LUI => lui,
+        InsnKind::AUIPC => auipc,
+        InsnKind::BEQ => beq,
+        InsnKind::BNE => bne,
+        InsnKind::BLT => blt,
+        InsnKind::BGE => bge,
+        InsnKind::BLTU => bltu,
+        InsnKind::BGEU => bgeu,
+        InsnKind::JAL if insn.rd == 0 => jal_no_link,
+        InsnKind::JAL => jal,
+        InsnKind::JALR if insn.rd == 0 => jalr_no_link,
+        InsnKind::JALR => jalr,
+        InsnKind::LB => lb,
+        InsnKind::LH => lh,
+        InsnKind::LW => lw,
+        InsnKind::LBU => lbu,
+        InsnKind::LHU => lhu,
+        InsnKind::SB => sb,
+        InsnKind::SH => sh,
+        InsnKind::SW => sw,
+        InsnKind::LUI_ADDI if H::FUSED => lui_addi,
+        InsnKind::AUIPC_JALR if H::FUSED => auipc_jalr,
+        InsnKind::ADDI_BNE if H::FUSED => addi_bne,
+        InsnKind::SLLI_ADD if H::FUSED => slli_add,
+        // Probes, fused pairs run one at a time, ecall, ebreak and
+        // invalid words all trap or are rare enough for the interpreter
+        _ => interpret,
+    }
+}
+
+fn interpret<H: Hooks, M: MemoryPolicy>(vm: &mut VM<'_, H, M>) -> Result<usize, RubicVError> {
+    vm.execute::<false>()
+}
+
+// rd = f(rs1, rs2)
+macro_rules! register_ops {
+    ($($name:ident = |$a:ident, $b:ident| $value:expr;)*) => {$(
+        fn $name<H: Hooks, M: MemoryPolicy>(vm: &mut VM<'_, H, M>) -> Result<usize, RubicVError> {
+            let insn = vm.current();
+            vm.hooks.on_step(vm.ppc, insn, &vm.registers);
+            let ($a, $b) = (vm.reg(insn.rs1), vm.reg(insn.rs2));
+            vm.set_reg(insn.rd, $value);
+            vm.ppc += 1;
+            Ok(1)
+        }
+    )*};
+}
+
+// rd = f(rs1, imm)
+macro_rules! immediate_ops {
+    ($($name:ident = |$a:ident, $imm:ident| $value:expr;)*) => {$(
+        fn $name<H: Hooks, M: MemoryPolicy>(vm: &mut VM<'_, H, M>) -> Result<usize, RubicVError> {
+            let insn = vm.current();
+            vm.hooks.on_step(vm.ppc, insn, &vm.registers);
+            let ($a, $imm) = (vm.reg(insn.rs1), insn.imm as u32);
+            vm.set_reg(insn.rd, $value);
+            vm.ppc += 1;
+            Ok(1)
+        }
+    )*};
+}
+
+// Taken if f(rs1, rs2), to the ppc in imm
+macro_rules! branches {
+    ($($name:ident = |$a:ident, $b:ident| $taken:expr;)*) => {$(
+        fn $name<H: Hooks, M: MemoryPolicy>(vm: &mut VM<'_, H, M>) -> Result<usize, RubicVError> {
+            let insn = vm.current();
+            vm.hooks.on_step(vm.ppc, insn, &vm.registers);
+            let ($a, $b) = (vm.reg(insn.rs1), vm.reg(insn.rs2));
+            let taken = $taken;
+            let next_ppc = if taken { insn.imm as usize } else { vm.ppc + 1 };
+            vm.hooks.on_branch(vm.ppc, next_ppc, taken);
+            vm.ppc = next_ppc;
+            Ok(1)
+        }
+    )*};
+}
+
+// rd = the `size` bytes at rs1 + imm, widened by f
+macro_rules! loads {
+    ($($name:ident = |$value:ident: $size:literal| $widened:expr;)*) => {$(
+        fn $name<H: Hooks, M: MemoryPolicy>(vm: &mut VM<'_, H, M>) -> Result<usize, RubicVError> {
+            let insn = vm.current();
+            vm.hooks.on_step(vm.ppc, insn, &vm.registers);
+            let addr = vm.reg(insn.rs1).wrapping_add(insn.imm as u32);
+            VM::<H, M>::check_load(addr, $size)?;
+            let $value = if $size == 1 { vm.read_u8(addr) as u32 } else { vm.read_sized(addr, $size) };
+            vm.hooks.on_mem_read(addr, $size, $value);
+            vm.set_reg(insn.rd, $widened);
+            vm.retire_access()?;
+            Ok(1)
+        }
+    )*};
+}
+
+// The low `size` bytes of rs2 to rs1 + imm
+macro_rules! stores {
+    ($($name:ident = $size:literal;)*) => {$(
+        fn $name<H: Hooks, M: MemoryPolicy>(vm: &mut VM<'_, H, M>) -> Result<usize, RubicVError> {
+            let insn = vm.current();
+            vm.hooks.on_step(vm.ppc, insn, &vm.registers);
+            let (addr, value) = (vm.reg(insn.rs1).wrapping_add(insn.imm as u32), vm.reg(insn.rs2));
+            VM::<H, M>::check_store(addr, $size)?;
+            let mask = u32::MAX >> (32 - 8 * $size);
+            vm.hooks.on_mem_write(addr, $size, vm.read_sized(addr & RW_MASK, $size), value & mask);
+            vm.write_sized(addr, $size, value);
+            vm.retire_access()?;
+            Ok(1)
+        }
+    )*};
+}
+
+register_ops! {
+    add = |a, b| a.wrapping_add(b);
+    sub = |a, b| a.wrapping_sub(b);
+    xor = |a, b| a ^ b;
+    or = |a, b| a | b;
+    and = |a, b| a & b;
+    sll = |a, b| a << (b & 0x1f);
+    srl = |a, b| a >> (b & 0x1f);
+    sra = |a, b| ((a as i32) >> (b & 0x1f)) as u32;
+    slt = |a, b| ((a as i32) < (b as i32)) as u32;
+    sltu = |a, b| (a < b) as u32;
+    mul = |a, b| a.wrapping_mul(b);
+    mulh = |a, b| ((a as i32 as i64).wrapping_mul(b as i32 as i64) >> 32) as u32;
+    mulhsu = |a, b| ((a as i32 as i64).wrapping_mul(b as u64 as i64) >> 32) as u32;
+    mulhu = |a, b| ((a as u64).wrapping_mul(b as u64) >> 32) as u32;
+    div = |a, b| if b == 0 {
+        u32::MAX
+    } else if a as i32 == i32::MIN && b as i32 == -1 {
+        a
+    } else {
+        (a as i32).wrapping_div(b as i32) as u32
+    };
+    divu = |a, b| a.checked_div(b).unwrap_or(u32::MAX);
+    rem = |a, b| if b == 0 {
+        a
+    } else if a as i32 == i32::MIN && b as i32 == -1 {
+        0
+    } else {
+        (a as i32).wrapping_rem(b as i32) as u32
+    };
+    remu = |a, b| a.checked_rem(b).unwrap_or(a);
+}
+
+immediate_ops! {
+    addi = |a, imm| a.wrapping_add(imm);
+    xori = |a, imm| a ^ imm;
+    ori = |a, imm| a | imm;
+    andi = |a, imm| a & imm;
+    slli = |a, imm| a << (imm & 0x1f);
+    srli = |a, imm| a >> (imm & 0x1f);
+    srai = |a, imm| ((a as i32) >> (imm & 0x1f)) as u32;
+    slti = |a, imm| ((a as i32) < (imm as i32)) as u32;
+    sltiu = |a, imm| (a < imm) as u32;
+}
+
+branches! {
+    beq = |a, b| a == b;
+    bne = |a, b| a != b;
+    blt = |a, b| (a as i32) < (b as i32);
+    bge = |a, b| (a as i32) >= (b as i32);
+    bltu = |a, b| a < b;
+    bgeu = |a, b| a >= b;
+}
+
+loads! {
+    lb = |value: 1| sign_extend(value, 8);
+    lh = |value: 2| sign_extend(value, 16);
+    lw = |value: 4| value;
+    lbu = |value: 1| value;
+    lhu = |value: 2| value;
+}
+
+stores! {
+    sb = 1;
+    sh = 2;
+    sw = 4;
+}
+
+fn lui<H: Hooks, M: MemoryPolicy>(vm: &mut VM<'_, H, M>) -> Result<usize, RubicVError> {
+    let insn = vm.current();
+    vm.hooks.on_step(vm.ppc, insn, &vm.registers);
+    vm.set_reg(insn.rd, insn.imm as u32);
+    vm.ppc += 1;
+    Ok(1)
+}
+
+fn auipc<H: Hooks, M: MemoryPolicy>(vm: &mut VM<'_, H, M>) -> Result<usize, RubicVError> {
+    let insn = vm.current();
+    vm.hooks.on_step(vm.ppc, insn, &vm.registers);
+    vm.set_reg(insn.rd, ((vm.ppc as u32) * 4).wrapping_add(insn.imm as u32));
+    vm.ppc += 1;
+    Ok(1)
+}
+
+fn jal<H: Hooks, M: MemoryPolicy>(vm: &mut VM<'_, H, M>) -> Result<usize, RubicVError> {
+    let insn = vm.current();
+    vm.hooks.on_step(vm.ppc, insn, &vm.registers);
+    vm.set_reg(insn.rd, ((vm.ppc + 1) * 4) as u32);
+    jump_to(vm, insn.imm as usize)
+}
+
+fn jal_no_link<H: Hooks, M: MemoryPolicy>(vm: &mut VM<'_, H, M>) -> Result<usize, RubicVError> {
+    let insn = vm.current();
+    vm.hooks.on_step(vm.ppc, insn, &vm.registers);
+    jump_to(vm, insn.imm as usize)
+}
+
+fn jalr<H: Hooks, M: MemoryPolicy>(vm: &mut VM<'_, H, M>) -> Result<usize, RubicVError> {
+    let insn = vm.current();
+    vm.hooks.on_step(vm.ppc, insn, &vm.registers);
+    // rs1 may be rd, so it is read before the link is written
+    let target_addr = vm.reg(insn.rs1).wrapping_add(insn.imm as u32) & !1;
+    vm.set_reg(insn.rd, ((vm.ppc + 1) * 4) as u32);
+    jump_to(vm, clamp(vm, target_addr))
+}
+
+fn jalr_no_link<H: Hooks, M: MemoryPolicy>(vm: &mut VM<'_, H, M>) -> Result<usize, RubicVError> {
+    let insn = vm.current();
+    vm.hooks.on_step(vm.ppc, insn, &vm.registers);
+    let target_addr = vm.reg(insn.rs1).wrapping_add(insn.imm as u32) & !1;
+    jump_to(vm, clamp(vm, target_addr))
+}
+
+#[inline(always)]
+fn jump_to<H: Hooks, M: MemoryPolicy>(vm: &mut VM<'_, H, M>, next_ppc: usize) -> Result<usize, RubicVError> {
+    vm.hooks.on_branch(vm.ppc, next_ppc, true);
+    vm.ppc = next_ppc;
+    Ok(1)
+}
+
+// Wild JALR targets land on the sentinel, as in step()
+#[inline(always)]
+fn clamp<H: Hooks, M: MemoryPolicy>(vm: &VM<'_, H, M>, target_addr: u32) -> usize {
+    ((target_addr / 4) as usize).min(vm.pre_decoded_instructions.len() - 1)
+}
+
+// Fused pairs, laid out as described at PreDecodedInstruction::unfused.
+// Only picked when H::FUSED, and only run when their whole block was paid.
+
+fn lui_addi<H: Hooks, M: MemoryPolicy>(vm: &mut VM<'_, H, M>) -> Result<usize, RubicVError> {
+    let insn = vm.current();
+    vm.hooks.on_step(vm.ppc, insn, &vm.registers);
+    vm.set_reg(insn.rd, insn.imm as u32);
+    vm.ppc += 2;
+    Ok(2)
+}
+
+fn auipc_jalr<H: Hooks, M: MemoryPolicy>(vm: &mut VM<'_, H, M>) -> Result<usize, RubicVError> {
+    let insn = vm.current();
+    vm.hooks.on_step(vm.ppc, insn, &vm.registers);
+    let jalr = unsafe { vm.pre_decoded_instructions.get_unchecked(vm.ppc + 1) };
+    let base = ((vm.ppc as u32) * 4).wrapping_add(insn.imm as u32);
+    vm.set_reg(insn.rd, base);
+    if jalr.rd != 0 {
+        vm.set_reg(jalr.rd, ((vm.ppc + 2) * 4) as u32);
+    }
+    let next_ppc = clamp(vm, base.wrapping_add(jalr.imm as u32) & !1);
+    vm.hooks.on_branch(vm.ppc + 1, next_ppc, true);
+    vm.ppc = next_ppc;
+    Ok(2)
+}
+
+fn addi_bne<H: Hooks, M: MemoryPolicy>(vm: &mut VM<'_, H, M>) -> Result<usize, RubicVError> {
+    let insn = vm.current();
+    vm.hooks.on_step(vm.ppc, insn, &vm.registers);
+    let value = vm.reg(insn.rs1).wrapping_add(insn.imm as u32);
+    vm.set_reg(insn.rd, value);
+    let taken = value != vm.reg(insn.rs2);
+    let next_ppc = if taken {
+        unsafe { vm.pre_decoded_instructions.get_unchecked(vm.ppc + 1) }.imm as usize
+    } else {
+        vm.ppc + 2
+    };
+    vm.hooks.on_branch(vm.ppc + 1, next_ppc, taken);
+    vm.ppc = next_ppc;
+    Ok(2)
+}
+
+fn slli_add<H: Hooks, M: MemoryPolicy>(vm: &mut VM<'_, H, M>) -> Result<usize, RubicVError> {
+    let insn = vm.current();
+    vm.hooks.on_step(vm.ppc, insn, &vm.registers);
+    let shifted = vm.reg(insn.rs1) << (insn.imm as u32 & 0x1f);
+    vm.set_reg((insn.imm >> 8) as u8, shifted);
+    vm.set_reg(insn.rd, vm.reg(insn.rs2).wrapping_add(shifted));
+    vm.ppc += 2;
+    Ok(2)
+}
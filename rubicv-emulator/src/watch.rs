@@ -0,0 +1,113 @@
+use alloc::vec::Vec;
+use crate::hooks::Hooks;
+use crate::instructions::PreDecodedInstruction;
+use crate::memory::{MEMORY_MASK, RW_MASK};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    // Either
+    Access,
+}
+
+// Watches the `len` bytes from `addr`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Watchpoint {
+    pub addr: u32,
+    pub len: u32,
+    pub kind: WatchKind,
+}
+
+impl Watchpoint {
+    fn triggers(&self, addr: u32, size: u8, write: bool) -> bool {
+        let kind = matches!((self.kind, write), (WatchKind::Access, _) | (WatchKind::Write, true) | (WatchKind::Read, false));
+        kind && addr < self.addr.saturating_add(self.len) && self.addr < addr + size as u32
+    }
+}
+
+// A load or store that touched a watched range. `addr` is where the access
+// landed after masking, which for a store outside the RW region is not the
+// address the guest computed. Loads report the value read as both `old`
+// and `new`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WatchHit {
+    pub pc: u32,
+    pub addr: u32,
+    pub size: u8,
+    pub write: bool,
+    pub old: u32,
+    pub new: u32,
+}
+
+// Hooks that stop a run with ExecutionResult::Watchpoint once an
+// instruction accesses a watched range:
+//
+//   let mut vm = VM::<Watchpoints>::with_hooks(slab, entry, &insns, Watchpoints::default());
+//   vm.hooks.add(Watchpoint { addr, len: 4, kind: WatchKind::Write });
+//
+// Only guest loads and stores are watched, not syscall handlers.
+#[derive(Default)]
+pub struct Watchpoints {
+    watches: Vec<Watchpoint>,
+    ppc: usize,
+    hit: Option<WatchHit>,
+}
+
+impl Watchpoints {
+    pub fn add(&mut self, watch: Watchpoint) {
+        if !self.watches.contains(&watch) {
+            self.watches.push(watch);
+        }
+    }
+
+    pub fn remove(&mut self, watch: &Watchpoint) -> bool {
+        let before = self.watches.len();
+        self.watches.retain(|w| w != watch);
+        self.watches.len() != before
+    }
+
+    pub fn clear(&mut self) {
+        self.watches.clear();
+    }
+
+    pub fn list(&self) -> &[Watchpoint] {
+        &self.watches
+    }
+
+    // The first watchpoint that `hit` triggers
+    pub fn triggered_by(&self, hit: &WatchHit) -> Option<&Watchpoint> {
+        self.watches.iter().find(|w| w.triggers(hit.addr, hit.size, hit.write))
+    }
+
+    fn check(&mut self, addr: u32, size: u8, write: bool, old: u32, new: u32) {
+        if self.hit.is_none() && self.watches.iter().any(|w| w.triggers(addr, size, write)) {
+            self.hit = Some(WatchHit { pc: (self.ppc * 4) as u32, addr, size, write, old, new });
+        }
+    }
+}
+
+impl Hooks for Watchpoints {
+    #[inline(always)]
+    fn on_step(&mut self, ppc: usize, _insn: &PreDecodedInstruction, _registers: &[u32; 32]) {
+        self.ppc = ppc;
+    }
+
+    // Matched where the access lands once masked into the region the VM
+    // reads from or writes to
+    fn on_mem_read(&mut self, addr: u32, size: u8, value: u32) {
+        self.check(addr & MEMORY_MASK, size, false, value, value);
+    }
+
+    fn on_mem_write(&mut self, addr: u32, size: u8, old: u32, value: u32) {
+        self.check(addr & RW_MASK, size, true, old, value);
+    }
+
+    fn take_watch_hit(&mut self) -> Option<WatchHit> {
+        self.hit.take()
+    }
+
+    fn watchpoints(&mut self) -> Option<&mut Watchpoints> {
+        Some(self)
+    }
+}
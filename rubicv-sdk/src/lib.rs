@@ -1,6 +1,24 @@
 #![no_std]
 
 pub use rubicv_emulator::memory::*;
+pub use rubicv_emulator::syscalls::SYS_EXIT;
+
+// Host call: number in a7, arguments in a0-a2, result returned in a0
+#[cfg(target_arch = "riscv32")]
+#[inline(always)]
+pub fn syscall(number: u32, a0: u32, a1: u32, a2: u32) -> u32 {
+    let ret: u32;
+    unsafe {
+        core::arch::asm!(
+            "ecall",
+            inlateout("a0") a0 => ret,
+            in("a1") a1,
+            in("a2") a2,
+            in("a7") number,
+        );
+    }
+    ret
+}
 
 
 #[macro_export]
@@ -32,9 +50,9 @@ macro_rules! entrypoint {
             loop {
                 unsafe {
                     core::arch::asm!(
-                        "mv a1, {0}
-                         ecall",
-                        in(reg) 0,
+                        "ecall",
+                        in("a0") 0,
+                        in("a7") SYS_EXIT,
                         options(noreturn)
                     );
                 }
@@ -46,9 +64,9 @@ macro_rules! entrypoint {
             loop {
                 unsafe {
                     core::arch::asm!(
-                        "mv a1, {0}",
                         "ecall",
-                        in(reg) 1,
+                        in("a0") 1,
+                        in("a7") SYS_EXIT,
                         options(noreturn)
                     );
                 }
@@ -0,0 +1,922 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use serde_json::{json, Value};
+use rubicv_emulator::disasm::{Disasm, ABI_NAMES};
+use rubicv_emulator::errors::RubicVError;
+use rubicv_emulator::instructions::{InsnKind, PredecodedProgram};
+use rubicv_emulator::memory::*;
+use rubicv_emulator::replay::TimeTravel;
+use rubicv_emulator::syscalls::NoSyscalls;
+use rubicv_emulator::vm::{ExecutionResult, VMOperations, VM};
+use rubicv_emulator::watch::{WatchHit, WatchKind, Watchpoint, Watchpoints};
+use crate::dwarf::DebugInfo;
+use crate::{guest_memory, load_guest, parse_u32};
+
+// The guest is the only thread
+const THREAD_ID: u32 = 1;
+// How many instructions run between checks for a pause request
+const PAUSE_POLL: usize = 1 << 16;
+// Deepest stack trace reported
+const MAX_FRAMES: usize = 64;
+// Instructions between the snapshots reverse execution replays from
+const CHECKPOINT_INTERVAL: usize = 10_000;
+
+// `utils dap`
+//
+// Serves the Debug Adapter Protocol over stdio. The client launches a guest
+// with `{"program": <bin-or-elf-file>, "args": [...], "stopOnEntry": bool}`;
+// with an ELF that has DWARF, breakpoints and stepping work on source lines.
+pub fn run() -> Result<(), Box<dyn std::error::Error>> {
+    run_with(io::stdin(), &mut io::stdout())
+}
+
+// The adapter over any pair of streams
+fn run_with(input: impl Read + Send + 'static, out: &mut dyn Write) -> Result<(), Box<dyn std::error::Error>> {
+    let requests = spawn_reader(input);
+    let mut client = Client { out, seq: 1 };
+
+    let launch = loop {
+        let Ok(request) = requests.recv() else {
+            return Ok(());
+        };
+        match request["command"].as_str().unwrap_or_default() {
+            "initialize" => client.respond(&request, json!({
+                "supportsConfigurationDoneRequest": true,
+                "supportsReadMemoryRequest": true,
+                "supportsDisassembleRequest": true,
+                "supportsInstructionBreakpoints": true,
+                "supportsSteppingGranularity": true,
+                "supportsTerminateRequest": true,
+                "supportsStepBack": true,
+                "supportsDataBreakpoints": true,
+            }))?,
+            "launch" => match Launch::new(&request["arguments"]) {
+                Ok(launch) => {
+                    client.respond(&request, Value::Null)?;
+                    break launch;
+                }
+                Err(e) => client.fail(&request, &e.to_string())?,
+            },
+            "disconnect" => return Ok(client.respond(&request, Value::Null)?),
+            _ => client.fail(&request, "no program has been launched")?,
+        }
+    };
+
+    let Launch { program, symbols, lines, mut memory, stop_on_entry } = launch;
+    let mut vm = VM::<Watchpoints>::with_hooks(
+        memory.as_mut() as *mut [u8],
+        program.entrypoint,
+        &program.instructions,
+        Watchpoints::default(),
+    );
+    let vm: &mut dyn VMOperations = &mut vm;
+    vm.set_register(2, STACK_START);
+    let mut session = Session {
+        program: &program,
+        symbols,
+        lines,
+        history: TimeTravel::new(vm, CHECKPOINT_INTERVAL),
+        vm,
+        source_breakpoints: BTreeMap::new(),
+        instruction_breakpoints: Vec::new(),
+        next_breakpoint_id: 1,
+        stop_on_entry,
+        exited: false,
+        steps: 0,
+        pending: VecDeque::new(),
+    };
+    client.event("initialized", Value::Null)?;
+    session.serve(&requests, &mut client)?;
+    Ok(())
+}
+
+// Requests are read on their own thread so a running guest can notice a pause
+fn spawn_reader(input: impl Read + Send + 'static) -> Receiver<Value> {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut input = BufReader::new(input);
+        while let Ok(Some(message)) = read_message(&mut input) {
+            if sender.send(message).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+// One `Content-Length: N\r\n\r\n{json}` message, or None at end of input
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        match line.trim_end() {
+            "" if length.is_some() => break,
+            header => if let Some(value) = header.strip_prefix("Content-Length:") {
+                length = value.trim().parse::<usize>().ok();
+            },
+        }
+    }
+    let mut body = vec![0u8; length.unwrap_or_default()];
+    input.read_exact(&mut body)?;
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+struct Client<'w> {
+    out: &'w mut dyn Write,
+    seq: u64,
+}
+
+impl Client<'_> {
+    fn send(&mut self, mut message: Value) -> io::Result<()> {
+        message["seq"] = self.seq.into();
+        self.seq += 1;
+        let body = message.to_string();
+        write!(self.out, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+        self.out.flush()
+    }
+
+    fn respond(&mut self, request: &Value, body: Value) -> io::Result<()> {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": true,
+            "body": body,
+        }))
+    }
+
+    fn fail(&mut self, request: &Value, message: &str) -> io::Result<()> {
+        self.send(json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": request["command"],
+            "success": false,
+            "message": message,
+        }))
+    }
+
+    fn event(&mut self, event: &str, body: Value) -> io::Result<()> {
+        self.send(json!({ "type": "event", "event": event, "body": body }))
+    }
+
+    fn stopped(&mut self, reason: &str, text: Option<String>) -> io::Result<()> {
+        self.event("stopped", json!({
+            "reason": reason,
+            "threadId": THREAD_ID,
+            "allThreadsStopped": true,
+            "text": text,
+        }))
+    }
+}
+
+struct Launch {
+    program: PredecodedProgram,
+    symbols: Vec<(String, u32)>,
+    lines: Option<DebugInfo>,
+    memory: Box<[u8]>,
+    stop_on_entry: bool,
+}
+
+impl Launch {
+    fn new(arguments: &Value) -> Result<Self, Box<dyn std::error::Error>> {
+        let path = arguments["program"].as_str().ok_or("launch needs a program")?;
+        let guest = load_guest(path)?;
+        let program = PredecodedProgram::new(&guest.bytes)
+            .map_err(|e| format!("{}: not a RubicV program ({:?})", path, e))?;
+        let lines = match &guest.elf {
+            Some(elf) => DebugInfo::load(elf)?,
+            None => None,
+        };
+        // Numbers may come as JSON numbers or as strings
+        let args: Vec<String> = arguments["args"].as_array().into_iter()
+            .flatten()
+            .map(|arg| arg.as_str().map_or_else(|| arg.to_string(), str::to_string))
+            .collect();
+        Ok(Launch {
+            program,
+            symbols: guest.symbols,
+            lines,
+            memory: guest_memory(&args)?,
+            stop_on_entry: arguments["stopOnEntry"].as_bool().unwrap_or(false),
+        })
+    }
+}
+
+// Why the guest stopped
+enum Stop {
+    // The step finished
+    Done,
+    Breakpoint,
+    Ebreak,
+    // Going back only finds where the access was, not what it was
+    Watchpoint(Option<WatchHit>),
+    Exception(String),
+    Exited(u32),
+    // Going back reached the first recorded instruction
+    StartOfHistory,
+    // A pause request, answered once the stop is reported
+    Paused(Option<Value>),
+}
+
+struct Session<'s> {
+    program: &'s PredecodedProgram,
+    symbols: Vec<(String, u32)>,
+    lines: Option<DebugInfo>,
+    history: TimeTravel,
+    vm: &'s mut dyn VMOperations,
+    // Resolved addresses per source path, and those set from the disassembly
+    source_breakpoints: BTreeMap<String, Vec<u32>>,
+    instruction_breakpoints: Vec<u32>,
+    next_breakpoint_id: u32,
+    stop_on_entry: bool,
+    exited: bool,
+    steps: usize,
+    // Requests that arrived while the guest ran
+    pending: VecDeque<Value>,
+}
+
+impl Session<'_> {
+    fn serve(&mut self, requests: &Receiver<Value>, client: &mut Client) -> io::Result<()> {
+        loop {
+            let request = match self.pending.pop_front() {
+                Some(request) => request,
+                None => match requests.recv() {
+                    Ok(request) => request,
+                    Err(_) => return Ok(()),
+                },
+            };
+            if !self.handle(&request, requests, client)? {
+                return Ok(());
+            }
+        }
+    }
+
+    // Returns false once the client disconnects
+    fn handle(&mut self, request: &Value, requests: &Receiver<Value>, client: &mut Client) -> io::Result<bool> {
+        let arguments = &request["arguments"];
+        let command = request["command"].as_str().unwrap_or_default();
+        let instruction = arguments["granularity"] == "instruction";
+        match command {
+            "setBreakpoints" => {
+                let body = self.set_breakpoints(arguments);
+                client.respond(request, body)?;
+            }
+            "setInstructionBreakpoints" => {
+                let body = self.set_instruction_breakpoints(arguments);
+                client.respond(request, body)?;
+            }
+            "setExceptionBreakpoints" => client.respond(request, json!({ "breakpoints": [] }))?,
+            "dataBreakpointInfo" => {
+                let body = self.data_breakpoint_info(arguments);
+                client.respond(request, body)?;
+            }
+            "setDataBreakpoints" => {
+                let body = self.set_data_breakpoints(arguments);
+                client.respond(request, body)?;
+            }
+            "configurationDone" => {
+                client.respond(request, Value::Null)?;
+                if self.stop_on_entry {
+                    client.stopped("entry", None)?;
+                } else {
+                    let stop = self.run_until(requests, |_| false);
+                    self.report(stop, client)?;
+                }
+            }
+            "threads" => client.respond(request, json!({ "threads": [{ "id": THREAD_ID, "name": "guest" }] }))?,
+            "stackTrace" => {
+                let body = self.stack_trace(arguments);
+                client.respond(request, body)?;
+            }
+            "scopes" => {
+                let frame = arguments["frameId"].as_u64().unwrap_or(0);
+                client.respond(request, json!({ "scopes": [{
+                    "name": "Registers",
+                    "presentationHint": "registers",
+                    "variablesReference": frame + 1,
+                    "expensive": false,
+                }] }))?;
+            }
+            "variables" => {
+                let body = self.variables(arguments);
+                client.respond(request, body)?;
+            }
+            "continue" | "next" | "stepIn" | "stepOut" if self.exited => {
+                client.fail(request, "the program has exited")?;
+            }
+            "continue" => {
+                client.respond(request, json!({ "allThreadsContinued": true }))?;
+                let stop = self.run_until(requests, |_| false);
+                self.report(stop, client)?;
+            }
+            "next" => {
+                client.respond(request, Value::Null)?;
+                let stop = if instruction { self.step_over(requests) } else { self.next_line(requests) };
+                self.report(stop, client)?;
+            }
+            "stepIn" => {
+                client.respond(request, Value::Null)?;
+                let stop = if instruction { self.run_until(requests, |_| true) } else { self.step_in_line(requests) };
+                self.report(stop, client)?;
+            }
+            "stepOut" => {
+                client.respond(request, Value::Null)?;
+                let stop = self.step_out(requests);
+                self.report(stop, client)?;
+            }
+            "stepBack" => {
+                client.respond(request, Value::Null)?;
+                let stop = if instruction { self.reverse_step() } else { self.step_back_line() };
+                self.report(stop, client)?;
+            }
+            "reverseContinue" => {
+                client.respond(request, Value::Null)?;
+                let stop = self.reverse_continue();
+                self.report(stop, client)?;
+            }
+            // The guest only runs while a request is being handled, so it
+            // is already stopped
+            "pause" => {
+                client.respond(request, Value::Null)?;
+                client.stopped("pause", None)?;
+            }
+            "readMemory" => match self.read_memory(arguments) {
+                Some(body) => client.respond(request, body)?,
+                None => client.fail(request, "bad memory reference")?,
+            },
+            "disassemble" => match self.disassemble(arguments) {
+                Some(body) => client.respond(request, body)?,
+                None => client.fail(request, "bad memory reference")?,
+            },
+            "terminate" => {
+                client.respond(request, Value::Null)?;
+                client.event("terminated", Value::Null)?;
+            }
+            "disconnect" => {
+                client.respond(request, Value::Null)?;
+                return Ok(false);
+            }
+            _ => client.fail(request, &format!("unsupported request: {}", command))?,
+        }
+        Ok(true)
+    }
+
+    fn report(&mut self, stop: Stop, client: &mut Client) -> io::Result<()> {
+        match stop {
+            Stop::Done => client.stopped("step", None),
+            Stop::Breakpoint => client.stopped("breakpoint", None),
+            Stop::Ebreak => client.stopped("breakpoint", Some("ebreak".to_string())),
+            Stop::Watchpoint(hit) => {
+                let text = hit.map(|hit| if hit.write {
+                    format!("store to 0x{:08x}: 0x{:x} -> 0x{:x}", hit.addr, hit.old, hit.new)
+                } else {
+                    format!("load from 0x{:08x}: 0x{:x}", hit.addr, hit.new)
+                });
+                client.stopped("data breakpoint", text)
+            }
+            Stop::Exception(text) => client.stopped("exception", Some(text)),
+            Stop::StartOfHistory => client.stopped("step", Some("start of the recorded history".to_string())),
+            Stop::Paused(request) => {
+                if let Some(request) = request {
+                    client.respond(&request, Value::Null)?;
+                }
+                client.stopped("pause", None)
+            }
+            Stop::Exited(code) => {
+                self.exited = true;
+                client.event("exited", json!({ "exitCode": code }))?;
+                client.event("terminated", Value::Null)
+            }
+        }
+    }
+
+    fn pc(&self) -> u32 {
+        (self.vm.get_ppc() * 4) as u32
+    }
+
+    fn is_breakpoint(&self, addr: u32) -> bool {
+        self.instruction_breakpoints.contains(&addr)
+            || self.source_breakpoints.values().any(|addrs| addrs.contains(&addr))
+    }
+
+    // Steps until `done`, a breakpoint, an ebreak, a pause request or the
+    // end of the guest
+    fn run_until(&mut self, requests: &Receiver<Value>, done: impl Fn(&Self) -> bool) -> Stop {
+        loop {
+            match self.history.step(self.vm, &mut NoSyscalls) {
+                None => {}
+                Some(ExecutionResult::Breakpoint) => return Stop::Ebreak,
+                Some(ExecutionResult::Watchpoint(hit)) => return Stop::Watchpoint(Some(hit)),
+                Some(ExecutionResult::Success(code)) => return Stop::Exited(code),
+                Some(ExecutionResult::Error(e)) => return Stop::Exception(format!("{:?}", e)),
+                Some(other) => return Stop::Exception(format!("{:?}", other)),
+            }
+            if done(self) {
+                return Stop::Done;
+            }
+            if self.is_breakpoint(self.pc()) {
+                return Stop::Breakpoint;
+            }
+            self.steps += 1;
+            if self.steps.is_multiple_of(PAUSE_POLL) {
+                while let Ok(request) = requests.try_recv() {
+                    match request["command"].as_str() {
+                        Some("pause") => return Stop::Paused(Some(request)),
+                        Some("disconnect" | "terminate") => {
+                            self.pending.push_back(request);
+                            return Stop::Paused(None);
+                        }
+                        _ => self.pending.push_back(request),
+                    }
+                }
+            }
+        }
+    }
+
+    // One instruction, or a whole call for a JAL/JALR that links
+    fn step_over(&mut self, requests: &Receiver<Value>) -> Stop {
+        let ppc = self.vm.get_ppc();
+        let is_call = self.program.original(ppc)
+            .is_some_and(|insn| matches!(insn.kind, InsnKind::JAL | InsnKind::JALR) && insn.rd != 0);
+        if !is_call {
+            return self.run_until(requests, |_| true);
+        }
+        let sp = self.vm.get_register(2);
+        self.run_until(requests, |s| s.vm.get_ppc() == ppc + 1 && s.vm.get_register(2) >= sp)
+    }
+
+    fn current_line(&self) -> Option<(PathBuf, u32)> {
+        line_of(self.lines.as_ref(), &*self.vm)
+    }
+
+    fn frame_base(&self) -> u32 {
+        frame_base_of(self.lines.as_ref(), &*self.vm)
+    }
+
+    // Steps over calls until a different line of this function or a caller
+    fn next_line(&mut self, requests: &Receiver<Value>) -> Stop {
+        let Some(start) = self.current_line() else {
+            return self.step_over(requests);
+        };
+        let base = self.frame_base();
+        loop {
+            let stop = self.step_over(requests);
+            if !matches!(stop, Stop::Done) {
+                return stop;
+            }
+            if self.current_line().is_some_and(|line| line != start) && self.frame_base() >= base {
+                return Stop::Done;
+            }
+        }
+    }
+
+    fn step_in_line(&mut self, requests: &Receiver<Value>) -> Stop {
+        let Some(start) = self.current_line() else {
+            return self.run_until(requests, |_| true);
+        };
+        self.run_until(requests, |s| s.current_line().is_some_and(|line| line != start))
+    }
+
+    // Runs until the current function returns to its caller
+    fn step_out(&mut self, requests: &Receiver<Value>) -> Stop {
+        let mut registers = self.vm.get_registers();
+        let cfa = self.lines.as_ref()
+            .and_then(|lines| lines.unwind(self.pc(), &mut registers, |addr| self.vm.read_u32(addr)))
+            .unwrap_or(registers[2]);
+        let return_to = registers[1];
+        self.run_until(requests, |s| s.pc() == return_to && s.vm.get_register(2) >= cfa)
+    }
+
+    // Reverse execution answers whether it moved at all; going back is
+    // never blocked by the exit
+    fn moved_back(&mut self, moved: Result<bool, RubicVError>, stop: Stop) -> Stop {
+        self.exited = false;
+        match moved {
+            Ok(true) => stop,
+            Ok(false) => Stop::StartOfHistory,
+            Err(e) => Stop::Exception(format!("{:?}", e)),
+        }
+    }
+
+    fn reverse_step(&mut self) -> Stop {
+        let moved = self.history.reverse_step(self.vm);
+        self.moved_back(moved, Stop::Done)
+    }
+
+    fn reverse_continue(&mut self) -> Stop {
+        let breakpoints: BTreeSet<u32> = self.instruction_breakpoints.iter()
+            .chain(self.source_breakpoints.values().flatten())
+            .copied()
+            .collect();
+        let moved = self.history.reverse_until(self.vm, |vm| breakpoints.contains(&((vm.get_ppc() * 4) as u32)));
+        // Otherwise it stopped just after a watched access
+        let stop = if breakpoints.contains(&self.pc()) { Stop::Breakpoint } else { Stop::Watchpoint(None) };
+        self.moved_back(moved, stop)
+    }
+
+    // Back to the start of the previous line of this function or a caller,
+    // passing over calls made in between
+    fn step_back_line(&mut self) -> Stop {
+        let Some(start) = self.current_line() else {
+            return self.reverse_step();
+        };
+        let lines = self.lines.as_ref();
+        let base = self.frame_base();
+        let moved = self.history.reverse_until(self.vm, |vm| {
+            line_of(lines, vm).is_some_and(|line| line != start) && frame_base_of(lines, vm) >= base
+        });
+        if !matches!(moved, Ok(true)) {
+            return self.moved_back(moved, Stop::Done);
+        }
+        // Find where the line was entered: go back past its first
+        // instruction, then forward over anything that call made
+        let line = self.current_line();
+        let base = self.frame_base();
+        let moved = self.history.reverse_until(self.vm, |vm| {
+            line_of(lines, vm) != line && frame_base_of(lines, vm) >= base
+        });
+        if matches!(moved, Ok(true)) {
+            self.history.step(self.vm, &mut NoSyscalls);
+            while frame_base_of(lines, &*self.vm) < base {
+                self.history.step(self.vm, &mut NoSyscalls);
+            }
+        }
+        self.moved_back(Ok(true), Stop::Done)
+    }
+
+    // (pc, registers) per frame, innermost first
+    fn frames(&self) -> Vec<(u32, [u32; 32])> {
+        let mut registers = self.vm.get_registers();
+        let mut pc = self.pc();
+        let mut frames = vec![(pc, registers)];
+        let Some(lines) = &self.lines else {
+            return frames;
+        };
+        while frames.len() < MAX_FRAMES {
+            // A return address may be the first instruction after the
+            // caller's function, so look up the call itself
+            let lookup = if frames.len() == 1 { pc } else { pc.wrapping_sub(4) };
+            let sp = registers[2];
+            let Some(cfa) = lines.unwind(lookup, &mut registers, |addr| self.vm.read_u32(addr)) else {
+                break;
+            };
+            let caller = registers[1];
+            // _start is entered with ra = 0
+            if caller == 0 || (caller == pc && cfa == sp) {
+                break;
+            }
+            pc = caller;
+            frames.push((pc, registers));
+        }
+        frames
+    }
+
+    fn symbol(&self, addr: u32) -> Option<String> {
+        let (name, _) = self.symbols.iter().rev().find(|(_, at)| *at <= addr)?;
+        Some(format!("{:#}", rustc_demangle::demangle(name)))
+    }
+
+    fn source(path: &Path) -> Value {
+        json!({
+            "name": path.file_name().map(|name| name.to_string_lossy()),
+            "path": path.to_string_lossy(),
+        })
+    }
+
+    fn stack_trace(&self, arguments: &Value) -> Value {
+        let frames = self.frames();
+        let start = arguments["startFrame"].as_u64().unwrap_or(0) as usize;
+        let levels = match arguments["levels"].as_u64() {
+            Some(0) | None => frames.len(),
+            Some(levels) => levels as usize,
+        };
+        let stack_frames: Vec<Value> = frames.iter()
+            .enumerate()
+            .skip(start)
+            .take(levels)
+            .map(|(id, (pc, _))| {
+                let lookup = if id == 0 { *pc } else { pc.wrapping_sub(4) };
+                let mut frame = json!({
+                    "id": id,
+                    "name": self.symbol(*pc).unwrap_or_else(|| format!("0x{:08x}", pc)),
+                    "line": 0,
+                    "column": 0,
+                    "instructionPointerReference": format!("0x{:08x}", pc),
+                });
+                if let Some((path, line)) = self.lines.as_ref().and_then(|lines| lines.line_for(lookup)) {
+                    frame["source"] = Self::source(path);
+                    frame["line"] = line.into();
+                    frame["column"] = 1.into();
+                }
+                frame
+            })
+            .collect();
+        json!({ "stackFrames": stack_frames, "totalFrames": frames.len() })
+    }
+
+    // Each frame's scope is its variablesReference minus one
+    fn variables(&self, arguments: &Value) -> Value {
+        let frame = arguments["variablesReference"].as_u64().unwrap_or(0).saturating_sub(1) as usize;
+        let Some((pc, registers)) = self.frames().get(frame).copied() else {
+            return json!({ "variables": [] });
+        };
+        let mut variables = vec![json!({
+            "name": "pc",
+            "value": format!("0x{:08x}", pc),
+            "variablesReference": 0,
+            "memoryReference": format!("0x{:08x}", pc),
+        })];
+        for (r, value) in registers.iter().enumerate() {
+            variables.push(json!({
+                "name": format!("{} (x{})", ABI_NAMES[r], r),
+                "value": format!("0x{:08x}", value),
+                "variablesReference": 0,
+                "memoryReference": format!("0x{:08x}", value),
+            }));
+        }
+        json!({ "variables": variables })
+    }
+
+    fn set_breakpoints(&mut self, arguments: &Value) -> Value {
+        let path = arguments["source"]["path"].as_str().unwrap_or_default().to_string();
+        let mut addrs = Vec::new();
+        let breakpoints: Vec<Value> = arguments["breakpoints"].as_array().into_iter()
+            .flatten()
+            .map(|breakpoint| {
+                let line = breakpoint["line"].as_u64().unwrap_or(0) as u32;
+                let resolved = self.lines.as_ref().and_then(|lines| lines.address_for(Path::new(&path), line));
+                let Some((line, addr)) = resolved else {
+                    return json!({ "verified": false, "message": "no code at this line" });
+                };
+                addrs.push(addr);
+                self.next_breakpoint_id += 1;
+                json!({
+                    "id": self.next_breakpoint_id - 1,
+                    "verified": true,
+                    "line": line,
+                    "source": { "path": path },
+                    "instructionReference": format!("0x{:08x}", addr),
+                })
+            })
+            .collect();
+        self.source_breakpoints.insert(path, addrs);
+        json!({ "breakpoints": breakpoints })
+    }
+
+    fn set_instruction_breakpoints(&mut self, arguments: &Value) -> Value {
+        self.instruction_breakpoints.clear();
+        let breakpoints: Vec<Value> = arguments["breakpoints"].as_array().into_iter()
+            .flatten()
+            .map(|breakpoint| {
+                let addr = breakpoint["instructionReference"].as_str()
+                    .and_then(parse_u32)
+                    .map(|addr| addr.wrapping_add(breakpoint["offset"].as_i64().unwrap_or(0) as u32));
+                match addr {
+                    Some(addr) if addr.is_multiple_of(4) && (addr as usize / 4) < self.program.words.len() => {
+                        self.instruction_breakpoints.push(addr);
+                        self.next_breakpoint_id += 1;
+                        json!({ "id": self.next_breakpoint_id - 1, "verified": true })
+                    }
+                    _ => json!({ "verified": false, "message": "not an instruction of the program" }),
+                }
+            })
+            .collect();
+        json!({ "breakpoints": breakpoints })
+    }
+
+    // Only memory can be watched, so the name must be an address or a symbol
+    fn data_breakpoint_info(&self, arguments: &Value) -> Value {
+        let name = arguments["name"].as_str().unwrap_or_default();
+        let addr = self.symbols.iter()
+            .find(|(symbol, _)| symbol == name)
+            .map(|(_, addr)| *addr)
+            .or_else(|| parse_u32(name));
+        let Some(addr) = addr.filter(|addr| *addr < MEMORY_SIZE) else {
+            return json!({ "dataId": null, "description": "only guest memory addresses can be watched" });
+        };
+        let len = arguments["bytes"].as_u64().unwrap_or(4).max(1);
+        json!({
+            "dataId": format!("0x{:08x}/{}", addr, len),
+            "description": format!("{} bytes at 0x{:08x}", len, addr),
+            "accessTypes": ["read", "write", "readWrite"],
+        })
+    }
+
+    fn set_data_breakpoints(&mut self, arguments: &Value) -> Value {
+        let mut watches = Vec::new();
+        let breakpoints: Vec<Value> = arguments["breakpoints"].as_array().into_iter()
+            .flatten()
+            .map(|breakpoint| {
+                let watch = breakpoint["dataId"].as_str()
+                    .and_then(|id| id.split_once('/'))
+                    .and_then(|(addr, len)| Some((parse_u32(addr)?, len.parse::<u32>().ok()?)));
+                let kind = match breakpoint["accessType"].as_str() {
+                    Some("read") => WatchKind::Read,
+                    Some("readWrite") => WatchKind::Access,
+                    _ => WatchKind::Write,
+                };
+                let Some((addr, len)) = watch else {
+                    return json!({ "verified": false, "message": "not a guest memory range" });
+                };
+                watches.push(Watchpoint { addr, len, kind });
+                self.next_breakpoint_id += 1;
+                json!({ "id": self.next_breakpoint_id - 1, "verified": true })
+            })
+            .collect();
+        let watchpoints = self.vm.watchpoints().expect("the session VM has watchpoint hooks");
+        watchpoints.clear();
+        for watch in watches {
+            watchpoints.add(watch);
+        }
+        json!({ "breakpoints": breakpoints })
+    }
+
+    fn read_memory(&self, arguments: &Value) -> Option<Value> {
+        let reference = parse_u32(arguments["memoryReference"].as_str()?)?;
+        let start = reference as i64 + arguments["offset"].as_i64().unwrap_or(0);
+        let count = arguments["count"].as_u64().unwrap_or(0) as i64;
+        let readable = start.clamp(0, MEMORY_SIZE as i64)..(start + count).clamp(0, MEMORY_SIZE as i64);
+        let bytes: Vec<u8> = readable.clone().map(|addr| self.vm.read_u8(addr as u32)).collect();
+        Some(json!({
+            "address": format!("0x{:08x}", readable.start),
+            "data": base64(&bytes),
+            "unreadableBytes": count - bytes.len() as i64,
+        }))
+    }
+
+    fn disassemble(&self, arguments: &Value) -> Option<Value> {
+        let reference = parse_u32(arguments["memoryReference"].as_str()?)? as i64;
+        let first = reference + arguments["offset"].as_i64().unwrap_or(0)
+            + arguments["instructionOffset"].as_i64().unwrap_or(0) * 4;
+        let count = arguments["instructionCount"].as_i64().unwrap_or(0);
+        let instructions: Vec<Value> = (0..count)
+            .map(|i| first + i * 4)
+            .map(|addr| {
+                let index = usize::try_from(addr / 4).ok().filter(|_| addr >= 0 && addr % 4 == 0);
+                let decoded = index.and_then(|index| Some((self.program.original(index)?, self.program.words.get(index)?)));
+                let Some((insn, word)) = decoded else {
+                    return json!({
+                        "address": format!("0x{:08x}", addr as u32),
+                        "instruction": "??",
+                        "presentationHint": "invalid",
+                    });
+                };
+                let mut line = json!({
+                    "address": format!("0x{:08x}", addr),
+                    "instructionBytes": format!("{:08x}", word),
+                    "instruction": Disasm::new(&insn).to_string(),
+                });
+                if let Some((name, _)) = self.symbols.iter().find(|(_, at)| *at as i64 == addr) {
+                    line["symbol"] = format!("{:#}", rustc_demangle::demangle(name)).into();
+                }
+                if let Some((path, source_line)) = self.lines.as_ref().and_then(|lines| lines.line_for(addr as u32)) {
+                    line["location"] = Self::source(path);
+                    line["line"] = source_line.into();
+                }
+                line
+            })
+            .collect();
+        Some(json!({ "instructions": instructions }))
+    }
+}
+
+fn line_of(lines: Option<&DebugInfo>, vm: &dyn VMOperations) -> Option<(PathBuf, u32)> {
+    let (path, line) = lines?.line_for((vm.get_ppc() * 4) as u32)?;
+    Some((path.to_path_buf(), line))
+}
+
+// The frame's CFA, which unlike sp stays put through the prologue
+fn frame_base_of(lines: Option<&DebugInfo>, vm: &dyn VMOperations) -> u32 {
+    let mut registers = vm.get_registers();
+    lines.and_then(|lines| lines.unwind((vm.get_ppc() * 4) as u32, &mut registers, |addr| vm.read_u32(addr)))
+        .unwrap_or(registers[2])
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = u32::from_be_bytes([0, chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i)) as usize & 63] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/test_data/bump");
+
+    fn request(seq: u64, command: &str, arguments: Value) -> String {
+        let body = json!({ "seq": seq, "type": "request", "command": command, "arguments": arguments }).to_string();
+        format!("Content-Length: {}\r\n\r\n{}", body.len(), body)
+    }
+
+    // Runs the adapter over `requests` and returns what it sent back
+    fn exchange(requests: &[(&str, Value)]) -> Vec<Value> {
+        let input: String = requests.iter()
+            .enumerate()
+            .map(|(i, (command, arguments))| request(i as u64 + 1, command, arguments.clone()))
+            .collect();
+        let mut out = Vec::new();
+        run_with(io::Cursor::new(input.into_bytes()), &mut out).unwrap();
+        let mut out = out.as_slice();
+        std::iter::from_fn(|| read_message(&mut out).unwrap()).collect()
+    }
+
+    // `response:<command>` or `event:<event>`
+    fn kind(message: &Value) -> String {
+        match message["type"].as_str() {
+            Some("response") => format!("response:{}", message["command"].as_str().unwrap()),
+            _ => format!("event:{}", message["event"].as_str().unwrap()),
+        }
+    }
+
+    #[test]
+    fn test_breakpoint_session() {
+        let messages = exchange(&[
+            ("initialize", json!({ "adapterID": "rubicv" })),
+            ("launch", json!({ "program": format!("{}.elf", FIXTURE) })),
+            // Line 16 is the `bump:` label, so the breakpoint moves to its first instruction
+            ("setBreakpoints", json!({ "source": { "path": format!("{}.s", FIXTURE) }, "breakpoints": [{ "line": 16 }] })),
+            ("configurationDone", Value::Null),
+            ("stackTrace", json!({ "threadId": THREAD_ID })),
+            ("continue", json!({ "threadId": THREAD_ID })),
+            ("disconnect", Value::Null),
+        ]);
+        let kinds: Vec<String> = messages.iter().map(kind).collect();
+        assert_eq!(kinds, [
+            "response:initialize",
+            "response:launch",
+            "event:initialized",
+            "response:setBreakpoints",
+            "response:configurationDone",
+            "event:stopped",
+            "response:stackTrace",
+            "response:continue",
+            "event:exited",
+            "event:terminated",
+            "response:disconnect",
+        ]);
+        assert!(messages.iter().filter(|m| m["type"] == "response").all(|m| m["success"] == true));
+        let seqs: Vec<u64> = messages.iter().map(|m| m["seq"].as_u64().unwrap()).collect();
+        assert_eq!(seqs, (1..=messages.len() as u64).collect::<Vec<_>>());
+
+        let breakpoint = &messages[3]["body"]["breakpoints"][0];
+        assert_eq!(breakpoint["verified"], true);
+        assert_eq!(breakpoint["line"], 18);
+        assert_eq!(breakpoint["instructionReference"], "0x0000001c");
+
+        assert_eq!(messages[5]["body"]["reason"], "breakpoint");
+
+        // Unwound through bump's frame info to the call in _start
+        let frames = &messages[6]["body"]["stackFrames"];
+        assert_eq!(messages[6]["body"]["totalFrames"], 2);
+        assert_eq!(frames[0]["name"], "bump");
+        assert_eq!(frames[0]["line"], 18);
+        assert_eq!(frames[0]["source"]["name"], "bump.s");
+        assert_eq!(frames[1]["name"], "_start");
+        assert_eq!(frames[1]["line"], 9);
+        assert_eq!(frames[1]["instructionPointerReference"], "0x0000000c");
+
+        assert_eq!(messages[8]["body"]["exitCode"], 42);
+    }
+
+    #[test]
+    fn test_requests_before_launch() {
+        let messages = exchange(&[
+            ("threads", Value::Null),
+            ("launch", json!({})),
+            ("launch", json!({ "program": format!("{}.missing", FIXTURE) })),
+            ("disconnect", Value::Null),
+        ]);
+        let kinds: Vec<String> = messages.iter().map(kind).collect();
+        assert_eq!(kinds, ["response:threads", "response:launch", "response:launch", "response:disconnect"]);
+        assert_eq!(messages[0]["message"], "no program has been launched");
+        assert_eq!(messages[1]["message"], "launch needs a program");
+        assert_eq!(messages[2]["success"], false);
+        assert_eq!(messages[3]["success"], true);
+    }
+
+    #[test]
+    fn test_read_message() {
+        let mut input = "Content-Length: 2\r\nContent-Type: x\r\n\r\n{}Content-Length: 4\r\n\r\nnull".as_bytes();
+        assert_eq!(read_message(&mut input).unwrap(), Some(json!({})));
+        assert_eq!(read_message(&mut input).unwrap(), Some(Value::Null));
+        assert_eq!(read_message(&mut input).unwrap(), None);
+        assert!(read_message(&mut "Content-Length: 3\r\n\r\n{]}".as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,499 @@
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, Write};
+use rubicv_emulator::disasm::{Disasm, ABI_NAMES};
+use rubicv_emulator::instructions::{InsnKind, PredecodedProgram};
+use rubicv_emulator::memory::*;
+use rubicv_emulator::replay::TimeTravel;
+use rubicv_emulator::syscalls::NoSyscalls;
+use rubicv_emulator::vm::{ExecutionResult, VMOperations, VM};
+use rubicv_emulator::watch::{WatchHit, WatchKind, Watchpoint, Watchpoints};
+use crate::{guest_memory, load_guest, parse_u32};
+
+// Instructions between the snapshots reverse execution replays from
+const CHECKPOINT_INTERVAL: usize = 10_000;
+
+const HELP: &str = "\
+step [n]           execute n instructions (s)
+next               step over calls (n)
+continue           run to the next breakpoint, ebreak or exit (c)
+reverse-step [n]   undo n instructions (rs)
+reverse-continue   run backwards to the previous breakpoint or watchpoint (rc)
+break <addr|sym>   set a breakpoint (b)
+delete [addr|sym]  remove one breakpoint, or all of them (d)
+watch <addr|sym> [len]   stop after a store to len bytes (default 4)
+rwatch <addr|sym> [len]  stop after a load from them
+awatch <addr|sym> [len]  stop after either
+unwatch [addr|sym] remove the watchpoints at addr, or all of them
+info               list breakpoints and watchpoints
+regs               dump registers (r)
+x <addr> [len]     hexdump guest memory
+list [addr]        disassemble around the pc or addr (l)
+quit               leave the debugger (q)
+An empty line repeats the previous command.";
+
+// `utils debug <file> [args...]`: u32 args are copied to ARGS_START
+pub fn run(path: &str, args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let guest = load_guest(path)?;
+    let program = PredecodedProgram::new(&guest.bytes)
+        .map_err(|e| format!("{}: not a RubicV program ({:?})", path, e))?;
+
+    let mut memory = guest_memory(args)?;
+
+    let mut vm = VM::<Watchpoints>::with_hooks(
+        memory.as_mut() as *mut [u8],
+        program.entrypoint,
+        &program.instructions,
+        Watchpoints::default(),
+    );
+    let vm: &mut dyn VMOperations = &mut vm;
+    vm.set_register(2, STACK_START);
+    let mut session = Session {
+        program: &program,
+        symbols: guest.symbols,
+        history: TimeTravel::new(vm, CHECKPOINT_INTERVAL),
+        vm,
+        breakpoints: BTreeSet::new(),
+        running: true,
+    };
+    session.show_location();
+
+    let stdin = io::stdin();
+    let mut last = String::new();
+    loop {
+        print!("(rubicv) ");
+        io::stdout().flush()?;
+        let mut line = String::new();
+        if stdin.lock().read_line(&mut line)? == 0 {
+            return Ok(());
+        }
+        let line = match line.trim() {
+            "" => last.clone(),
+            command => command.to_string(),
+        };
+        if !session.execute(&line) {
+            return Ok(());
+        }
+        last = line;
+    }
+}
+
+struct Session<'s> {
+    program: &'s PredecodedProgram,
+    symbols: Vec<(String, u32)>,
+    history: TimeTravel,
+    vm: &'s mut dyn VMOperations,
+    breakpoints: BTreeSet<u32>,
+    running: bool,
+}
+
+impl Session<'_> {
+    // Returns false to quit
+    fn execute(&mut self, line: &str) -> bool {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            [] => {}
+            ["q" | "quit"] => return false,
+            ["h" | "help"] => println!("{}", HELP),
+            ["s" | "step"] => self.step(1),
+            ["s" | "step", n] => match n.parse() {
+                Ok(n) => self.step(n),
+                Err(_) => println!("bad count: {}", n),
+            },
+            ["n" | "next"] => self.next(),
+            ["c" | "continue"] => self.resume_until(|_| false),
+            ["rs" | "reverse-step"] => self.reverse_step(1),
+            ["rs" | "reverse-step", n] => match n.parse() {
+                Ok(n) => self.reverse_step(n),
+                Err(_) => println!("bad count: {}", n),
+            },
+            ["rc" | "reverse-continue"] => self.reverse_continue(),
+            ["b" | "break", location] => match self.resolve(location) {
+                Some(addr) => {
+                    self.breakpoints.insert(addr);
+                    println!("breakpoint at {}", self.describe(addr));
+                }
+                None => println!("no such location: {}", location),
+            },
+            ["d" | "delete"] => self.breakpoints.clear(),
+            ["d" | "delete", location] => match self.resolve(location) {
+                Some(addr) if self.breakpoints.remove(&addr) => {}
+                _ => println!("no breakpoint at {}", location),
+            },
+            [command @ ("watch" | "rwatch" | "awatch"), location, len @ ..] if len.len() <= 1 => {
+                let kind = match *command {
+                    "watch" => WatchKind::Write,
+                    "rwatch" => WatchKind::Read,
+                    _ => WatchKind::Access,
+                };
+                match (self.resolve(location), len.first().map_or(Some(4), |len| parse_u32(len))) {
+                    (Some(addr), Some(len)) if len > 0 => {
+                        self.watchpoints().add(Watchpoint { addr, len, kind });
+                        println!("watchpoint on {} bytes at {}", len, self.describe(addr));
+                    }
+                    _ => println!("usage: {} <addr|sym> [len]", command),
+                }
+            }
+            ["unwatch"] => self.watchpoints().clear(),
+            ["unwatch", location] => match self.resolve(location) {
+                Some(addr) => {
+                    let watchpoints = self.watchpoints();
+                    let matching: Vec<Watchpoint> = watchpoints.list().iter().filter(|w| w.addr == addr).copied().collect();
+                    if matching.is_empty() {
+                        println!("no watchpoint at {}", location);
+                    }
+                    for watch in &matching {
+                        watchpoints.remove(watch);
+                    }
+                }
+                None => println!("no such location: {}", location),
+            },
+            ["info"] | ["info", "breakpoints"] => {
+                for addr in &self.breakpoints {
+                    println!("  {}", self.describe(*addr));
+                }
+                let watches = self.watchpoints().list().to_vec();
+                for watch in watches {
+                    println!("  {:?} watch on {} bytes at {}", watch.kind, watch.len, self.describe(watch.addr));
+                }
+            }
+            ["r" | "regs"] => self.dump_registers(),
+            ["x", addr] => self.hexdump(addr, "64"),
+            ["x", addr, len] => self.hexdump(addr, len),
+            ["l" | "list"] => self.list(self.pc()),
+            ["l" | "list", location] => match self.resolve(location) {
+                Some(addr) => self.list(addr),
+                None => println!("no such location: {}", location),
+            },
+            _ => println!("unknown command, try help"),
+        }
+        true
+    }
+
+    fn watchpoints(&mut self) -> &mut Watchpoints {
+        self.vm.watchpoints().expect("the debugger VM has watchpoint hooks")
+    }
+
+    fn pc(&self) -> u32 {
+        (self.vm.get_ppc() * 4) as u32
+    }
+
+    // Runs one instruction; returns false once execution has stopped
+    fn step_once(&mut self) -> bool {
+        if !self.running {
+            println!("the program is not running");
+            return false;
+        }
+        match self.history.step(self.vm, &mut NoSyscalls) {
+            None => true,
+            Some(ExecutionResult::Breakpoint) => {
+                println!("ebreak at {}", self.describe(self.pc() - 4));
+                false
+            }
+            Some(ExecutionResult::Watchpoint(hit)) => {
+                self.report_watch(&hit);
+                false
+            }
+            Some(ExecutionResult::Success(code)) => {
+                println!("exited with code {} after {} instructions", code, self.vm.get_cycle_count());
+                self.running = false;
+                false
+            }
+            Some(other) => {
+                println!("stopped: {:?}", other);
+                self.running = other.is_resumable();
+                false
+            }
+        }
+    }
+
+    fn step(&mut self, count: usize) {
+        for _ in 0..count {
+            if !self.step_once() {
+                break;
+            }
+        }
+        self.show_location();
+    }
+
+    // Steps over JAL/JALR that link a return address, stopping when the call
+    // returns to the next instruction with the stack unwound
+    fn next(&mut self) {
+        let ppc = self.vm.get_ppc();
+        let is_call = self.program.original(ppc)
+            .is_some_and(|insn| matches!(insn.kind, InsnKind::JAL | InsnKind::JALR) && insn.rd != 0);
+        if !is_call {
+            return self.step(1);
+        }
+        let sp = self.vm.get_register(2);
+        self.resume_until(|vm| vm.get_ppc() == ppc + 1 && vm.get_register(2) >= sp);
+    }
+
+    fn resume_until(&mut self, done: impl Fn(&dyn VMOperations) -> bool) {
+        loop {
+            if !self.step_once() {
+                break;
+            }
+            if done(&*self.vm) {
+                break;
+            }
+            if self.breakpoints.contains(&self.pc()) {
+                println!("breakpoint at {}", self.describe(self.pc()));
+                break;
+            }
+        }
+        self.show_location();
+    }
+
+    fn reverse_step(&mut self, count: usize) {
+        for _ in 0..count {
+            match self.history.reverse_step(self.vm) {
+                Ok(true) => {}
+                Ok(false) => {
+                    println!("at the start of the recorded history");
+                    break;
+                }
+                Err(e) => return println!("cannot go back: {:?}", e),
+            }
+        }
+        self.running = true;
+        self.show_location();
+    }
+
+    fn reverse_continue(&mut self) {
+        let breakpoints = &self.breakpoints;
+        match self.history.reverse_until(self.vm, |vm| breakpoints.contains(&((vm.get_ppc() * 4) as u32))) {
+            Ok(true) if self.breakpoints.contains(&self.pc()) => println!("breakpoint at {}", self.describe(self.pc())),
+            // Otherwise it stopped just after a watched access
+            Ok(true) => println!("watchpoint after {}", self.describe(self.pc() - 4)),
+            Ok(false) => println!("at the start of the recorded history"),
+            Err(e) => return println!("cannot go back: {:?}", e),
+        }
+        self.running = true;
+        self.show_location();
+    }
+
+    fn report_watch(&self, hit: &WatchHit) {
+        let access = if hit.write { "store" } else { "load" };
+        println!("watchpoint: {} of {} bytes at {} by {}", access, hit.size, self.describe(hit.addr), self.describe(hit.pc));
+        if hit.write {
+            println!("  old 0x{:x}  new 0x{:x}", hit.old, hit.new);
+        } else {
+            println!("  value 0x{:x}", hit.new);
+        }
+    }
+
+    fn show_location(&self) {
+        if self.running {
+            self.print_insn(self.pc(), true);
+        }
+    }
+
+    fn print_insn(&self, addr: u32, current: bool) {
+        let marker = if current { "=>" } else if self.breakpoints.contains(&addr) { " *" } else { "  " };
+        match self.program.original(addr as usize / 4) {
+            Some(insn) => println!("{} {}: {}", marker, self.describe(addr), Disasm::new(&insn)),
+            None => println!("{} {}: <outside the program>", marker, self.describe(addr)),
+        }
+    }
+
+    fn list(&self, around: u32) {
+        let first = (around / 4).saturating_sub(5) as usize;
+        let last = (around as usize / 4 + 6).min(self.program.words.len());
+        for ppc in first..last {
+            let addr = (ppc * 4) as u32;
+            if let Some((name, _)) = self.symbols.iter().find(|(_, at)| *at == addr) {
+                println!("{}:", name);
+            }
+            self.print_insn(addr, self.running && addr == self.pc());
+        }
+    }
+
+    fn dump_registers(&self) {
+        println!("pc   0x{:08x}  cycles {}  fuel {}", self.pc(), self.vm.get_cycle_count(), self.vm.get_fuel_used());
+        let registers = self.vm.get_registers();
+        for row in 0..8 {
+            let line: Vec<String> = (0..4)
+                .map(|col| row + col * 8)
+                .map(|r| format!("{:<4} 0x{:08x}", ABI_NAMES[r], registers[r]))
+                .collect();
+            println!("{}", line.join("  "));
+        }
+    }
+
+    fn hexdump(&self, addr: &str, len: &str) {
+        let (Some(start), Some(len)) = (self.resolve(addr), parse_u32(len)) else {
+            return println!("usage: x <addr> [len]");
+        };
+        let end = start.saturating_add(len).min(MEMORY_SIZE);
+        for row in (start..end).step_by(16) {
+            let bytes: Vec<u8> = (row..(row + 16).min(end)).map(|a| self.vm.read_u8(a)).collect();
+            let hex: Vec<String> = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+            let text: String = bytes.iter()
+                .map(|&b| if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' })
+                .collect();
+            let region = if row < RO_START { "rw" } else { "ro" };
+            println!("0x{:08x} {}  {:<47}  |{}|", row, region, hex.join(" "), text);
+        }
+    }
+
+    // A symbol name or a number
+    fn resolve(&self, location: &str) -> Option<u32> {
+        self.symbols.iter()
+            .find(|(name, _)| name == location)
+            .map(|(_, addr)| *addr)
+            .or_else(|| parse_u32(location))
+    }
+
+    // `0x00000044 <_start+4>`
+    fn describe(&self, addr: u32) -> String {
+        match self.symbols.iter().rev().find(|(_, at)| *at <= addr) {
+            Some((name, at)) if *at == addr => format!("0x{:08x} <{}>", addr, name),
+            Some((name, at)) => format!("0x{:08x} <{}+{}>", addr, name, addr - at),
+            None => format!("0x{:08x}", addr),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rubicv_emulator::asm::assemble;
+
+    const PROGRAM: &str = "
+    _start:
+        li a0, 1
+        call bump
+        lui a1, 0x2             // SCRATCH_START
+        sw a0, 0(a1)
+        li a7, 0
+        ecall
+    bump:
+        addi a0, a0, 41
+        ret
+    ";
+
+    fn with_session(test: impl FnOnce(&mut Session)) {
+        let program = PredecodedProgram::new(&assemble(PROGRAM).unwrap()).unwrap();
+        let mut memory = guest_memory(&[]).unwrap();
+        let mut vm = VM::<Watchpoints>::with_hooks(
+            memory.as_mut() as *mut [u8],
+            program.entrypoint,
+            &program.instructions,
+            Watchpoints::default(),
+        );
+        let vm: &mut dyn VMOperations = &mut vm;
+        vm.set_register(2, STACK_START);
+        let mut session = Session {
+            program: &program,
+            symbols: vec![("_start".to_string(), 0), ("bump".to_string(), 24)],
+            history: TimeTravel::new(vm, CHECKPOINT_INTERVAL),
+            vm,
+            breakpoints: BTreeSet::new(),
+            running: true,
+        };
+        test(&mut session);
+    }
+
+    #[test]
+    fn test_break_and_continue_to_exit() {
+        with_session(|session| {
+            assert!(session.execute("b bump"));
+            assert_eq!(session.breakpoints, BTreeSet::from([24]));
+            session.execute("c");
+            assert_eq!(session.pc(), 24);
+            assert_eq!(session.vm.get_register(10), 1);
+
+            session.execute("c");
+            assert!(!session.running);
+            assert_eq!(session.vm.get_register(10), 42);
+            assert_eq!(session.vm.read_u32(SCRATCH_START), 42);
+
+            // Nothing left to run
+            let cycles = session.vm.get_cycle_count();
+            session.execute("s");
+            assert_eq!(session.vm.get_cycle_count(), cycles);
+        });
+    }
+
+    #[test]
+    fn test_next_steps_over_calls() {
+        with_session(|session| {
+            session.execute("s");
+            assert_eq!(session.pc(), 4);
+            session.execute("n");
+            assert_eq!(session.pc(), 8);
+            assert_eq!(session.vm.get_register(10), 42);
+            // Not a call, so a single step
+            session.execute("n");
+            assert_eq!(session.pc(), 12);
+        });
+    }
+
+    #[test]
+    fn test_reverse_execution() {
+        with_session(|session| {
+            session.execute("s 4");
+            assert_eq!(session.pc(), 8);
+            session.execute("rs 2");
+            assert_eq!(session.pc(), 24);
+            assert_eq!(session.vm.get_register(10), 1);
+
+            session.execute("b 4");
+            session.execute("rc");
+            assert_eq!(session.pc(), 4);
+            session.execute("rc");
+            assert_eq!(session.pc(), 0);
+            assert_eq!(session.vm.get_cycle_count(), 0);
+        });
+    }
+
+    #[test]
+    fn test_watchpoint_stops_after_the_store() {
+        with_session(|session| {
+            session.execute("watch 0x2000");
+            assert_eq!(session.watchpoints().list().len(), 1);
+            session.execute("c");
+            assert!(session.running);
+            assert_eq!(session.pc(), 16);
+            assert_eq!(session.vm.read_u32(SCRATCH_START), 42);
+
+            session.execute("unwatch 0x2000");
+            assert!(session.watchpoints().list().is_empty());
+            session.execute("c");
+            assert!(!session.running);
+        });
+    }
+
+    #[test]
+    fn test_malformed_commands_change_nothing() {
+        with_session(|session| {
+            for line in ["s x", "rs -1", "b nowhere", "watch 0x2000 0", "watch bump 1 2", "x", "frob", ""] {
+                assert!(session.execute(line), "{}", line);
+            }
+            assert_eq!(session.pc(), 0);
+            assert!(session.breakpoints.is_empty());
+            assert!(session.watchpoints().list().is_empty());
+
+            session.execute("b 0x18");
+            session.execute("d nowhere");
+            assert_eq!(session.breakpoints.len(), 1);
+            session.execute("d");
+            assert!(session.breakpoints.is_empty());
+
+            assert!(!session.execute("quit"));
+        });
+    }
+
+    #[test]
+    fn test_symbols() {
+        with_session(|session| {
+            assert_eq!(session.resolve("bump"), Some(24));
+            assert_eq!(session.resolve("0x1c"), Some(28));
+            assert_eq!(session.resolve("16"), Some(16));
+            assert_eq!(session.resolve("nowhere"), None);
+            assert_eq!(session.describe(24), "0x00000018 <bump>");
+            assert_eq!(session.describe(28), "0x0000001c <bump+4>");
+            assert_eq!(session.describe(8), "0x00000008 <_start+8>");
+        });
+    }
+}
@@ -0,0 +1,133 @@
+use std::path::{Path, PathBuf};
+use gimli::{
+    BaseAddresses, CfaRule, DebugFrame, EndianSlice, LittleEndian, Register, RegisterRule,
+    UnwindContext, UnwindSection,
+};
+use goblin::elf::Elf;
+
+type Slice<'d> = EndianSlice<'d, LittleEndian>;
+
+// RISC-V DWARF register numbers match the x registers
+const SP: usize = 2;
+
+// One row of the line table: `addr` starts code for `line` of `files[file]`.
+// Line 0 is code with no source line, such as past the end of a sequence.
+#[derive(Clone, Copy, Debug)]
+struct Row {
+    addr: u32,
+    file: usize,
+    line: u32,
+    is_stmt: bool,
+}
+
+// Source lines and call frame information from a guest ELF
+pub struct DebugInfo {
+    files: Vec<PathBuf>,
+    // Sorted by address
+    rows: Vec<Row>,
+    debug_frame: Vec<u8>,
+}
+
+impl DebugInfo {
+    // None when the ELF carries no line table
+    pub fn load(buffer: &[u8]) -> Result<Option<Self>, Box<dyn std::error::Error>> {
+        let elf = Elf::parse(buffer)?;
+        let section = |name: &str| -> &[u8] {
+            elf.section_headers.iter()
+                .find(|s| elf.shdr_strtab.get_at(s.sh_name) == Some(name))
+                .and_then(|s| buffer.get(s.sh_offset as usize..(s.sh_offset + s.sh_size) as usize))
+                .unwrap_or(&[])
+        };
+        let dwarf = gimli::Dwarf::load(|id| -> Result<Slice, gimli::Error> {
+            Ok(EndianSlice::new(section(id.name()), LittleEndian))
+        })?;
+
+        let mut info = DebugInfo { files: Vec::new(), rows: Vec::new(), debug_frame: section(".debug_frame").to_vec() };
+        let mut units = dwarf.units();
+        while let Some(header) = units.next()? {
+            let unit = dwarf.unit(header)?;
+            let Some(program) = unit.line_program.clone() else {
+                continue;
+            };
+            let comp_dir = unit.comp_dir.map(|dir| PathBuf::from(dir.to_string_lossy().into_owned()));
+            let mut rows = program.rows();
+            while let Some((header, row)) = rows.next_row()? {
+                let addr = row.address() as u32;
+                let (Some(file), Some(line), false) = (row.file(header), row.line(), row.end_sequence()) else {
+                    info.rows.push(Row { addr, file: 0, line: 0, is_stmt: false });
+                    continue;
+                };
+                let mut path = comp_dir.clone().unwrap_or_default();
+                if let Some(dir) = file.directory(header) {
+                    path.push(dwarf.attr_string(&unit, dir)?.to_string_lossy().as_ref());
+                }
+                path.push(dwarf.attr_string(&unit, file.path_name())?.to_string_lossy().as_ref());
+                let file = match info.files.iter().position(|known| *known == path) {
+                    Some(index) => index,
+                    None => {
+                        info.files.push(path);
+                        info.files.len() - 1
+                    }
+                };
+                info.rows.push(Row { addr, file, line: line.get() as u32, is_stmt: row.is_stmt() });
+            }
+        }
+        if info.rows.is_empty() {
+            return Ok(None);
+        }
+        // A sequence that ends where another starts sorts ahead of it
+        info.rows.sort_by_key(|row| (row.addr, row.line != 0));
+        Ok(Some(info))
+    }
+
+    // The source file and line `addr` belongs to
+    pub fn line_for(&self, addr: u32) -> Option<(&Path, u32)> {
+        let index = self.rows.partition_point(|row| row.addr <= addr);
+        let row = self.rows[..index].last()?;
+        (row.line != 0).then(|| (self.files[row.file].as_path(), row.line))
+    }
+
+    // The first statement of `line` in `file`, or of the closest line after
+    // it that has code. Returns the line actually used and its address.
+    pub fn address_for(&self, file: &Path, line: u32) -> Option<(u32, u32)> {
+        self.rows.iter()
+            .filter(|row| row.is_stmt && row.line >= line && same_file(&self.files[row.file], file))
+            .min_by_key(|row| (row.line, row.addr))
+            .map(|row| (row.line, row.addr))
+    }
+
+    // Recovers the caller's registers from the .debug_frame rules for `pc`,
+    // returning the canonical frame address: the sp before the call. The
+    // caller resumes at the restored ra. None when no rule covers `pc`.
+    pub fn unwind(&self, pc: u32, registers: &mut [u32; 32], read_u32: impl Fn(u32) -> u32) -> Option<u32> {
+        let mut debug_frame = DebugFrame::new(&self.debug_frame, LittleEndian);
+        debug_frame.set_address_size(4);
+        let mut context = Box::new(UnwindContext::new());
+        let row = debug_frame
+            .unwind_info_for_address(&BaseAddresses::default(), &mut context, pc as u64, DebugFrame::cie_from_offset)
+            .ok()?;
+        let cfa = match row.cfa() {
+            CfaRule::RegisterAndOffset { register, offset } => {
+                registers.get(register.0 as usize)?.wrapping_add(*offset as u32)
+            }
+            CfaRule::Expression(_) => return None,
+        };
+        let current = *registers;
+        for (n, value) in registers.iter_mut().enumerate().skip(1) {
+            match row.register(Register(n as u16)) {
+                RegisterRule::Offset(offset) => *value = read_u32(cfa.wrapping_add(offset as u32)),
+                RegisterRule::ValOffset(offset) => *value = cfa.wrapping_add(offset as u32),
+                RegisterRule::Register(other) => *value = current.get(other.0 as usize).copied()?,
+                _ => {}
+            }
+        }
+        registers[SP] = cfa;
+        Some(cfa)
+    }
+}
+
+// Paths from the client and from DWARF may differ in how much of the
+// directory they spell out
+fn same_file(known: &Path, wanted: &Path) -> bool {
+    known == wanted || known.ends_with(wanted) || wanted.ends_with(known)
+}
@@ -0,0 +1,603 @@
+use std::collections::BTreeSet;
+use std::io::{self, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use rubicv_emulator::disasm::ABI_NAMES;
+use rubicv_emulator::errors::RubicVError;
+use rubicv_emulator::instructions::PredecodedProgram;
+use rubicv_emulator::memory::*;
+use rubicv_emulator::replay::TimeTravel;
+use rubicv_emulator::syscalls::NoSyscalls;
+use rubicv_emulator::vm::{ExecutionResult, VMOperations, VM};
+use rubicv_emulator::watch::{WatchKind, Watchpoint, Watchpoints};
+use crate::{guest_memory, load_guest};
+
+// Register numbers as gdb sees them: x0-x31, then the pc
+const PC_REGNUM: usize = 32;
+// How many instructions a continue runs between checks for a ^C from gdb
+const INTERRUPT_POLL: usize = 1 << 16;
+// Instructions between the snapshots reverse execution replays from
+const CHECKPOINT_INTERVAL: usize = 10_000;
+
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+const SIGSEGV: u8 = 11;
+
+// `utils gdb [--port N | --stdio] <file> [args...]`
+//
+// Serves the GDB remote protocol over a VM paused at its entry point. Point
+// gdb at the guest ELF for symbols and source, then
+// `target remote :1234` or `target remote | utils gdb --stdio <file>`.
+pub fn run(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let (transport, rest) = match args {
+        [flag, port, rest @ ..] if flag == "--port" => (Some(port.parse::<u16>()?), rest),
+        [flag, rest @ ..] if flag == "--stdio" => (None, rest),
+        rest => (Some(1234), rest),
+    };
+    let [path, guest_args @ ..] = rest else {
+        return Err("missing guest file".into());
+    };
+
+    let guest = load_guest(path)?;
+    let program = PredecodedProgram::new(&guest.bytes)
+        .map_err(|e| format!("{}: not a RubicV program ({:?})", path, e))?;
+    let mut memory = guest_memory(guest_args)?;
+    let mut vm = VM::<Watchpoints>::with_hooks(
+        memory.as_mut() as *mut [u8],
+        program.entrypoint,
+        &program.instructions,
+        Watchpoints::default(),
+    );
+    let vm: &mut dyn VMOperations = &mut vm;
+    vm.set_register(2, STACK_START);
+    let mut stub = Stub {
+        program: &program,
+        history: TimeTravel::new(vm, CHECKPOINT_INTERVAL),
+        vm,
+        breakpoints: BTreeSet::new(),
+        exited: None,
+    };
+
+    match transport {
+        Some(port) => {
+            let listener = TcpListener::bind(("127.0.0.1", port))?;
+            eprintln!("waiting for gdb on 127.0.0.1:{}", port);
+            let (stream, _) = listener.accept()?;
+            stream.set_nodelay(true)?;
+            let mut connection = Connection {
+                reader: BufReader::new(stream.try_clone()?),
+                writer: stream.try_clone()?,
+                interrupts: Some(stream),
+            };
+            stub.serve(&mut connection)
+        }
+        None => {
+            let mut connection = Connection {
+                reader: BufReader::new(io::stdin()),
+                writer: io::stdout(),
+                interrupts: None,
+            };
+            stub.serve(&mut connection)
+        }
+    }
+}
+
+struct Connection<R: Read, W: Write> {
+    reader: BufReader<R>,
+    writer: W,
+    // A non-blocking peek at the socket finds a ^C while the guest runs
+    interrupts: Option<TcpStream>,
+}
+
+impl<R: Read, W: Write> Connection<R, W> {
+    fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        let mut byte = [0u8];
+        match self.reader.read(&mut byte)? {
+            0 => Ok(None),
+            _ => Ok(Some(byte[0])),
+        }
+    }
+
+    // Next packet, acked, or None once gdb hangs up
+    fn read_packet(&mut self) -> io::Result<Option<Packet>> {
+        loop {
+            match self.read_byte()? {
+                None => return Ok(None),
+                Some(0x03) => return Ok(Some(Packet::Interrupt)),
+                Some(b'$') => break,
+                Some(_) => continue, // acks and line noise
+            }
+        }
+        let mut body = Vec::new();
+        loop {
+            match self.read_byte()? {
+                None => return Ok(None),
+                Some(b'#') => break,
+                Some(byte) => body.push(byte),
+            }
+        }
+        let mut checksum = [0u8; 2];
+        self.reader.read_exact(&mut checksum)?;
+        let expected = std::str::from_utf8(&checksum).ok().and_then(|c| u8::from_str_radix(c, 16).ok());
+        if expected != Some(checksum_of(&body)) {
+            self.writer.write_all(b"-")?;
+            self.writer.flush()?;
+            return self.read_packet();
+        }
+        self.writer.write_all(b"+")?;
+        Ok(Some(Packet::Command(String::from_utf8_lossy(&body).into_owned())))
+    }
+
+    fn send(&mut self, body: &str) -> io::Result<()> {
+        write!(self.writer, "${}#{:02x}", body, checksum_of(body.as_bytes()))?;
+        self.writer.flush()
+    }
+
+    fn interrupted(&mut self) -> bool {
+        let Some(stream) = &self.interrupts else {
+            return false;
+        };
+        let mut byte = [0u8];
+        if stream.set_nonblocking(true).is_err() {
+            return false;
+        }
+        let interrupted = matches!(stream.peek(&mut byte), Ok(1) if byte[0] == 0x03);
+        if interrupted {
+            let _ = self.reader.read(&mut byte);
+        }
+        let _ = stream.set_nonblocking(false);
+        interrupted
+    }
+}
+
+enum Packet {
+    Command(String),
+    Interrupt,
+}
+
+fn checksum_of(body: &[u8]) -> u8 {
+    body.iter().fold(0u8, |sum, b| sum.wrapping_add(*b))
+}
+
+struct Stub<'s> {
+    program: &'s PredecodedProgram,
+    history: TimeTravel,
+    vm: &'s mut dyn VMOperations,
+    // byte addresses
+    breakpoints: BTreeSet<u32>,
+    exited: Option<u32>,
+}
+
+impl Stub<'_> {
+    fn serve<R: Read, W: Write>(&mut self, connection: &mut Connection<R, W>) -> Result<(), Box<dyn std::error::Error>> {
+        while let Some(packet) = connection.read_packet()? {
+            let command = match packet {
+                Packet::Command(command) => command,
+                Packet::Interrupt => continue, // nothing is running
+            };
+            let reply = match command.as_bytes().first() {
+                Some(b'k') => return Ok(()),
+                Some(b'D') => {
+                    connection.send("OK")?;
+                    return Ok(());
+                }
+                Some(b'c') => self.resume(connection, false),
+                Some(b's') => self.resume(connection, true),
+                Some(b'b') if command == "bs" || command == "bc" => self.reverse(command == "bs"),
+                _ => self.handle(&command),
+            };
+            connection.send(&reply)?;
+        }
+        Ok(())
+    }
+
+    fn handle(&mut self, command: &str) -> String {
+        let Some((name, args)) = command.split_at_checked(1) else {
+            return String::new();
+        };
+        match name {
+            "?" => self.stop_reply(SIGTRAP),
+            "g" => self.vm.get_registers().iter()
+                .chain([self.pc()].iter())
+                .map(|r| hex_u32(*r))
+                .collect(),
+            "G" => {
+                let values: Vec<u32> = (0..args.len() / 8).filter_map(|i| parse_hex_u32(args.get(i * 8..i * 8 + 8)?)).collect();
+                if values.len() < 33 {
+                    return error(1);
+                }
+                if !self.valid_pc(values[PC_REGNUM]) {
+                    return error(22);
+                }
+                for (r, value) in values.iter().take(32).enumerate() {
+                    self.vm.set_register(r as u8, *value);
+                }
+                self.vm.set_ppc(values[PC_REGNUM] as usize / 4);
+                ok()
+            }
+            "p" => match usize::from_str_radix(args, 16) {
+                Ok(r) if r < 32 => hex_u32(self.vm.get_register(r as u8)),
+                Ok(PC_REGNUM) => hex_u32(self.pc()),
+                _ => error(1),
+            },
+            "P" => {
+                let parsed = args.split_once('=')
+                    .and_then(|(r, v)| Some((usize::from_str_radix(r, 16).ok()?, parse_hex_u32(v)?)));
+                match parsed {
+                    Some((r, value)) if r < 32 => self.vm.set_register(r as u8, value),
+                    Some((PC_REGNUM, value)) if self.valid_pc(value) => self.vm.set_ppc(value as usize / 4),
+                    Some((PC_REGNUM, _)) => return error(22),
+                    _ => return error(1),
+                }
+                ok()
+            }
+            "m" => match parse_range(args) {
+                Some((addr, len)) if addr as u64 + len as u64 <= MEMORY_SIZE as u64 => {
+                    (addr..addr + len).map(|a| format!("{:02x}", self.vm.read_u8(a))).collect()
+                }
+                _ => error(14),
+            },
+            "M" => {
+                let Some((range, data)) = args.split_once(':') else {
+                    return error(1);
+                };
+                match (parse_range(range), hex_bytes(data)) {
+                    // Code is executed from the predecoded program, so only
+                    // data in the RW region can be changed
+                    (Some((addr, len)), Some(bytes)) if addr >= CODE_SIZE && addr as u64 + len as u64 <= RW_SIZE as u64 && bytes.len() == len as usize => {
+                        for (i, byte) in bytes.iter().enumerate() {
+                            self.vm.write_u8(addr + i as u32, *byte);
+                        }
+                        ok()
+                    }
+                    _ => error(14),
+                }
+            }
+            "Z" | "z" => {
+                let mut parts = args.split(',');
+                let (Some(kind), Some(addr)) = (parts.next(), parts.next().and_then(|a| u32::from_str_radix(a, 16).ok())) else {
+                    return error(1);
+                };
+                let watch = match kind {
+                    "2" => Some(WatchKind::Write),
+                    "3" => Some(WatchKind::Read),
+                    "4" => Some(WatchKind::Access),
+                    _ => None,
+                };
+                if let Some(kind) = watch {
+                    // For watchpoints the last field is the length in bytes
+                    let Some(len) = parts.next().and_then(|l| u32::from_str_radix(l, 16).ok()) else {
+                        return error(1);
+                    };
+                    let watch = Watchpoint { addr, len, kind };
+                    let watchpoints = self.watchpoints();
+                    if name == "Z" {
+                        watchpoints.add(watch);
+                    } else {
+                        watchpoints.remove(&watch);
+                    }
+                    return ok();
+                }
+                // Software and hardware breakpoints are the same thing here
+                if kind != "0" && kind != "1" {
+                    return String::new();
+                }
+                if !self.valid_pc(addr) {
+                    return error(22);
+                }
+                if name == "Z" {
+                    self.breakpoints.insert(addr);
+                } else {
+                    self.breakpoints.remove(&addr);
+                }
+                ok()
+            }
+            "H" => ok(),
+            "T" => ok(),
+            "q" => self.query(command),
+            _ => String::new(),
+        }
+    }
+
+    fn query(&self, command: &str) -> String {
+        if command.starts_with("qSupported") {
+            "PacketSize=4000;qXfer:features:read+;swbreak+;hwbreak+;ReverseStep+;ReverseContinue+".to_string()
+        } else if command == "qAttached" {
+            "1".to_string()
+        } else if command == "qC" {
+            "QC1".to_string()
+        } else if command == "qfThreadInfo" {
+            "m1".to_string()
+        } else if command == "qsThreadInfo" {
+            "l".to_string()
+        } else if let Some(range) = command.strip_prefix("qXfer:features:read:target.xml:") {
+            let Some((offset, len)) = parse_range(range) else {
+                return error(1);
+            };
+            let xml = target_xml();
+            let start = (offset as usize).min(xml.len());
+            let end = (start + len as usize).min(xml.len());
+            let more = if end < xml.len() { "m" } else { "l" };
+            format!("{}{}", more, &xml[start..end])
+        } else {
+            String::new()
+        }
+    }
+
+    // Single steps, or continues until a breakpoint, an ebreak, the exit or a
+    // ^C, and returns the stop reply
+    fn resume<R: Read, W: Write>(&mut self, connection: &mut Connection<R, W>, single: bool) -> String {
+        if let Some(code) = self.exited {
+            return format!("W{:02x}", code as u8);
+        }
+        let mut executed = 0;
+        loop {
+            match self.history.step(self.vm, &mut NoSyscalls) {
+                None => {}
+                Some(ExecutionResult::Breakpoint) => return self.stop_reply(SIGTRAP),
+                Some(ExecutionResult::Watchpoint(hit)) => {
+                    let matching = self.watchpoints().triggered_by(&hit).copied();
+                    // gdb wants the watched address, not where the access started
+                    let (reason, addr) = match matching {
+                        Some(Watchpoint { kind: WatchKind::Read, addr, .. }) => ("rwatch", addr),
+                        Some(Watchpoint { kind: WatchKind::Access, addr, .. }) => ("awatch", addr),
+                        Some(Watchpoint { addr, .. }) => ("watch", addr),
+                        None => ("awatch", hit.addr),
+                    };
+                    return format!("T{:02x}{}:{:x};", SIGTRAP, reason, addr);
+                }
+                Some(ExecutionResult::Success(code)) => {
+                    self.exited = Some(code);
+                    return format!("W{:02x}", code as u8);
+                }
+                Some(ExecutionResult::Error(e)) => {
+                    let signal = match e {
+                        RubicVError::InvalidInstruction | RubicVError::IllegalInstruction => SIGILL,
+                        _ => SIGSEGV,
+                    };
+                    return self.stop_reply(signal);
+                }
+                Some(_) => return self.stop_reply(SIGTRAP),
+            }
+            executed += 1;
+            if single || self.breakpoints.contains(&self.pc()) {
+                return self.stop_reply(SIGTRAP);
+            }
+            if executed % INTERRUPT_POLL == 0 && connection.interrupted() {
+                return self.stop_reply(SIGINT);
+            }
+        }
+    }
+
+    // Steps back one instruction, or runs back to the previous breakpoint
+    fn reverse(&mut self, single: bool) -> String {
+        let moved = if single {
+            self.history.reverse_step(self.vm)
+        } else {
+            let breakpoints = &self.breakpoints;
+            self.history.reverse_until(self.vm, |vm| breakpoints.contains(&((vm.get_ppc() * 4) as u32)))
+        };
+        self.exited = None;
+        match moved {
+            Ok(true) => self.stop_reply(SIGTRAP),
+            Ok(false) => format!("T{:02x}replaylog:begin;", SIGTRAP),
+            Err(_) => error(14),
+        }
+    }
+
+    fn stop_reply(&self, signal: u8) -> String {
+        match self.exited {
+            Some(code) => format!("W{:02x}", code as u8),
+            None => format!("S{:02x}", signal),
+        }
+    }
+
+    fn pc(&self) -> u32 {
+        (self.vm.get_ppc() * 4) as u32
+    }
+
+    // Only the start of an instruction in the program can be jumped to
+    fn valid_pc(&self, pc: u32) -> bool {
+        pc.is_multiple_of(4) && (pc as usize / 4) < self.program.words.len()
+    }
+
+    fn watchpoints(&mut self) -> &mut Watchpoints {
+        self.vm.watchpoints().expect("the stub VM has watchpoint hooks")
+    }
+}
+
+fn target_xml() -> String {
+    let mut xml = String::from(concat!(
+        "<?xml version=\"1.0\"?><!DOCTYPE target SYSTEM \"gdb-target.dtd\">",
+        "<target version=\"1.0\"><architecture>riscv:rv32</architecture>",
+        "<feature name=\"org.gnu.gdb.riscv.cpu\">",
+    ));
+    for (r, name) in ABI_NAMES.iter().enumerate() {
+        let kind = match r {
+            1 => "code_ptr",
+            2 | 8 => "data_ptr",
+            _ => "int",
+        };
+        xml.push_str(&format!("<reg name=\"{}\" bitsize=\"32\" type=\"{}\" regnum=\"{}\"/>", name, kind, r));
+    }
+    xml.push_str(&format!("<reg name=\"pc\" bitsize=\"32\" type=\"code_ptr\" regnum=\"{}\"/>", PC_REGNUM));
+    xml.push_str("</feature></target>");
+    xml
+}
+
+fn ok() -> String {
+    "OK".to_string()
+}
+
+fn error(code: u8) -> String {
+    format!("E{:02x}", code)
+}
+
+// Registers and memory go over the wire in target (little-endian) byte order
+fn hex_u32(value: u32) -> String {
+    value.to_le_bytes().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn parse_hex_u32(text: &str) -> Option<u32> {
+    let bytes = hex_bytes(text)?;
+    Some(u32::from_le_bytes(bytes.try_into().ok()?))
+}
+
+fn hex_bytes(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len()).step_by(2).map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok()).collect()
+}
+
+// `addr,len` in hex
+fn parse_range(text: &str) -> Option<(u32, u32)> {
+    let (addr, len) = text.split_once(',')?;
+    Some((u32::from_str_radix(addr, 16).ok()?, u32::from_str_radix(len, 16).ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rubicv_emulator::asm::assemble;
+
+    const PROGRAM: &str = "
+    _start:
+        li a0, 1
+        call bump
+        lui a1, 0x2             // SCRATCH_START
+        sw a0, 0(a1)
+        li a7, 0
+        ecall
+    bump:
+        addi a0, a0, 41
+        ret
+    ";
+
+    fn with_stub(test: impl FnOnce(&mut Stub)) {
+        let program = PredecodedProgram::new(&assemble(PROGRAM).unwrap()).unwrap();
+        let mut memory = guest_memory(&[]).unwrap();
+        let mut vm = VM::<Watchpoints>::with_hooks(
+            memory.as_mut() as *mut [u8],
+            program.entrypoint,
+            &program.instructions,
+            Watchpoints::default(),
+        );
+        let vm: &mut dyn VMOperations = &mut vm;
+        let mut stub = Stub {
+            program: &program,
+            history: TimeTravel::new(vm, CHECKPOINT_INTERVAL),
+            vm,
+            breakpoints: BTreeSet::new(),
+            exited: None,
+        };
+        test(&mut stub);
+    }
+
+    fn packet(body: &str) -> String {
+        format!("${}#{:02x}", body, checksum_of(body.as_bytes()))
+    }
+
+    // Serves `input` and returns everything written back
+    fn exchange(stub: &mut Stub, input: &str) -> String {
+        let mut connection = Connection {
+            reader: BufReader::new(input.as_bytes()),
+            writer: Vec::new(),
+            interrupts: None,
+        };
+        stub.serve(&mut connection).unwrap();
+        String::from_utf8(connection.writer).unwrap()
+    }
+
+    #[test]
+    fn test_checksum() {
+        assert_eq!(checksum_of(b""), 0);
+        assert_eq!(checksum_of(b"OK"), 0x9a);
+        assert_eq!(checksum_of(b"?"), 0x3f);
+        // Sums wrap around
+        assert_eq!(checksum_of(b"\xff\x02"), 0x01);
+        assert_eq!(packet("g"), "$g#67");
+    }
+
+    #[test]
+    fn test_serve_acks_and_replies() {
+        with_stub(|stub| {
+            let input = ["+", &packet("?"), &packet("Z0,18,4"), &packet("c"), &packet("p0a"), &packet("c"), &packet("k")].concat();
+            let expected = ["+", &packet("S05"), "+", &packet("OK"), "+", &packet("S05"), "+", &packet("01000000"), "+", &packet("W2a"), "+"].concat();
+            assert_eq!(exchange(stub, &input), expected);
+        });
+    }
+
+    #[test]
+    fn test_bad_checksums_are_nacked() {
+        with_stub(|stub| {
+            // The corrupted packet is dropped and gdb retransmits it
+            assert_eq!(exchange(stub, &["$?#00", &packet("?")].concat()), ["-+", &packet("S05")].concat());
+            assert_eq!(exchange(stub, "$?#zz"), "-");
+            // gdb hanging up mid-packet ends the session
+            assert_eq!(exchange(stub, "$?"), "");
+            assert_eq!(exchange(stub, "noise\x03"), "");
+        });
+    }
+
+    #[test]
+    fn test_pc_writes_are_validated() {
+        with_stub(|stub| {
+            assert_eq!(stub.handle("P20=18000000"), ok());
+            assert_eq!(stub.pc(), 0x18);
+            // Misaligned, and past the end of the program
+            assert_eq!(stub.handle("P20=1a000000"), error(22));
+            assert_eq!(stub.handle("P20=00100000"), error(22));
+            assert_eq!(stub.pc(), 0x18);
+
+            let registers = |pc: u32| ["00000000".repeat(10), hex_u32(7), "00000000".repeat(21), hex_u32(pc)].concat();
+            assert_eq!(stub.handle(&format!("G{}", registers(2))), error(22));
+            assert_eq!(stub.vm.get_register(10), 0);
+            assert_eq!(stub.handle(&format!("G{}", registers(4))), ok());
+            assert_eq!(stub.vm.get_register(10), 7);
+            assert_eq!(stub.pc(), 4);
+            assert_eq!(stub.handle("p20"), hex_u32(4));
+
+            assert_eq!(stub.handle("Z0,2,4"), error(22));
+            assert_eq!(stub.handle("Z0,1000,4"), error(22));
+            assert!(stub.breakpoints.is_empty());
+        });
+    }
+
+    #[test]
+    fn test_malformed_packets() {
+        with_stub(|stub| {
+            assert_eq!(stub.handle(""), "");
+            assert_eq!(stub.handle("X"), "");
+            assert_eq!(stub.handle("G0000"), error(1));
+            assert_eq!(stub.handle("p"), error(1));
+            assert_eq!(stub.handle("p21"), error(1));
+            assert_eq!(stub.handle("Pzz=00000000"), error(1));
+            assert_eq!(stub.handle("P0a=123"), error(1));
+            assert_eq!(stub.handle("m2000"), error(14));
+            assert_eq!(stub.handle("mffffffff,2"), error(14));
+            assert_eq!(stub.handle("M2000,2"), error(1));
+            assert_eq!(stub.handle("M2000,2:ab"), error(14));
+            // Code cannot be patched
+            assert_eq!(stub.handle("M0,1:00"), error(14));
+            assert_eq!(stub.handle("Z0"), error(1));
+            assert_eq!(stub.handle("Z2,2000"), error(1));
+            assert_eq!(stub.handle("Z9,0,4"), "");
+            assert_eq!(stub.handle("qXfer:features:read:target.xml:zz"), error(1));
+        });
+    }
+
+    #[test]
+    fn test_memory_and_registers() {
+        with_stub(|stub| {
+            assert_eq!(stub.handle("M2000,4:78563412"), ok());
+            assert_eq!(stub.vm.read_u32(SCRATCH_START), 0x12345678);
+            assert_eq!(stub.handle("m2000,4"), "78563412");
+            assert_eq!(stub.handle("P0b=efbeadde"), ok());
+            assert_eq!(stub.handle("p0b"), "efbeadde");
+            let registers = stub.handle("g");
+            assert_eq!(registers.len(), 33 * 8);
+            assert_eq!(&registers[11 * 8..12 * 8], "efbeadde");
+        });
+    }
+}
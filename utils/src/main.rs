@@ -1,37 +1,186 @@
+mod dap;
+mod debug;
+mod dwarf;
+mod gdb;
+
 use std::env;
 use goblin::Object;
+use goblin::elf::{section_header, sym, Elf};
 use std::fs;
+use rubicv_emulator::aot;
+use rubicv_emulator::asm;
+use rubicv_emulator::disasm::Listing;
+use rubicv_emulator::errors::RubicVError;
 use rubicv_emulator::instructions::PredecodedProgram;
+use rubicv_emulator::memory::{ARGS_START, MEMORY_SIZE};
+
+fn usage(program: &str) -> ! {
+    eprintln!("Usage: {} <elf-file> <output-file>", program);
+    eprintln!("       {} disasm [--numeric] <bin-file>", program);
+    eprintln!("       {} asm <source-file> <output-file>", program);
+    eprintln!("       {} verify <bin-or-elf-file>", program);
+    eprintln!("       {} aot <bin-or-elf-file> <output.rs>", program);
+    eprintln!("       {} debug <bin-or-elf-file> [args...]", program);
+    eprintln!("       {} gdb [--port N | --stdio] <bin-or-elf-file> [args...]", program);
+    eprintln!("       {} dap", program);
+    std::process::exit(1);
+}
+
+const SUBCOMMANDS: [&str; 7] = ["disasm", "asm", "verify", "aot", "debug", "gdb", "dap"];
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let args: Vec<String> = env::args().collect();
-    if args.len() != 3 {
-        eprintln!("Usage: {} <elf-file> <output-file>", args[0]);
-        std::process::exit(1);
+    match args.get(1).map(String::as_str) {
+        Some("disasm") => disasm(&args),
+        Some("asm") if args.len() == 4 => assemble(&args[2], &args[3]),
+        Some("verify") if args.len() == 3 => verify(&args[2]),
+        Some("aot") if args.len() == 4 => translate(&args[2], &args[3]),
+        Some("debug") if args.len() >= 3 => debug::run(&args[2], &args[3..]),
+        Some("gdb") if args.len() >= 3 => gdb::run(&args[2..]),
+        Some("dap") if args.len() == 2 => dap::run(),
+        // A subcommand with the wrong arguments is not a file to convert
+        Some(elf) if args.len() == 3 && !SUBCOMMANDS.contains(&elf) => convert(&args[1], &args[2]),
+        _ => usage(&args[0]),
     }
+}
 
-    let buffer = fs::read(&args[1])?;
+// ELF -> [entry][text] RubicV binary
+fn convert(elf_path: &str, output_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let buffer = fs::read(elf_path)?;
 
     if let Object::Elf(elf) = Object::parse(&buffer)? {
-        let entry = elf.entry as u32;
-
-        // Extract .text section
-        let text_data = if let Some(text) = elf.section_headers.iter()
-            .find(|&s| elf.shdr_strtab.get_at(s.sh_name) == Some(".text")) {
-            &buffer[text.sh_offset as usize..(text.sh_offset + text.sh_size) as usize]
-        } else {
-            &[]
-        };
-
-        let text_vec = text_data.to_vec();
-        let mut rubicv_elf_bytes = vec![];
-        rubicv_elf_bytes.extend_from_slice(entry.to_le_bytes().as_ref());
-        rubicv_elf_bytes.extend_from_slice(&text_vec);
+        let rubicv_elf_bytes = elf_to_rubicv(&elf, &buffer);
         let _predecoded_program = PredecodedProgram::new(&rubicv_elf_bytes).unwrap();
-        fs::write(&args[2], &rubicv_elf_bytes)?;
+        fs::write(output_path, &rubicv_elf_bytes)?;
         println!("{:?}", rubicv_elf_bytes);
-        println!("Raw bytes saved to {}", args[2]);
+        println!("Raw bytes saved to {}", output_path);
+    }
+
+    Ok(())
+}
+
+fn elf_to_rubicv(elf: &Elf, buffer: &[u8]) -> Vec<u8> {
+    let entry = elf.entry as u32;
+
+    // Extract .text section
+    let text_data = if let Some(text) = elf.section_headers.iter()
+        .find(|&s| elf.shdr_strtab.get_at(s.sh_name) == Some(".text")) {
+        &buffer[text.sh_offset as usize..(text.sh_offset + text.sh_size) as usize]
+    } else {
+        &[]
+    };
+
+    let mut rubicv_elf_bytes = vec![];
+    rubicv_elf_bytes.extend_from_slice(entry.to_le_bytes().as_ref());
+    rubicv_elf_bytes.extend_from_slice(text_data);
+    rubicv_elf_bytes
+}
+
+// A guest for the debuggers: either an ELF, converted on the fly and keeping
+// its symbols and debug info, or a RubicV binary without any
+pub struct Guest {
+    pub bytes: Vec<u8>,
+    pub symbols: Vec<(String, u32)>,
+    pub elf: Option<Vec<u8>>,
+}
+
+pub fn load_guest(path: &str) -> Result<Guest, Box<dyn std::error::Error>> {
+    let buffer = fs::read(path)?;
+    if !buffer.starts_with(b"\x7fELF") {
+        return Ok(Guest { bytes: buffer, symbols: vec![], elf: None });
     }
+    let Object::Elf(elf) = Object::parse(&buffer)? else {
+        return Err(format!("{}: not an ELF file", path).into());
+    };
+    let bytes = elf_to_rubicv(&elf, &buffer);
+    let mut symbols: Vec<(String, u32)> = elf.syms.iter()
+        .filter(|sym| matches!(sym.st_type(), sym::STT_FUNC | sym::STT_OBJECT | sym::STT_NOTYPE))
+        .filter(|sym| sym.st_shndx != section_header::SHN_UNDEF as usize && sym.st_shndx != section_header::SHN_ABS as usize)
+        .filter_map(|sym| {
+            // Skip mapping symbols and assembler-local labels
+            let name = elf.strtab.get_at(sym.st_name)?;
+            (!name.is_empty() && !name.starts_with('$') && !name.starts_with(".L"))
+                .then(|| (name.to_string(), sym.st_value as u32))
+        })
+        .collect();
+    symbols.sort_by_key(|(_, addr)| *addr);
+    Ok(Guest { bytes, symbols, elf: Some(buffer) })
+}
+
+// A fresh slab with the u32 `args` copied to ARGS_START
+pub fn guest_memory(args: &[String]) -> Result<Box<[u8]>, Box<dyn std::error::Error>> {
+    let mut memory = vec![0u8; MEMORY_SIZE as usize].into_boxed_slice();
+    for (i, arg) in args.iter().enumerate() {
+        let value = parse_u32(arg).ok_or_else(|| format!("bad argument: {}", arg))?;
+        let at = ARGS_START as usize + i * 4;
+        memory[at..at + 4].copy_from_slice(&value.to_le_bytes());
+    }
+    Ok(memory)
+}
+
+// Decimal or 0x-prefixed hex
+pub fn parse_u32(text: &str) -> Option<u32> {
+    match text.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+fn disasm(args: &[String]) -> Result<(), Box<dyn std::error::Error>> {
+    let (numeric, path) = match &args[2..] {
+        [flag, path] if flag == "--numeric" => (true, path),
+        [path] => (false, path),
+        _ => usage(&args[0]),
+    };
+
+    let bytes = fs::read(path)?;
+    let program = PredecodedProgram::new(&bytes)
+        .map_err(|e| format!("{}: not a RubicV binary ({:?})", path, e))?;
+    let listing = Listing::new(&program);
+    if numeric {
+        print!("{}", listing.numeric());
+    } else {
+        print!("{}", listing);
+    }
+    Ok(())
+}
+
+fn assemble(source_path: &str, output_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let source = fs::read_to_string(source_path)?;
+    let bytes = asm::assemble(&source).map_err(|e| match e {
+        RubicVError::AssemblerError { line, reason } => format!("{}:{}: {}", source_path, line, reason),
+        e => format!("{}: {:?}", source_path, e),
+    })?;
+    fs::write(output_path, &bytes)?;
+    println!("{} bytes saved to {}", bytes.len(), output_path);
+    Ok(())
+}
+
+// Writes the program as a Rust module, see rubicv_emulator::aot
+fn translate(path: &str, output_path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let guest = load_guest(path)?;
+    let program = PredecodedProgram::new(&guest.bytes)
+        .map_err(|e| format!("{}: not a RubicV program ({:?})", path, e))?;
+    fs::write(output_path, aot::translate(&program))?;
+    println!("{} instructions translated to {}", program.words.len(), output_path);
+    Ok(())
+}
+
+// Runs the load-time verifier and prints the capability report
+fn verify(path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let guest = load_guest(path)?;
+    let program = PredecodedProgram::new(&guest.bytes)
+        .map_err(|e| format!("{}: not a RubicV program ({:?})", path, e))?;
+    let capabilities = program.verify().map_err(|e| match e {
+        RubicVError::InvalidProgram { pc, reason } => format!("{}: 0x{:08x}: {}", path, pc, reason),
+        e => format!("{}: {:?}", path, e),
+    })?;
 
+    let kinds: Vec<String> = capabilities.kinds.iter().map(|kind| format!("{:?}", kind).to_lowercase()).collect();
+    let syscalls: Vec<String> = capabilities.syscalls.iter().map(|syscall| syscall.to_string()).collect();
+    println!("entry       0x{:08x}, {} instructions", program.entrypoint * 4, program.words.len());
+    println!("kinds       {}", kinds.join(" "));
+    println!("syscalls    {}{}", syscalls.join(" "), if capabilities.dynamic_syscalls { " and numbers set at run time" } else { "" });
+    println!("indirect    {}", if capabilities.indirect_jumps { "jumps to run-time targets" } else { "none" });
     Ok(())
 }
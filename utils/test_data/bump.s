@@ -0,0 +1,20 @@
+# Debug adapter fixture, built with
+#   llvm-mc -triple=riscv32 -mattr=+m,-relax -filetype=obj -g bump.s -o bump.o
+#   rust-lld -flavor gnu -Ttext=0 --image-base=0 -e _start --no-relax bump.o -o bump.elf
+    .text
+    .cfi_sections .debug_frame
+    .globl _start
+_start:
+    li a0, 1
+    call bump
+    lui a1, 0x2
+    sw a0, 0(a1)
+    li a7, 0
+    ecall
+
+    .globl bump
+bump:
+    .cfi_startproc
+    addi a0, a0, 41
+    ret
+    .cfi_endproc